@@ -0,0 +1,335 @@
+/*
+    This file is part of grunge, a coherent noise generation library.
+*/
+
+//! A command-line tool for rendering and inspecting noise modules described in
+//! JSON, as written by the library's `serial` module.
+//!
+//! ```ignore
+//! grunge render terrain.json --size 1024x1024 --bounds -4,-4,4,4 --out out.png
+//! grunge stats terrain.json --size 256x256 --bins 20
+//! ```
+//!
+//! The description may be either a single module or a graph. Images map the
+//! output range (by default [-1, 1]) onto black through white; files ending in
+//! `.raw` are instead written as a heightmap of little-endian 32-bit floats,
+//! one row at a time.
+
+extern crate getopts;
+extern crate image;
+extern crate serialize;
+extern crate grunge;
+
+use std::io::File;
+use std::io::stdio::stderr;
+use std::os;
+
+use getopts::{optopt, optflag, getopts, usage, OptGroup, Matches};
+use image::GenericImage;
+use serialize::json;
+
+use grunge::modules::NoiseModule;
+use grunge::serial;
+
+/// The region of the plane to sample, and the number of samples along each
+/// axis.
+struct Grid {
+    width: uint,
+    height: uint,
+    bounds: (f32, f32, f32, f32),
+}
+
+impl Grid {
+    /// The point sampled for the pixel at column `i` and row `j`. The first row
+    /// lies along the lower y bound.
+    fn point(&self, i: uint, j: uint) -> (f32, f32) {
+        let (x0, y0, x1, y1) = self.bounds;
+        (x0 + (x1 - x0) * i as f32 / self.width as f32,
+         y0 + (y1 - y0) * j as f32 / self.height as f32)
+    }
+
+    /// Samples `noise` at every point of the grid, row by row.
+    fn sample(&self, noise: &NoiseModule) -> Result<Vec<f32>, String> {
+        let mut values = Vec::with_capacity(self.width * self.height);
+        for j in range(0, self.height) {
+            for i in range(0, self.width) {
+                let (x, y) = self.point(i, j);
+                match noise.generate_2d(x, y) {
+                    Ok(value) => values.push(value),
+                    Err(e) => return Err(e.to_string())
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+fn options() -> Vec<OptGroup> {
+    vec![
+        optopt("s", "size", "The number of samples along each axis (default 512x512).", "WxH"),
+        optopt("b", "bounds", "The region to sample (default 0,0,1,1).", "X0,Y0,X1,Y1"),
+        optopt("o", "out", "The file to write (render only; default out.png).", "FILE"),
+        optopt("r", "range", "The output range mapped onto black and white, or \"auto\" for the module's bounds (render only; default -1,1).", "LO,HI"),
+        optopt("n", "bins", "The number of histogram bins (stats only; default 10).", "N"),
+        optflag("h", "help", "Print this message.")
+    ]
+}
+
+fn print_usage(program: &str, opts: &[OptGroup]) {
+    let brief = format!("Usage: {} (render | stats) FILE [options]", program);
+    println!("{}", usage(brief.as_slice(), opts));
+}
+
+/// Parses a list of `count` numbers separated by `separator`.
+fn parse_list<T: FromStr>(text: &str, separator: char, count: uint)
+    -> Option<Vec<T>> {
+    let values: Vec<Option<T>> = text.split(separator)
+        .map(|part| from_str(part.trim())).collect();
+    if values.len() == count && values.iter().all(|value| value.is_some()) {
+        Some(values.move_iter().map(|value| value.unwrap()).collect())
+    } else {
+        None
+    }
+}
+
+fn parse_grid(matches: &Matches) -> Result<Grid, String> {
+    let size = matches.opt_str("size").unwrap_or("512x512".to_string());
+    let size = match parse_list::<uint>(size.as_slice(), 'x', 2) {
+        Some(ref size) if size[0] > 0 && size[1] > 0 => (size[0], size[1]),
+        _ => return Err(format!("Invalid size: {}", size))
+    };
+
+    let bounds = matches.opt_str("bounds").unwrap_or("0,0,1,1".to_string());
+    let bounds = match parse_list::<f32>(bounds.as_slice(), ',', 4) {
+        Some(b) => (b[0], b[1], b[2], b[3]),
+        None => return Err(format!("Invalid bounds: {}", bounds))
+    };
+
+    let (width, height) = size;
+    Ok(Grid { width: width, height: height, bounds: bounds })
+}
+
+/// Loads a module or graph description from a file.
+fn load(path: &str) -> Result<Box<NoiseModule>, String> {
+    let text = match File::open(&Path::new(path)).read_to_string() {
+        Ok(text) => text,
+        Err(e) => return Err(format!("Could not read {}: {}", path, e))
+    };
+    let json = match json::from_str(text.as_slice()) {
+        Ok(json) => json,
+        Err(e) => return Err(format!("Could not parse {}: {}", path, e))
+    };
+
+    let result = if json.find(&"nodes".to_string()).is_some() {
+        serial::graph_from_json(&json)
+    } else {
+        serial::from_json(&json)
+    };
+    result.map_err(|e| format!("Could not load {}: {}", path, e))
+}
+
+/// Parses the output range mapped onto black and white.
+fn parse_range(noise: &NoiseModule, matches: &Matches)
+    -> Result<(f32, f32), String> {
+    let range = matches.opt_str("range").unwrap_or("-1,1".to_string());
+    let (lower, upper) = if range.as_slice() == "auto" {
+        noise.bounds()
+    } else {
+        match parse_list::<f32>(range.as_slice(), ',', 2) {
+            Some(r) => (r[0], r[1]),
+            None => return Err(format!("Invalid range: {}", range))
+        }
+    };
+    if !(upper > lower) || lower.is_infinite() || upper.is_infinite() {
+        return Err(format!("Invalid range: ({}, {})", lower, upper));
+    }
+    Ok((lower, upper))
+}
+
+fn render(noise: &NoiseModule, matches: &Matches) -> Result<(), String> {
+    let grid = try!(parse_grid(matches));
+    let out = matches.opt_str("out").unwrap_or("out.png".to_string());
+
+    // Raw heightmaps keep the full precision of the output, and so have no
+    // range. Everything is checked before the file is created, so that a bad
+    // option does not leave an empty file behind.
+    let raw = out.as_slice().ends_with(".raw");
+    let levels = if raw {
+        None
+    } else {
+        Some(try!(parse_range(noise, matches)))
+    };
+    let values = try!(grid.sample(noise));
+
+    let mut file = match File::create(&Path::new(out.as_slice())) {
+        Ok(file) => file,
+        Err(e) => return Err(format!("Could not create {}: {}", out, e))
+    };
+
+    let (lower, upper) = match levels {
+        Some(levels) => levels,
+        None => {
+            for value in values.iter() {
+                match file.write_le_f32(*value) {
+                    Ok(()) => (),
+                    Err(e) => return Err(format!("Could not write {}: {}",
+                                                 out, e))
+                }
+            }
+            return Ok(());
+        }
+    };
+
+    let mut imbuf = image::ImageBuf::new(grid.width as u32, grid.height as u32);
+    for j in range(0, grid.height) {
+        for i in range(0, grid.width) {
+            let value = (values[j * grid.width + i] - lower) / (upper - lower);
+            let value = value.max(0.0).min(1.0) * 255.0;
+            imbuf.put_pixel(i as u32, j as u32, image::Luma(value as u8));
+        }
+    }
+
+    match image::ImageLuma8(imbuf).save(file, image::PNG) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write {}: {}", out, e))
+    }
+}
+
+fn stats(noise: &NoiseModule, matches: &Matches) -> Result<(), String> {
+    let grid = try!(parse_grid(matches));
+    let values = try!(grid.sample(noise));
+    let bins = match matches.opt_str("bins") {
+        Some(bins) => match from_str::<uint>(bins.as_slice()) {
+            Some(bins) if bins > 0 => bins,
+            _ => return Err(format!("Invalid number of bins: {}", bins))
+        },
+        None => 10
+    };
+
+    let min = values.iter().fold(Float::infinity(), |a: f32, &b| a.min(b));
+    let max = values.iter().fold(Float::neg_infinity(), |a: f32, &b| a.max(b));
+    let mean = values.iter().fold(0.0, |a, &b| a + b) / values.len() as f32;
+    let variance = values.iter().fold(0.0, |a, &b| a + (b - mean) * (b - mean))
+        / values.len() as f32;
+    let (lower, upper) = noise.bounds();
+
+    println!("samples:  {}", values.len());
+    println!("min:      {}", min);
+    println!("max:      {}", max);
+    println!("mean:     {}", mean);
+    println!("std dev:  {}", variance.sqrt());
+    println!("bounds:   [{}, {}]", lower, upper);
+
+    // Histogram of the sampled range, with bars scaled to the largest bin.
+    let mut counts = Vec::from_elem(bins, 0u);
+    let width = (max - min) / bins as f32;
+    for value in values.iter() {
+        let bin = if width > 0.0 { ((*value - min) / width) as uint } else { 0 };
+        *counts.get_mut(bin.min(bins - 1)) += 1;
+    }
+
+    let largest = counts.iter().fold(1u, |a, &b| a.max(b));
+    println!("");
+    for (bin, count) in counts.iter().enumerate() {
+        let start = min + width * bin as f32;
+        let bar = "#".repeat(*count * 50 / largest);
+        println!("[{:9.4}, {:9.4}) {:8} {}", start, start + width, count, bar);
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let args = os::args();
+    let program = args[0].clone();
+    let opts = options();
+
+    let matches = match getopts(args.tail(), opts.as_slice()) {
+        Ok(matches) => matches,
+        Err(e) => {
+            let _ = writeln!(stderr(), "{}", e);
+            print_usage(program.as_slice(), opts.as_slice());
+            os::set_exit_status(2);
+            return;
+        }
+    };
+
+    if matches.opt_present("help") || matches.free.len() != 2 {
+        print_usage(program.as_slice(), opts.as_slice());
+        if !matches.opt_present("help") {
+            os::set_exit_status(2);
+        }
+        return;
+    }
+
+    let command = matches.free[0].as_slice();
+    let result = load(matches.free[1].as_slice()).and_then(|noise| {
+        match command {
+            "render" => render(&*noise, &matches),
+            "stats" => stats(&*noise, &matches),
+            _ => Err(format!("Unknown command: {}", command))
+        }
+    });
+
+    match result {
+        Ok(()) => (),
+        Err(e) => {
+            let _ = writeln!(stderr(), "{}", e);
+            os::set_exit_status(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use getopts::{getopts, Matches};
+
+    use grunge::modules::ConstNoise;
+
+    use super::{options, parse_list, parse_grid, parse_range};
+
+    fn matches(args: &[&str]) -> Matches {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string())
+                                    .collect();
+        getopts(args.as_slice(), options().as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(parse_list::<uint>("512x256", 'x', 2), Some(vec![512, 256]));
+        assert_eq!(parse_list::<f32>(" -1, 0.5 ", ',', 2),
+                   Some(vec![-1.0, 0.5]));
+        assert_eq!(parse_list::<f32>("1,2,3", ',', 2), None);
+        assert_eq!(parse_list::<f32>("1,", ',', 2), None);
+        assert_eq!(parse_list::<uint>("-1x2", 'x', 2), None);
+    }
+
+    #[test]
+    fn test_parse_grid() {
+        let grid = parse_grid(&matches(&[])).unwrap();
+        assert_eq!((grid.width, grid.height), (512, 512));
+        assert_eq!(grid.bounds, (0.0, 0.0, 1.0, 1.0));
+
+        let grid = parse_grid(&matches(&["--size", "4x2",
+                                         "--bounds", "-4,-2,4,2"])).unwrap();
+        assert_eq!((grid.width, grid.height), (4, 2));
+        assert_eq!(grid.point(0, 0), (-4.0, -2.0));
+        assert_eq!(grid.point(2, 1), (0.0, 0.0));
+
+        assert!(parse_grid(&matches(&["--size", "0x2"])).is_err());
+        assert!(parse_grid(&matches(&["--bounds", "0,0,1"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let noise = ConstNoise::new(0.5);
+        assert_eq!(parse_range(&noise, &matches(&[])), Ok((-1.0, 1.0)));
+        assert_eq!(parse_range(&noise, &matches(&["--range", "0,2"])),
+                   Ok((0.0, 2.0)));
+        assert!(parse_range(&noise, &matches(&["--range", "1,0"])).is_err());
+        assert!(parse_range(&noise, &matches(&["--range", "x"])).is_err());
+
+        // The bounds of a constant are an empty range.
+        assert!(parse_range(&noise, &matches(&["--range", "auto"])).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+/*
+    This file is part of grunge, a coherent noise generation library.
+*/
+
+//! Dirty little hacks for dealing with boxes and trait type-erasure, shared by
+//! the modules that hold their sources as trait objects.
+//!
+//! A `Box<NoiseModule>` is itself a NoiseModule, so that the generic modifiers
+//! can hold one as their source; ClampedNoise is Clamped with a boxed source,
+//! and so on.
+
+use serialize::json::Json;
+
+use primitives::{NoiseModule, VectorNoiseModule};
+use modifiers::Modifiable;
+use shader::{Program, Register};
+
+/// Clones `t`. Calling this on a `&Box<NoiseModule>` clones the box, rather
+/// than auto-dereferencing to the trait object, which cannot be cloned.
+pub fn clone<T: Clone>(t: &T) -> T { t.clone() }
+
+impl Clone for Box<NoiseModule> {
+    fn clone(&self) -> Box<NoiseModule> { (**self).to_box() }
+}
+
+impl Clone for Box<VectorNoiseModule> {
+    fn clone(&self) -> Box<VectorNoiseModule> { (**self).to_box() }
+}
+
+impl NoiseModule for Box<NoiseModule> {
+    #[inline]
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        (**self).generate_2d(x, y)
+    }
+
+    #[inline]
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        (**self).generate_3d(x, y, z)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        (**self).bounds()
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        (**self).to_shader(program, x, y)
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        (**self).to_json()
+    }
+
+    /// Clones the module inside the box, rather than boxing the box.
+    fn to_box(&self) -> Box<NoiseModule> {
+        (**self).to_box()
+    }
+}
+
+impl Modifiable for Box<NoiseModule> {}
@@ -0,0 +1,833 @@
+/*
+    This file is part of grunge, a coherent noise generation library.
+*/
+
+//! Building noise modules from short expressions, such as
+//!
+//! ```ignore
+//! clamp(scalebias(pink(seed=3, octaves=8), 0.5, 0.5), 0, 1) * cylinder(2.0)
+//! ```
+//!
+//! Each function builds the module of the same type in the [serial]
+//! (../serial/index.html) module, and takes the fields of its description as
+//! arguments, with the same names and defaults. The first few arguments may be
+//! given by position instead of by name: modifiers take their source module
+//! followed by the arguments of the corresponding method of Modifiable, while
+//! fractals take only their seed. Vectors and lists are written in brackets, as
+//! in `translate(pink(), [0.5, 2])`, and functions without arguments may leave
+//! off the parentheses. The flow parameters of a fractal are written as a call,
+//! as in `swiss(flow=flow(time=2))`, and turbulence takes a `seed` in place of
+//! its distortion modules.
+//!
+//! Closures registered with a [Registry](../serial/struct.Registry.html) are
+//! written by name, as `function(disc)` for FunctionNoise and
+//! `modifier(pink(), shift)` for ModifierNoise, and can be used in
+//! expressions compiled with `compile_with` and the same Registry.
+//!
+//! Modules may be added, subtracted and multiplied with `+`, `-` and `*`,
+//! which build CombinedNoise, or negated with a leading `-`. A number used in
+//! place of a module stands for ConstNoise.
+//!
+//! Errors are reported with the span of the expression they refer to, as byte
+//! offsets into the text.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use grunge::modules::NoiseModule;
+//! use grunge::expr;
+//!
+//! let noise = expr::compile("clamp(pink(seed=3) * 2, 0, 1)").unwrap();
+//! println!("{}", noise.generate_2d(1.0, -1.0));
+//!
+//! let error = expr::compile("clamp(pink(sed=3), 0, 1)").err().unwrap();
+//! assert_eq!(error.span, expr::Span { start: 11, end: 14 });
+//! ```
+
+use std::cell::RefCell;
+use std::default::Default;
+
+use primitives::{NoiseModule, VectorNoiseModule};
+use fractal::Flow;
+use geometry::ConstNoise;
+use modifiers::{ScaledBiasedNoise, CombinedNoise, Operation, Sum, Difference,
+                Product, TurbulenceNoise, Interpolation, CatmullRom,
+                MonotoneCubic};
+use serial::Registry;
+use reader;
+use reader::{FieldReader, Module, ScalarModule, VectorModule};
+
+/// A range of byte offsets into the text of an expression.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Span {
+    /// The offset of the first byte.
+    pub start: uint,
+
+    /// The offset one past the last byte.
+    pub end: uint,
+}
+
+/// An error in an expression, along with the span of the text it refers to.
+#[deriving(Clone, PartialEq, Show)]
+pub struct ExprError {
+    /// The part of the expression in error.
+    pub span: Span,
+
+    /// What went wrong.
+    pub kind: ErrorKind,
+}
+
+/// The ways in which compiling an expression can fail.
+#[deriving(Clone, PartialEq, Show)]
+pub enum ErrorKind {
+    /// A character that cannot begin any token.
+    UnexpectedCharacter(char),
+
+    /// A number that cannot be parsed. Contains its text.
+    InvalidNumber(String),
+
+    /// A token that does not fit the grammar at its position. Contains its
+    /// text.
+    UnexpectedToken(String),
+
+    /// The expression ends before it is complete.
+    UnexpectedEnd,
+
+    /// A call to a function that does not exist, or a name that is not
+    /// registered with the Registry. Contains the name.
+    UnknownFunction(String),
+
+    /// A named argument that the function does not take. Contains the names of
+    /// the function and the argument.
+    UnknownArgument(String, String),
+
+    /// An argument given more than once. Contains the names of the function
+    /// and the argument.
+    DuplicateArgument(String, String),
+
+    /// More arguments given by position than the function allows. Contains
+    /// the name of the function.
+    TooManyArguments(String),
+
+    /// A required argument that was not given. Contains the names of the
+    /// function and the argument.
+    MissingArgument(String, String),
+
+    /// An argument with the wrong type or an invalid value. Contains the names
+    /// of the function and the argument.
+    InvalidArgument(String, String),
+
+    /// A list, or a vector-valued module, used where a module is expected.
+    ExpectedModule,
+
+    /// Something other than a vector-valued module used where one is expected.
+    ExpectedVectorModule,
+}
+
+/// Compiles an expression into the module it describes.
+pub fn compile(text: &str) -> Result<Box<NoiseModule>, ExprError> {
+    compile_with(text, &Registry::new())
+}
+
+/// Compiles an expression into the module it describes, looking up the
+/// functions it refers to by name in the given Registry.
+pub fn compile_with(text: &str, registry: &Registry)
+    -> Result<Box<NoiseModule>, ExprError> {
+    let mut parser = Parser {
+        text: text, tokens: try!(tokenize(text)), position: 0
+    };
+    let expr = try!(parser.sum());
+    match *parser.peek() {
+        (EndToken, _) => scalar(&expr, registry),
+        _ => Err(parser.unexpected())
+    }
+}
+
+fn error(span: Span, kind: ErrorKind) -> ExprError {
+    ExprError { span: span, kind: kind }
+}
+
+// Tokens
+
+#[deriving(Clone, PartialEq, Show)]
+enum Token {
+    NumberToken(f64),
+    NameToken(String),
+    Symbol(char),
+    EndToken,
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+/// The offset of the first byte from `i` onwards that is not a digit.
+fn skip_digits(bytes: &[u8], mut i: uint) -> uint {
+    while i < bytes.len() && (bytes[i] as char).is_digit() {
+        i += 1;
+    }
+    i
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, Span)>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut i = 0u;
+
+    while i < text.len() {
+        let start = i;
+        let range = text.char_range_at(i);
+        let c = range.ch;
+
+        if c.is_whitespace() {
+            i = range.next;
+            continue;
+        }
+
+        let token = if c.is_digit() || c == '.' {
+            // Digits, an optional fraction and an optional exponent.
+            let bytes = text.as_bytes();
+            i = skip_digits(bytes, i);
+            if i < bytes.len() && bytes[i] == b'.' {
+                i = skip_digits(bytes, i + 1);
+            }
+            if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+                i += 1;
+                if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+                    i += 1;
+                }
+                i = skip_digits(bytes, i);
+            }
+            let number = text.slice(start, i);
+            match from_str::<f64>(number) {
+                Some(value) => NumberToken(value),
+                None => return Err(error(Span { start: start, end: i },
+                                         InvalidNumber(number.to_string())))
+            }
+        } else if is_name_start(c) {
+            while i < text.len() {
+                let range = text.char_range_at(i);
+                if !is_name_start(range.ch) && !range.ch.is_digit() {
+                    break;
+                }
+                i = range.next;
+            }
+            NameToken(text.slice(start, i).to_string())
+        } else if "()[],=+-*".contains_char(c) {
+            i = range.next;
+            Symbol(c)
+        } else {
+            return Err(error(Span { start: start, end: range.next },
+                             UnexpectedCharacter(c)));
+        };
+
+        tokens.push((token, Span { start: start, end: i }));
+    }
+
+    tokens.push((EndToken, Span { start: text.len(), end: text.len() }));
+    Ok(tokens)
+}
+
+// Syntax
+
+#[deriving(Clone, PartialEq, Show)]
+enum Node {
+    Constant(f64),
+    Name(String),
+    List(Vec<Expr>),
+    Call(String, Vec<Argument>),
+    Negate(Box<Expr>),
+    /// A binary operation, performed by CombinedNoise.
+    Binary(Operation, Box<Expr>, Box<Expr>),
+}
+
+#[deriving(Clone, PartialEq, Show)]
+struct Expr {
+    node: Node,
+    span: Span,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+struct Argument {
+    name: Option<(String, Span)>,
+    value: Expr,
+}
+
+/// A recursive descent parser over the tokens of an expression, which always
+/// end with EndToken.
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<(Token, Span)>,
+    position: uint,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> &(Token, Span) {
+        &self.tokens[self.position]
+    }
+
+    fn advance(&mut self) -> (Token, Span) {
+        let token = self.tokens[self.position].clone();
+        if self.position + 1 < self.tokens.len() {
+            self.position += 1;
+        }
+        token
+    }
+
+    /// Whether the next token is the given symbol.
+    fn at(&self, symbol: char) -> bool {
+        match *self.peek() {
+            (Symbol(c), _) => c == symbol,
+            _ => false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<Span, ExprError> {
+        if self.at(symbol) {
+            let (_, span) = self.advance();
+            Ok(span)
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// An error for the next token.
+    fn unexpected(&self) -> ExprError {
+        match *self.peek() {
+            (EndToken, span) => error(span, UnexpectedEnd),
+            (_, span) => error(span, UnexpectedToken(
+                self.text.slice(span.start, span.end).to_string()))
+        }
+    }
+
+    // sum := product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Expr, ExprError> {
+        let mut left = try!(self.product());
+        loop {
+            let op = if self.at('+') {
+                Sum
+            } else if self.at('-') {
+                Difference
+            } else {
+                return Ok(left);
+            };
+            self.advance();
+            let right = try!(self.product());
+            left = binary(op, left, right);
+        }
+    }
+
+    // product := unary ('*' unary)*
+    fn product(&mut self) -> Result<Expr, ExprError> {
+        let mut left = try!(self.unary());
+        while self.at('*') {
+            self.advance();
+            let right = try!(self.unary());
+            left = binary(Product, left, right);
+        }
+        Ok(left)
+    }
+
+    // unary := '-' unary | primary
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        if self.at('-') {
+            let (_, span) = self.advance();
+            let operand = try!(self.unary());
+            let end = operand.span.end;
+            Ok(Expr {
+                node: Negate(box operand),
+                span: Span { start: span.start, end: end }
+            })
+        } else {
+            self.primary()
+        }
+    }
+
+    // primary := number | name ['(' arguments ')'] | '(' sum ')'
+    //          | '[' [sum (',' sum)*] ']'
+    fn primary(&mut self) -> Result<Expr, ExprError> {
+        let (token, span) = self.peek().clone();
+        match token {
+            NumberToken(value) => {
+                self.advance();
+                Ok(Expr { node: Constant(value), span: span })
+            },
+            NameToken(name) => {
+                self.advance();
+                if !self.at('(') {
+                    return Ok(Expr { node: Name(name), span: span });
+                }
+                self.advance();
+                let arguments = try!(self.arguments());
+                let end = try!(self.expect(')'));
+                Ok(Expr {
+                    node: Call(name, arguments),
+                    span: Span { start: span.start, end: end.end }
+                })
+            },
+            Symbol('(') => {
+                self.advance();
+                let inner = try!(self.sum());
+                try!(self.expect(')'));
+                Ok(inner)
+            },
+            Symbol('[') => {
+                self.advance();
+                let mut items = Vec::new();
+                if !self.at(']') {
+                    loop {
+                        items.push(try!(self.sum()));
+                        if !self.at(',') {
+                            break;
+                        }
+                        self.advance();
+                    }
+                }
+                let end = try!(self.expect(']'));
+                Ok(Expr {
+                    node: List(items),
+                    span: Span { start: span.start, end: end.end }
+                })
+            },
+            _ => Err(self.unexpected())
+        }
+    }
+
+    // arguments := [argument (',' argument)*]
+    // argument := [name '='] sum
+    fn arguments(&mut self) -> Result<Vec<Argument>, ExprError> {
+        let mut arguments = Vec::new();
+        if self.at(')') {
+            return Ok(arguments);
+        }
+
+        loop {
+            let named = match self.tokens.get(self.position + 1) {
+                Some(&(Symbol('='), _)) => true,
+                _ => false
+            };
+            let name = match self.peek().clone() {
+                (NameToken(name), span) if named => {
+                    self.advance();
+                    self.advance();
+                    Some((name, span))
+                },
+                _ => None
+            };
+            arguments.push(Argument { name: name, value: try!(self.sum()) });
+
+            if !self.at(',') {
+                return Ok(arguments);
+            }
+            self.advance();
+        }
+    }
+}
+
+fn binary(op: Operation, left: Expr, right: Expr) -> Expr {
+    let span = Span { start: left.span.start, end: right.span.end };
+    Expr { node: Binary(op, box left, box right), span: span }
+}
+
+// Translation
+
+/// A function of the expression language, which builds the module of the
+/// same type in the serial module, reading its arguments as that module reads
+/// fields. The arguments named in `positional` may be given by position, in
+/// that order.
+struct Function {
+    name: &'static str,
+    positional: &'static [&'static str],
+}
+
+static FUNCTIONS: &'static [Function] = &[
+    Function { name: "pink", positional: &["seed"] },
+    Function { name: "billow", positional: &["seed"] },
+    Function { name: "ridged", positional: &["seed"] },
+    Function { name: "hybrid", positional: &["seed"] },
+    Function { name: "hetero", positional: &["seed"] },
+    Function { name: "swiss", positional: &["seed"] },
+    Function { name: "jordan", positional: &["seed"] },
+    Function { name: "eroded", positional: &["seed"] },
+    Function { name: "const", positional: &["value"] },
+    Function { name: "checkerboard", positional: &[] },
+    Function { name: "cylinder", positional: &["frequency"] },
+    Function { name: "clamp", positional: &["source", "min", "max"] },
+    Function { name: "scalebias", positional: &["source", "scale", "bias"] },
+    Function { name: "translate", positional: &["source", "translation"] },
+    Function { name: "rotate", positional: &["source", "rotation"] },
+    Function { name: "rotate_3d", positional: &["source", "rotation"] },
+    Function { name: "scale_input", positional: &["source", "scale"] },
+    Function { name: "affine", positional: &["source", "matrix"] },
+    Function { name: "add", positional: &["first", "second"] },
+    Function { name: "subtract", positional: &["first", "second"] },
+    Function { name: "multiply", positional: &["first", "second"] },
+    Function { name: "min", positional: &["first", "second"] },
+    Function { name: "max", positional: &["first", "second"] },
+    Function { name: "blend", positional: &["first", "second", "control"] },
+    Function { name: "select",
+               positional: &["outside", "inside", "control", "lower_bound",
+                             "upper_bound", "edge_falloff"] },
+    Function { name: "turbulence",
+               positional: &["source", "frequency", "power", "roughness"] },
+    Function { name: "displace",
+               positional: &["source", "x_displace", "y_displace",
+                             "z_displace"] },
+    Function { name: "terrace", positional: &["source", "points"] },
+    Function { name: "curve",
+               positional: &["source", "points", "interpolation"] },
+    Function { name: "function", positional: &["name"] },
+    Function { name: "modifier", positional: &["source", "name"] },
+    Function { name: "magnitude", positional: &["source"] },
+    Function { name: "component", positional: &["source", "index"] },
+    Function { name: "curl", positional: &["seed"] },
+    Function { name: "composed", positional: &["x", "y", "z"] },
+];
+
+/// The arguments of a call, matched with the names of the parameters they
+/// are given for. Each argument that is read is marked as used, so that any
+/// left over once the module is built can be reported as unknown.
+struct Args<'a> {
+    function: &'static str,
+    span: Span,
+    arguments: Vec<(&'a str, Span, &'a Expr)>,
+    used: RefCell<Vec<&'a str>>,
+    registry: &'a Registry,
+}
+
+impl<'a> Args<'a> {
+    fn new(function: &'static str, positional: &'static [&'static str],
+           arguments: &'a [Argument], span: Span, registry: &'a Registry)
+        -> Result<Args<'a>, ExprError> {
+        let mut assigned: Vec<(&'a str, Span, &'a Expr)> = Vec::new();
+        let mut position = 0u;
+
+        for argument in arguments.iter() {
+            let (name, span) = match argument.name {
+                Some((ref name, span)) => (name.as_slice(), span),
+                None => {
+                    if position >= positional.len() {
+                        return Err(error(argument.value.span,
+                            TooManyArguments(function.to_string())));
+                    }
+                    position += 1;
+                    (positional[position - 1], argument.value.span)
+                }
+            };
+
+            if assigned.iter().any(|&(p, _, _)| p == name) {
+                return Err(error(span, DuplicateArgument(
+                    function.to_string(), name.to_string())));
+            }
+            assigned.push((name, span, &argument.value));
+        }
+
+        Ok(Args {
+            function: function, span: span, arguments: assigned,
+            used: RefCell::new(Vec::new()), registry: registry
+        })
+    }
+
+    fn get(&self, name: &str) -> Option<&'a Expr> {
+        match self.arguments.iter().find(|&&(p, _, _)| p == name) {
+            Some(&(p, _, expr)) => {
+                self.used.borrow_mut().push(p);
+                Some(expr)
+            },
+            None => None
+        }
+    }
+
+    /// Checks that every argument has been used.
+    fn finish(&self) -> Result<(), ExprError> {
+        let used = self.used.borrow();
+        match self.arguments.iter().find(|&&(p, _, _)| !used.contains(&p)) {
+            Some(&(name, span, _)) => Err(error(span, UnknownArgument(
+                self.function.to_string(), name.to_string()))),
+            None => Ok(())
+        }
+    }
+
+    fn list(&self, name: &str) -> Result<&'a [Expr], ExprError> {
+        match self.get(name) {
+            Some(&Expr { node: List(ref items), .. }) => Ok(items.as_slice()),
+            Some(_) => Err(self.invalid(name)),
+            None => Err(self.missing(name))
+        }
+    }
+
+    /// Reads a bare name, along with the span of the argument.
+    fn name(&self, name: &str) -> Result<(&'a str, Span), ExprError> {
+        match self.get(name) {
+            Some(&Expr { node: Name(ref value), span }) =>
+                Ok((value.as_slice(), span)),
+            Some(_) => Err(self.invalid(name)),
+            None => Err(self.missing(name))
+        }
+    }
+}
+
+impl<'a> FieldReader<ExprError> for Args<'a> {
+    fn unknown(&self, _: &str) -> ExprError {
+        error(self.span, UnknownFunction(self.function.to_string()))
+    }
+
+    fn missing(&self, name: &str) -> ExprError {
+        error(self.span, MissingArgument(self.function.to_string(),
+                                         name.to_string()))
+    }
+
+    /// An error for the given argument, pointing at its value.
+    fn invalid(&self, name: &str) -> ExprError {
+        let span = self.arguments.iter().find(|&&(p, _, _)| p == name)
+            .map_or(self.span, |&(_, _, expr)| expr.span);
+        error(span, InvalidArgument(self.function.to_string(),
+                                    name.to_string()))
+    }
+
+    fn number(&self, name: &str) -> Result<f32, ExprError> {
+        match self.get(name) {
+            Some(expr) => number(expr).ok_or(self.invalid(name)),
+            None => Err(self.missing(name))
+        }
+    }
+
+    fn number_or(&self, name: &str, default: f32) -> Result<f32, ExprError> {
+        match self.get(name) {
+            Some(expr) => number(expr).ok_or(self.invalid(name)),
+            None => Ok(default)
+        }
+    }
+
+    fn uint_or(&self, name: &str, default: uint) -> Result<uint, ExprError> {
+        match self.get(name).map(|expr| constant(expr)) {
+            Some(Some(value)) if value >= 0.0 && value.fract() == 0.0 =>
+                Ok(value as uint),
+            Some(_) => Err(self.invalid(name)),
+            None => Ok(default)
+        }
+    }
+
+    fn bool_or(&self, name: &str, default: bool) -> Result<bool, ExprError> {
+        match self.get(name).map(|expr| &expr.node) {
+            Some(&Name(ref value)) if value.as_slice() == "true" => Ok(true),
+            Some(&Name(ref value)) if value.as_slice() == "false" => Ok(false),
+            Some(_) => Err(self.invalid(name)),
+            None => Ok(default)
+        }
+    }
+
+    fn numbers(&self, name: &str) -> Result<Vec<f32>, ExprError> {
+        let mut result = Vec::new();
+        for item in try!(self.list(name)).iter() {
+            result.push(try!(number(item).ok_or(self.invalid(name))));
+        }
+        Ok(result)
+    }
+
+    fn points(&self, name: &str) -> Result<Vec<(f32, f32)>, ExprError> {
+        let mut result = Vec::new();
+        for item in try!(self.list(name)).iter() {
+            let pair = match item.node {
+                List(ref pair) if pair.len() == 2 => pair,
+                _ => return Err(self.invalid(name))
+            };
+            let x = try!(number(&pair[0]).ok_or(self.invalid(name)));
+            let y = try!(number(&pair[1]).ok_or(self.invalid(name)));
+            result.push((x, y));
+        }
+        Ok(result)
+    }
+
+    fn interpolation(&self, name: &str) -> Result<Interpolation, ExprError> {
+        match self.get(name).map(|expr| &expr.node) {
+            Some(&Name(ref value)) if value.as_slice() == "catmull_rom" =>
+                Ok(CatmullRom),
+            Some(&Name(ref value)) if value.as_slice() == "monotone_cubic" =>
+                Ok(MonotoneCubic),
+            Some(_) => Err(self.invalid(name)),
+            None => Ok(MonotoneCubic)
+        }
+    }
+
+    fn module(&self, name: &str) -> Result<Box<NoiseModule>, ExprError> {
+        match self.get(name) {
+            Some(expr) => scalar(expr, self.registry),
+            None => Err(self.missing(name))
+        }
+    }
+
+    fn optional_module(&self, name: &str)
+        -> Result<Option<Box<NoiseModule>>, ExprError> {
+        match self.get(name) {
+            Some(expr) => Ok(Some(try!(scalar(expr, self.registry)))),
+            None => Ok(None)
+        }
+    }
+
+    fn vector_module(&self, name: &str)
+        -> Result<Box<VectorNoiseModule>, ExprError> {
+        match self.get(name) {
+            Some(expr) => vector(expr, self.registry),
+            None => Err(self.missing(name))
+        }
+    }
+
+    /// Reads the optional flow parameters of a fractal module, written as
+    /// `flow(time=...)`.
+    fn flow(&self) -> Result<Option<Flow>, ExprError> {
+        let (arguments, span) = match self.get("flow") {
+            Some(&Expr { node: Call(ref name, ref arguments), span })
+                if name.as_slice() == "flow" => (arguments, span),
+            Some(_) => return Err(self.invalid("flow")),
+            None => return Ok(None)
+        };
+        let f = try!(Args::new("flow", &[], arguments.as_slice(), span,
+                               self.registry));
+        let d: Flow = Default::default();
+
+        let flow = Flow {
+            time: try!(f.number_or("time", d.time)),
+            speed: try!(f.number_or("speed", d.speed)),
+            speed_lacunarity: try!(f.number_or("speed_lacunarity",
+                                               d.speed_lacunarity))
+        };
+        try!(f.finish());
+        Ok(Some(flow))
+    }
+}
+
+/// The value of an expression made up of numbers alone, if it is one.
+fn constant(expr: &Expr) -> Option<f64> {
+    match expr.node {
+        Constant(value) => Some(value),
+        Negate(ref operand) => constant(&**operand).map(|value| -value),
+        Binary(ref operation, ref left, ref right) => {
+            match (constant(&**left), constant(&**right)) {
+                (Some(a), Some(b)) => Some(match *operation {
+                    Sum => a + b,
+                    Difference => a - b,
+                    _ => a * b
+                }),
+                _ => None
+            }
+        },
+        _ => None
+    }
+}
+
+/// The value of a number argument, which may also be one of the names `inf`
+/// and `nan`.
+fn number(expr: &Expr) -> Option<f32> {
+    match expr.node {
+        Name(ref name) if name.as_slice() == "inf" => Some(Float::infinity()),
+        Name(ref name) if name.as_slice() == "nan" => Some(Float::nan()),
+        _ => constant(expr).map(|value| value as f32)
+    }
+}
+
+/// Builds the module described by an expression.
+fn module(expr: &Expr, registry: &Registry) -> Result<Module, ExprError> {
+    match constant(expr) {
+        Some(value) => return Ok(ScalarModule(
+            box ConstNoise::new(value as f32) as Box<NoiseModule>)),
+        None => ()
+    }
+
+    Ok(match expr.node {
+        Name(ref name) => {
+            try!(call(name.as_slice(), &[], expr.span, registry))
+        },
+        Call(ref name, ref arguments) => {
+            try!(call(name.as_slice(), arguments.as_slice(), expr.span,
+                      registry))
+        },
+        Negate(ref operand) => ScalarModule(box ScaledBiasedNoise {
+            source: try!(scalar(&**operand, registry)), scale: -1.0, bias: 0.0
+        } as Box<NoiseModule>),
+        Binary(ref operation, ref left, ref right) => {
+            ScalarModule(box CombinedNoise {
+                operation: operation.clone(),
+                first: try!(scalar(&**left, registry)),
+                second: try!(scalar(&**right, registry))
+            } as Box<NoiseModule>)
+        },
+        _ => return Err(error(expr.span, ExpectedModule))
+    })
+}
+
+fn scalar(expr: &Expr, registry: &Registry)
+    -> Result<Box<NoiseModule>, ExprError> {
+    match try!(module(expr, registry)) {
+        ScalarModule(module) => Ok(module),
+        VectorModule(_) => Err(error(expr.span, ExpectedModule))
+    }
+}
+
+fn vector(expr: &Expr, registry: &Registry)
+    -> Result<Box<VectorNoiseModule>, ExprError> {
+    match try!(module(expr, registry)) {
+        VectorModule(module) => Ok(module),
+        ScalarModule(_) => Err(error(expr.span, ExpectedVectorModule))
+    }
+}
+
+/// Builds the module of a call to a function.
+fn call(name: &str, arguments: &[Argument], span: Span, registry: &Registry)
+    -> Result<Module, ExprError> {
+    let function = match FUNCTIONS.iter().find(|f| f.name == name) {
+        Some(function) => function,
+        None if name == "flow" => return Err(error(span, ExpectedModule)),
+        None => return Err(error(span, UnknownFunction(name.to_string())))
+    };
+    let args = try!(Args::new(function.name, function.positional, arguments,
+                              span, registry));
+
+    // A missing argument is more likely to be a misspelt one.
+    let result = match function.name {
+        "turbulence" => build_turbulence(&args),
+        "function" => build_function(&args),
+        "modifier" => build_modifier(&args),
+        name => reader::read(name, &args)
+    };
+    match result {
+        Err(ExprError { kind: MissingArgument(..), .. }) => {
+            try!(args.finish())
+        },
+        _ => ()
+    }
+    let module = try!(result);
+    try!(args.finish());
+    Ok(module)
+}
+
+/// Builds TurbulenceNoise from the arguments of Modifiable's `turbulence()`,
+/// rather than from its distortion modules. The defaults are those of
+/// libnoise.
+fn build_turbulence(args: &Args) -> Result<Module, ExprError> {
+    let source = try!(args.module("source"));
+    let frequency = try!(args.number_or("frequency", 1.0));
+    let power = try!(args.number_or("power", 1.0));
+    let roughness = try!(args.uint_or("roughness", 3));
+    let seed = try!(args.uint_or("seed", 0));
+    match TurbulenceNoise::new(&*source, seed, frequency, power, roughness) {
+        Ok(turbulence) => Ok(ScalarModule(box turbulence as Box<NoiseModule>)),
+        Err(_) => Err(args.invalid("roughness"))
+    }
+}
+
+fn build_function(args: &Args) -> Result<Module, ExprError> {
+    let (name, span) = try!(args.name("name"));
+    match args.registry.function(name) {
+        Some(function) => Ok(ScalarModule(box function as Box<NoiseModule>)),
+        None => Err(error(span, UnknownFunction(name.to_string())))
+    }
+}
+
+fn build_modifier(args: &Args) -> Result<Module, ExprError> {
+    let source = try!(args.module("source"));
+    let (name, span) = try!(args.name("name"));
+    match args.registry.modifier(name, &*source) {
+        Some(modifier) => Ok(ScalarModule(box modifier as Box<NoiseModule>)),
+        None => Err(error(span, UnknownFunction(name.to_string())))
+    }
+}
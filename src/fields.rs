@@ -0,0 +1,398 @@
+/*
+    This file is part of grunge, a coherent noise generation library.
+*/
+
+//! Types for generating vector fields, rather than scalar noise.
+//!
+//! These implement [VectorNoiseModule](../primitives/trait.VectorNoiseModule.html)
+//! instead of NoiseModule, and generate a vector for each input coordinate.
+//! ComposedVectorNoise builds a vector field out of several scalar modules,
+//! while the methods of [VectorModifiable](trait.VectorModifiable.html) turn a
+//! vector field back into scalar noise.
+//!
+//! ## Example
+//!
+//! The following uses two PinkNoise modules as the components of a wind field,
+//! and takes its magnitude as the wind speed:
+//!
+//! ```rust
+//! use grunge::modules::{NoiseModule, VectorNoiseModule, VectorModifiable,
+//!                       PinkNoise, ComposedVectorNoise};
+//!
+//! let wind = ComposedVectorNoise::new(&PinkNoise::new(0), &PinkNoise::new(1));
+//! let speed = wind.magnitude();
+//! println!("{} {}", wind.generate_2d(1.0, -1.0), speed.generate_2d(1.0, -1.0));
+//! ```
+
+use std::default::Default;
+use std::rand::{Rand, Rng};
+use std::sync::Arc;
+
+use cgmath::vector::{Vector, Vector2, Vector3, EuclideanVector};
+
+use serialize::json::{Json, ToJson};
+
+use primitives::{snoise_2d_grad, snoise_3d_grad, NoiseModule,
+                 VectorNoiseModule};
+use modifiers::Modifiable;
+use serial::{describe, number};
+use octaves::{check_octaves, octave_weight};
+use boxes::clone;
+
+/// This trait provides a way of turning vector-valued noise back into scalar
+/// noise, in the same way as [Modifiable](../modifiers/trait.Modifiable.html)
+/// does for scalar noise. All VectorNoiseModule implementations in this crate
+/// also implement this trait.
+pub trait VectorModifiable : VectorNoiseModule {
+    /// Creates a scalar noise module from the magnitude (i.e. length) of the
+    /// vectors generated by this module.
+    fn magnitude(&self) -> MagnitudeNoise {
+        MagnitudeNoise { source: self.to_box() }
+    }
+
+    /// Creates a scalar noise module from the component with the given
+    /// `index` (0 for x, 1 for y, 2 for z) of the vectors generated by this
+    /// module.
+    fn component(&self, index: uint) -> ComponentNoise {
+        ComponentNoise { source: self.to_box(), index: index }
+    }
+}
+
+/// CurlNoise generates a divergence-free vector field by taking the curl of a
+/// fractal noise potential, as described by Bridson et al. [1]. Particles
+/// advected through the field swirl around without bunching up or spreading
+/// out, which makes it well suited to smoke and fluid effects.
+///
+/// The curl is computed from the analytic gradients of the noise function, so
+/// the field is exactly divergence-free. In two dimensions the potential is a
+/// single noise function $\psi$, and the field is
+/// $(\partial \psi / \partial y, -\partial \psi / \partial x)$. In three
+/// dimensions it is a vector of three noise functions, seeded with `seed`,
+/// `seed + 1` and `seed + 2`.
+///
+/// 1. Bridson, Robert, Jim Hourihan and Marcus Nordenstam. (2007). _Curl-Noise
+///    for Procedural Fluid Flow_. ACM Transactions on Graphics (Proceedings of
+///    SIGGRAPH 2007) 26(3).
+#[deriving(Clone)]
+pub struct CurlNoise {
+    /// The "seed" used to ensure reproducibility and variation in the output of
+    /// the module.
+    pub seed: uint,
+
+    /// The scale of the potential. Setting this value is equivalent to scaling
+    /// all input coordinates by the same value, and also scales the magnitude
+    /// of the field.
+    pub frequency: f32,
+
+    /// The amplitude falloff of the successive octaves of the potential.
+    pub persistence: f32,
+
+    /// The frequency multiplier between successive octaves of the potential.
+    pub lacunarity: f32,
+
+    /// The number of octaves of noise summed to form the potential. This may
+    /// be fractional, in which case the last octave is weighted by the
+    /// fractional part.
+    pub octaves: f32
+}
+
+impl CurlNoise {
+    /// Create a new object with the seed `seed` and all parameters set to their
+    /// default values.
+    pub fn new(seed: uint) -> CurlNoise {
+        CurlNoise { seed: seed, .. Default::default() }
+    }
+
+    /// The gradient of the two-dimensional potential with the given seed.
+    fn potential_grad_2d(&self, point: Vector2<f32>, seed: uint)
+        -> Vector2<f32> {
+        let mut result = Vector2::new(0.0f32, 0.0);
+        let mut sample = point.mul_s(self.frequency);
+        let mut scale = self.frequency;
+
+        for octave in range(0, self.octaves.ceil() as uint) {
+            let (_, grad) = snoise_2d_grad(sample.x, sample.y, seed + octave);
+            // By the chain rule, each octave's gradient is scaled by its
+            // frequency as well as its amplitude.
+            let weight = octave_weight(self.octaves, octave);
+            result = result + grad.mul_s(scale * weight);
+            sample = sample.mul_s(self.lacunarity);
+            scale *= self.lacunarity * self.persistence;
+        }
+
+        result
+    }
+
+    /// The gradient of the three-dimensional potential with the given seed.
+    fn potential_grad_3d(&self, point: Vector3<f32>, seed: uint)
+        -> Vector3<f32> {
+        let mut result = Vector3::new(0.0f32, 0.0, 0.0);
+        let mut sample = point.mul_s(self.frequency);
+        let mut scale = self.frequency;
+
+        for octave in range(0, self.octaves.ceil() as uint) {
+            let (_, grad) = snoise_3d_grad(sample.x, sample.y, sample.z,
+                                           seed + octave);
+            let weight = octave_weight(self.octaves, octave);
+            result = result + grad.mul_s(scale * weight);
+            sample = sample.mul_s(self.lacunarity);
+            scale *= self.lacunarity * self.persistence;
+        }
+
+        result
+    }
+}
+
+impl Default for CurlNoise {
+    fn default() -> CurlNoise {
+        CurlNoise {
+            seed: 0, frequency: 1.0, persistence: 0.5, lacunarity: 2.0,
+            octaves: 1.0
+        }
+    }
+}
+
+impl Rand for CurlNoise {
+    fn rand<R: Rng>(rng: &mut R) -> CurlNoise {
+        CurlNoise { seed: rng.gen(), .. Default::default() }
+    }
+}
+
+impl VectorNoiseModule for CurlNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<Vector2<f32>, &str> {
+        try!(check_octaves(self.octaves, 1.0));
+
+        let grad = self.potential_grad_2d(Vector2::new(x, y), self.seed);
+        Ok(Vector2::new(grad.y, -grad.x))
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32)
+        -> Result<Vector3<f32>, &str> {
+        try!(check_octaves(self.octaves, 1.0));
+
+        let point = Vector3::new(x, y, z);
+        let a = self.potential_grad_3d(point, self.seed);
+        let b = self.potential_grad_3d(point, self.seed + 1);
+        let c = self.potential_grad_3d(point, self.seed + 2);
+
+        Ok(Vector3::new(c.y - b.z, a.z - c.x, b.x - a.y))
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("curl", vec![
+            ("seed", self.seed.to_json()),
+            ("frequency", number(self.frequency)),
+            ("persistence", number(self.persistence)),
+            ("lacunarity", number(self.lacunarity)),
+            ("octaves", number(self.octaves))
+        ]))
+    }
+}
+
+impl VectorModifiable for CurlNoise {}
+
+/// Builds a vector-valued noise module out of one scalar noise module for each
+/// component, which saves having to keep several scalar modules in sync.
+pub struct ComposedVectorNoise {
+    /// The module giving the x component of each vector.
+    pub x: Box<NoiseModule>,
+
+    /// The module giving the y component of each vector.
+    pub y: Box<NoiseModule>,
+
+    /// The module giving the z component of each vector, if any. Three-
+    /// dimensional input is only supported if this is set.
+    pub z: Option<Box<NoiseModule>>,
+}
+
+impl ComposedVectorNoise {
+    /// Creates a new two-dimensional ComposedVectorNoise with the given
+    /// component modules.
+    pub fn new(x: &NoiseModule, y: &NoiseModule) -> ComposedVectorNoise {
+        ComposedVectorNoise { x: x.to_box(), y: y.to_box(), z: None }
+    }
+
+    /// Creates a new three-dimensional ComposedVectorNoise with the given
+    /// component modules.
+    pub fn new_3d(x: &NoiseModule, y: &NoiseModule, z: &NoiseModule)
+        -> ComposedVectorNoise {
+        ComposedVectorNoise {
+            x: x.to_box(), y: y.to_box(), z: Some(z.to_box())
+        }
+    }
+}
+
+impl Clone for ComposedVectorNoise {
+    fn clone(&self) -> ComposedVectorNoise {
+        ComposedVectorNoise {
+            x: clone(&self.x), y: clone(&self.y), z: self.z.clone()
+        }
+    }
+}
+
+impl VectorNoiseModule for ComposedVectorNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<Vector2<f32>, &str> {
+        Ok(Vector2::new(try!(self.x.generate_2d(x, y)),
+                        try!(self.y.generate_2d(x, y))))
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32)
+        -> Result<Vector3<f32>, &str> {
+        match self.z {
+            Some(ref z_module) => Ok(Vector3::new(
+                try!(self.x.generate_3d(x, y, z)),
+                try!(self.y.generate_3d(x, y, z)),
+                try!(z_module.generate_3d(x, y, z))
+            )),
+            None => Err("This module has no z component.")
+        }
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let mut fields = vec![
+            ("x", try!(self.x.to_json())),
+            ("y", try!(self.y.to_json()))
+        ];
+        match self.z {
+            Some(ref z) => fields.push(("z", try!(z.to_json()))),
+            None => ()
+        }
+        Ok(describe("composed", fields))
+    }
+}
+
+impl VectorModifiable for ComposedVectorNoise {}
+
+/// Shares a single vector-valued source module between several parents, in the
+/// same way as [SharedNoise](../modifiers/struct.SharedNoise.html).
+pub struct SharedVectorNoise {
+    /// The shared source module.
+    pub source: Arc<Box<VectorNoiseModule>>,
+}
+
+impl SharedVectorNoise {
+    /// Creates a new SharedVectorNoise with the given source.
+    pub fn new(source: &VectorNoiseModule) -> SharedVectorNoise {
+        SharedVectorNoise { source: Arc::new(source.to_box()) }
+    }
+}
+
+impl Clone for SharedVectorNoise {
+    fn clone(&self) -> SharedVectorNoise {
+        SharedVectorNoise { source: self.source.clone() }
+    }
+}
+
+impl VectorNoiseModule for SharedVectorNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<Vector2<f32>, &str> {
+        self.source.generate_2d(x, y)
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32)
+        -> Result<Vector3<f32>, &str> {
+        self.source.generate_3d(x, y, z)
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        self.source.to_json()
+    }
+}
+
+impl VectorModifiable for SharedVectorNoise {}
+
+/// Creates a scalar noise module from the magnitude of the vectors generated by
+/// a vector-valued source module.
+pub struct MagnitudeNoise {
+    /// The source module.
+    pub source: Box<VectorNoiseModule>,
+}
+
+impl MagnitudeNoise {
+    /// Creates a new MagnitudeNoise with the given source.
+    pub fn new(source: &VectorNoiseModule) -> MagnitudeNoise {
+        MagnitudeNoise { source: source.to_box() }
+    }
+}
+
+impl Clone for MagnitudeNoise {
+    fn clone(&self) -> MagnitudeNoise {
+        MagnitudeNoise { source: clone(&self.source) }
+    }
+}
+
+impl NoiseModule for MagnitudeNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let v = try!(self.source.generate_2d(x, y));
+        Ok(v.length())
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        let v = try!(self.source.generate_3d(x, y, z));
+        Ok(v.length())
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        (0.0, Float::infinity())
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("magnitude", vec![
+            ("source", try!(self.source.to_json()))
+        ]))
+    }
+}
+
+impl Modifiable for MagnitudeNoise {}
+
+/// Creates a scalar noise module from one component of the vectors generated
+/// by a vector-valued source module.
+pub struct ComponentNoise {
+    /// The source module.
+    pub source: Box<VectorNoiseModule>,
+
+    /// The index of the component to take: 0 for x, 1 for y and 2 for z.
+    pub index: uint,
+}
+
+impl ComponentNoise {
+    /// Creates a new ComponentNoise with the given source and component index.
+    pub fn new(source: &VectorNoiseModule, index: uint) -> ComponentNoise {
+        ComponentNoise { source: source.to_box(), index: index }
+    }
+}
+
+impl Clone for ComponentNoise {
+    fn clone(&self) -> ComponentNoise {
+        ComponentNoise { source: clone(&self.source), index: self.index }
+    }
+}
+
+impl NoiseModule for ComponentNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let v = try!(self.source.generate_2d(x, y));
+        match self.index {
+            0 => Ok(v.x),
+            1 => Ok(v.y),
+            _ => Err("The component index must be 0 or 1 for two-dimensional input.")
+        }
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        let v = try!(self.source.generate_3d(x, y, z));
+        match self.index {
+            0 => Ok(v.x),
+            1 => Ok(v.y),
+            2 => Ok(v.z),
+            _ => Err("The component index must be 0, 1 or 2.")
+        }
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("component", vec![
+            ("source", try!(self.source.to_json())),
+            ("index", self.index.to_json())
+        ]))
+    }
+}
+
+impl Modifiable for ComponentNoise {}
@@ -9,8 +9,11 @@
 //! [Tron](http://en.wikipedia.org/wiki/Tron), and later demonstrated when he
 //! introduced the first "noise" function to industry in 1985. They were
 //! used to create water, cloud and fire textures, among other things.
-//! RidgedMultifractalNoise originates with F. Kenton Musgrave, who draws
-//! heavily on Perlin's noise function but adds his own touches.
+//! RidgedMultifractalNoise, HybridMultifractalNoise and HeteroTerrainNoise
+//! originate with F. Kenton Musgrave, who draws heavily on Perlin's noise
+//! function but adds his own touches. SwissTurbulenceNoise,
+//! JordanTurbulenceNoise and ErodedNoise go a step further, using the
+//! derivatives of each octave to warp or damp the octaves that follow.
 //!
 //! <div style="margin: 0 auto; display: block; text-align: center">
 //!     <img src="../static/pink.png" alt="PinkNoise">
@@ -36,20 +39,252 @@
 //!
 //! let mut rng = task_rng();
 //!
-//! let pink = PinkNoise { seed: 1035, octaves: 4, .. Default::default() };
+//! let pink = PinkNoise { seed: 1035, octaves: 4.0, .. Default::default() };
 //! let billow: BillowNoise = Rand::rand(&mut rng);
 //! ```
+//!
+//! ## Output range
+//!
+//! By default, the sum of the octaves of each module is scaled so that its
+//! output *usually* lies within [-1, 1] for the default parameters. Setting a
+//! module's `normalize` field instead rescales the output by the range of
+//! values the octaves can sum to, as given by `bounds()`, so that it is
+//! guaranteed to lie within [-1, 1] regardless of the other parameters.
+//! Normalized output typically occupies less of that range than the default
+//! scaling does.
 
 use std::default::Default;
 use std::rand::{Rand, Rng};
 
-use primitives::{snoise_2d, NoiseModule};
+use cgmath::vector::{Vector, Vector2, Vector3, dot};
+
+use serialize::json::{Json, ToJson};
+
+use primitives::{snoise_2d, snoise_2d_grad, snoise_3d, sflow_2d, sflow_2d_grad,
+                 NoiseModule, SNOISE_BOUND};
 use modifiers::Modifiable;
+use serial::{describe, number};
+use shader::{Program, Register, Simplex, FlowSimplex};
+use octaves::{check_octaves, octave_count, octave_weight, amplitude_sum};
 
+// These scale the sum of the octaves of each module so that the output
+// *usually* fits in [-1, 1] for the default parameters, which suits visual
+// work. Since the true range depends on the parameters, each module's
+// `bounds()` method gives a conservative range instead, and setting the
+// module's `normalize` field makes use of it to guarantee [-1, 1].
 static PINKNOISE_SCALE: f32 = 0.25;
 static BILLOWNOISE_SCALE: f32 = 0.25;
 static RMULTINOISE_SCALE: f32 = 0.28;
 
+// The octaves of the multifractals are multiplied by the sum or the weights
+// accumulated before them, so their sums have no simple closed-form spread.
+// These two scales are empirical rather than derived: with the default offset
+// of 0.7 the sum is always positive, so the output is `sum * scale - 1`, with
+// the scale picked by hand so that sampled peaks of the default modules come
+// out near 1. `test_multifractal_ranges` checks the sampled output against
+// `bounds()` and that it is centred in [-1, 1].
+static HYBRIDNOISE_SCALE: f32 = 0.58;
+static HETERONOISE_SCALE: f32 = 0.62;
+
+// Both turbulence fractals sum terms that are never negative (ridges 1 - |n|
+// and squares n^2, respectively), so they are mapped onto [-1, 1] by a scale
+// and a bias, again picked by hand from sampled output of the default modules.
+// The Swiss sum usually lies in about [0.4, 1.8], which the scale and bias
+// below map onto [-1, 1]; the Jordan sum starts at zero on the flats, so its
+// bias is -1 and its scale maps the highest sampled ridges to about 1.
+static SWISSNOISE_SCALE: f32 = 1.4;
+static SWISSNOISE_BIAS: f32 = -1.55;
+static JORDANNOISE_SCALE: f32 = 3.2;
+
+/// Scales the output of the noise function to within about [-1, 1] for the
+/// modules whose octaves depend on that range.
+static SIGNAL_SCALE: f32 = 0.3;
+
+static FLOW_3D_ERROR: &'static str =
+    "Flow noise does not support three-dimensional input.";
+
+/// The parameters of flow noise, which animates fractal noise by rotating the
+/// gradients of each octave's noise function over time, as proposed by Perlin
+/// and Neyret [1]. Unlike translating the input or sampling along a third
+/// dimension, this makes the output swirl in place, which suits water, lava and
+/// other fluids.
+///
+/// Higher octaves usually rotate faster than lower ones, since small eddies
+/// turn over faster than large ones; `speed_lacunarity` sets the ratio.
+///
+/// 1. Perlin, Ken and Fabrice Neyret. (2001). [_Flow Noise_]
+///    (http://evasion.imag.fr/Publications/2001/PN01/). SIGGRAPH Technical
+///    Sketches and Applications.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Flow {
+    /// The current time.
+    pub time: f32,
+
+    /// The angular speed of the gradients of the first octave, in radians per
+    /// unit time.
+    pub speed: f32,
+
+    /// The speed multiplier between successive octaves.
+    pub speed_lacunarity: f32,
+}
+
+impl Flow {
+    /// Create a new set of flow parameters at time `time`, with all other
+    /// parameters set to their default values.
+    pub fn new(time: f32) -> Flow {
+        Flow { time: time, .. Default::default() }
+    }
+
+    /// The angle of rotation of the gradients of the given octave.
+    fn angle(&self, octave: uint) -> f32 {
+        self.time * self.speed * self.speed_lacunarity.powi(octave as i32)
+    }
+}
+
+impl ToJson for Flow {
+    fn to_json(&self) -> Json {
+        describe("flow", vec![
+            ("time", number(self.time)),
+            ("speed", number(self.speed)),
+            ("speed_lacunarity", number(self.speed_lacunarity))
+        ])
+    }
+}
+
+impl Default for Flow {
+    fn default() -> Flow {
+        Flow { time: 0.0, speed: 1.0, speed_lacunarity: 1.5 }
+    }
+}
+
+/// Adds two intervals.
+fn interval_add((a, b): (f32, f32), (c, d): (f32, f32)) -> (f32, f32) {
+    (a + c, b + d)
+}
+
+/// Multiplies two intervals.
+fn interval_mul((a, b): (f32, f32), (c, d): (f32, f32)) -> (f32, f32) {
+    ((a * c).min(a * d).min(b * c).min(b * d),
+     (a * c).max(a * d).max(b * c).max(b * d))
+}
+
+/// Hashes a seed, octave and salt to a pseudo-random value in [0, 1).
+fn octave_hash(seed: uint, octave: uint, salt: uint) -> f32 {
+    let mut hash = (seed as u32 * 0x9E3779B1) ^ (octave as u32 * 0x85EBCA77) ^
+        (salt as u32 * 0xC2B2AE3D);
+    hash ^= hash >> 15;
+    hash *= 0x2C1B3C6D;
+    hash ^= hash >> 12;
+    hash *= 0x297A2D39;
+    hash ^= hash >> 15;
+    (hash >> 8) as f32 / 16777216.0
+}
+
+/// The largest offset applied to the input of an octave, along each axis.
+static OCTAVE_OFFSET: f32 = 64.0;
+
+/// Moves `point` into the domain of the given octave, rotating and/or
+/// offsetting it by amounts derived from the seed and octave. Rotation is about
+/// the z axis if `planar` is set, and about a pseudo-random axis otherwise.
+fn octave_domain(point: Vector3<f32>, seed: uint, octave: uint, rotate: bool,
+                 offset: bool, planar: bool) -> Vector3<f32> {
+    let mut result = point;
+
+    if rotate {
+        let angle = octave_hash(seed, octave, 0) * 2.0 * Float::pi();
+        let axis = if planar {
+            Vector3::new(0.0, 0.0, 1.0)
+        } else {
+            // A uniformly distributed unit vector.
+            let z = octave_hash(seed, octave, 1) * 2.0 - 1.0;
+            let phi = octave_hash(seed, octave, 2) * 2.0 * Float::pi();
+            let r = (1.0 - z * z).sqrt();
+            Vector3::new(r * phi.cos(), r * phi.sin(), z)
+        };
+
+        // Rodrigues' rotation formula.
+        let (sin, cos) = angle.sin_cos();
+        result = result.mul_s(cos) + axis.cross(&result).mul_s(sin) +
+            axis.mul_s(dot(axis, result) * (1.0 - cos));
+    }
+
+    if offset {
+        let shift = |salt| (octave_hash(seed, octave, salt) * 2.0 - 1.0) *
+            OCTAVE_OFFSET;
+        let z = if planar { 0.0 } else { shift(5) };
+        result = result + Vector3::new(shift(3), shift(4), z);
+    }
+
+    result
+}
+
+/// Maps a value lying within `bounds` linearly onto [-1, 1].
+fn normalize(value: f32, (lower, upper): (f32, f32)) -> f32 {
+    if upper > lower {
+        (value - lower) / (upper - lower) * 2.0 - 1.0
+    } else {
+        0.0
+    }
+}
+
+/// Appends the noise of the given octave at the point `sample` to a shader
+/// program, moving the point into the octave's domain as `octave_domain` does
+/// for planar input.
+fn octave_shader(program: &mut Program, (x, y): (Register, Register),
+                 seed: uint, octave: uint, rotate: bool, offset: bool,
+                 flow: &Option<Flow>) -> Register {
+    let (mut x, mut y) = (x, y);
+
+    if rotate {
+        // About the z axis, Rodrigues' formula reduces to a rotation matrix.
+        let angle = octave_hash(seed, octave, 0) * 2.0 * Float::pi();
+        let (sin, cos) = angle.sin_cos();
+        let (sin, cos) = (program.constant(sin), program.constant(cos));
+        let (xc, ys) = (program.mul(x, cos), program.mul(y, sin));
+        let (yc, xs) = (program.mul(y, cos), program.mul(x, sin));
+        x = program.sub(xc, ys);
+        y = program.add(yc, xs);
+    }
+
+    if offset {
+        let shift = |salt| (octave_hash(seed, octave, salt) * 2.0 - 1.0) *
+            OCTAVE_OFFSET;
+        let (dx, dy) = (program.constant(shift(3)), program.constant(shift(4)));
+        x = program.add(x, dx);
+        y = program.add(y, dy);
+    }
+
+    match *flow {
+        Some(ref flow) => {
+            program.push(FlowSimplex(x, y, seed + octave, flow.angle(octave)))
+        },
+        None => program.push(Simplex(x, y, seed + octave))
+    }
+}
+
+/// Appends the multiplication of both coordinates of `sample` by `factor` to a
+/// shader program.
+fn scale_shader(program: &mut Program, (x, y): (Register, Register),
+                factor: Register) -> (Register, Register) {
+    (program.mul(x, factor), program.mul(y, factor))
+}
+
+/// Appends `normalize(value, bounds)` to a shader program.
+fn normalize_shader(program: &mut Program, value: Register,
+                    (lower, upper): (f32, f32)) -> Register {
+    if upper > lower {
+        let lower_value = program.constant(lower);
+        let range = program.constant(upper - lower);
+        let (two, one) = (program.constant(2.0), program.constant(1.0));
+        let shifted = program.sub(value, lower_value);
+        let scaled = program.div(shifted, range);
+        let doubled = program.mul(scaled, two);
+        program.sub(doubled, one)
+    } else {
+        program.constant(0.0)
+    }
+}
+
 /// PinkNoise is generated by calculating the contribution of a number of
 /// individual `octaves` of noise samples, and then adding them together.
 /// PinkNoise is a kind of fractal noise, because the contributions are self-
@@ -84,7 +319,34 @@ pub struct PinkNoise {
     /// The number of octaves is the number of successive additive samples of
     /// the noise function this module will use to generate output. It is
     /// essentially a measure of the level of "detail" in the output.
-    pub octaves: uint
+    ///
+    /// The number of octaves may be fractional, in which case the last octave
+    /// is weighted by the fractional part.
+    pub octaves: f32,
+
+    /// The highest frequency to sample. Octaves above this frequency are
+    /// dropped, and the highest octave below it is faded out smoothly, which
+    /// avoids aliasing. This is usually the Nyquist limit of the sampling
+    /// grid, i.e. half the number of samples per unit distance.
+    pub max_frequency: f32,
+
+    /// If set, the gradients of the noise function rotate over time, so that
+    /// animating the time makes the output swirl rather than slide. Flow noise
+    /// only supports two-dimensional input.
+    pub flow: Option<Flow>,
+
+    /// Whether to rotate the input of each octave by a different angle, derived
+    /// from the seed. This hides the alignment of the noise function's lattice
+    /// with the axes, which is otherwise reinforced by every octave. The first
+    /// octave is rotated too.
+    pub rotate_octaves: bool,
+
+    /// Whether to offset the input of each octave by a different amount,
+    /// derived from the seed, so that the octaves do not share an origin.
+    pub offset_octaves: bool,
+
+    /// Whether to normalize the output to [-1, 1]; see the module docs.
+    pub normalize: bool
 }
 
 impl PinkNoise {
@@ -99,7 +361,9 @@ impl Default for PinkNoise {
     fn default() -> PinkNoise {
         PinkNoise {
             seed: 0, frequency: 1.0, persistence: 0.5,
-            lacunarity: 2.0, octaves: 6
+            lacunarity: 2.0, octaves: 6.0,
+            max_frequency: Float::infinity(), flow: None, rotate_octaves: false,
+            offset_octaves: false, normalize: false
         }
     }
 }
@@ -110,27 +374,129 @@ impl Rand for PinkNoise {
     }
 }
 
+impl PinkNoise {
+    /// Sums the octaves of `noise` sampled around `point`, where `noise` is
+    /// given the sample and the octave. The z coordinate is ignored by
+    /// two-dimensional noise functions, which should set `planar`.
+    fn fractal(&self, point: Vector3<f32>, planar: bool,
+               noise: |Vector3<f32>, uint| -> f32)
+        -> Result<f32, &'static str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let mut result: f32 = 0.0;
+        let mut sample = point.mul_s(self.frequency);
+        let mut persistence = 1.0;
+
+        for octave in range(0, count.ceil() as uint) {
+            let domain = octave_domain(sample, self.seed, octave,
+                                       self.rotate_octaves,
+                                       self.offset_octaves, planar);
+            result += octave_weight(count, octave) * persistence *
+                noise(domain, octave);
+            sample = sample.mul_s(self.lacunarity);
+            persistence *= self.persistence;
+        }
+
+        Ok(self.output(result))
+    }
+
+    /// A conservative range for the sum of the octaves.
+    fn raw_bounds(&self) -> (f32, f32) {
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+        let bound = amplitude_sum(self.persistence, count) * SNOISE_BOUND;
+        (-bound, bound)
+    }
+
+    /// Scales the sum of the octaves to the module's output.
+    fn output(&self, result: f32) -> f32 {
+        if self.normalize {
+            normalize(result, self.raw_bounds())
+        } else {
+            result * PINKNOISE_SCALE
+        }
+    }
+}
+
 impl NoiseModule for PinkNoise {
     fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
-        if self.octaves <= 1 {
-            return Err("The number of octaves must be two or greater.");
-        } else if self.octaves > 30 {
-            return Err("The number of octaves must be less than 30.");
+        let point = Vector3::new(x, y, 0.0);
+        match self.flow {
+            Some(ref flow) => self.fractal(point, true, |p, octave| {
+                sflow_2d(p.x, p.y, self.seed + octave, flow.angle(octave))
+            }),
+            None => self.fractal(point, true, |p, octave| {
+                snoise_2d(p.x, p.y, self.seed + octave)
+            })
         }
+    }
 
-        let mut result: f32 = 0.0;
-        let mut sample_x = self.frequency * x;
-        let mut sample_y = self.frequency * y;
-        let mut persistence = 1.0;
+    /// Flow noise is not supported for three-dimensional input.
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        if self.flow.is_some() {
+            return Err(FLOW_3D_ERROR);
+        }
+
+        self.fractal(Vector3::new(x, y, z), false, |p, octave| {
+            snoise_3d(p.x, p.y, p.z, self.seed + octave)
+        })
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (lower, upper) = self.raw_bounds();
+        (self.output(lower), self.output(upper))
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
 
-        for octave in range(0, self.octaves) {
-            result += persistence * snoise_2d(sample_x, sample_y, self.seed + octave);
-            sample_x *= self.lacunarity;
-            sample_y *= self.lacunarity;
+        let frequency = program.constant(self.frequency);
+        let lacunarity = program.constant(self.lacunarity);
+        let mut sample = scale_shader(program, (x, y), frequency);
+        let mut result = program.constant(0.0);
+        let mut persistence = 1.0f32;
+
+        for octave in range(0, count.ceil() as uint) {
+            let noise = octave_shader(program, sample, self.seed, octave,
+                                      self.rotate_octaves, self.offset_octaves,
+                                      &self.flow);
+            let amplitude = program.constant(octave_weight(count, octave) *
+                                             persistence);
+            let term = program.mul(amplitude, noise);
+            result = program.add(result, term);
+            sample = scale_shader(program, sample, lacunarity);
             persistence *= self.persistence;
         }
 
-        Ok(result * PINKNOISE_SCALE)
+        Ok(if self.normalize {
+            normalize_shader(program, result, self.raw_bounds())
+        } else {
+            let scale = program.constant(PINKNOISE_SCALE);
+            program.mul(result, scale)
+        })
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let mut fields = vec![
+            ("seed", self.seed.to_json()),
+            ("frequency", number(self.frequency)),
+            ("persistence", number(self.persistence)),
+            ("lacunarity", number(self.lacunarity)),
+            ("octaves", number(self.octaves)),
+            ("max_frequency", number(self.max_frequency)),
+            ("rotate_octaves", self.rotate_octaves.to_json()),
+            ("offset_octaves", self.offset_octaves.to_json()),
+            ("normalize", self.normalize.to_json())
+        ];
+        fields.extend(self.flow.iter().map(|flow| ("flow", flow.to_json())));
+        Ok(describe("pink", fields))
     }
 }
 
@@ -159,11 +525,38 @@ pub struct BillowNoise {
     /// The number of octaves is the number of successive additive samples of
     /// the noise function this module will use to generate output. It is
     ///  essentially a measure of the level of "detail" in the output.
-    pub octaves: uint,
+    ///
+    /// The number of octaves may be fractional, in which case the last octave
+    /// is weighted by the fractional part.
+    pub octaves: f32,
+
+    /// The highest frequency to sample. Octaves above this frequency are
+    /// dropped, and the highest octave below it is faded out smoothly, which
+    /// avoids aliasing. This is usually the Nyquist limit of the sampling
+    /// grid, i.e. half the number of samples per unit distance.
+    pub max_frequency: f32,
+
+    /// If set, the gradients of the noise function rotate over time, so that
+    /// animating the time makes the output swirl rather than slide. Flow noise
+    /// only supports two-dimensional input.
+    pub flow: Option<Flow>,
+
+    /// Whether to rotate the input of each octave by a different angle, derived
+    /// from the seed. This hides the alignment of the noise function's lattice
+    /// with the axes, which is otherwise reinforced by every octave. The first
+    /// octave is rotated too.
+    pub rotate_octaves: bool,
+
+    /// Whether to offset the input of each octave by a different amount,
+    /// derived from the seed, so that the octaves do not share an origin.
+    pub offset_octaves: bool,
 
     /// The offset from zero, used to reduce visual artifacts when using the
     /// absolute value function.
-    pub offset: f32
+    pub offset: f32,
+
+    /// Whether to normalize the output to [-1, 1]; see the module docs.
+    pub normalize: bool
 }
 
 impl BillowNoise {
@@ -178,7 +571,9 @@ impl Default for BillowNoise {
     fn default() -> BillowNoise {
         BillowNoise {
             seed: 0, frequency: 1.0, persistence: 0.5,
-            lacunarity: 2.0, offset: 0.2, octaves: 6
+            lacunarity: 2.0, offset: 0.2, octaves: 6.0,
+            max_frequency: Float::infinity(), flow: None, rotate_octaves: false,
+            offset_octaves: false, normalize: false
         }
     }
 }
@@ -189,28 +584,148 @@ impl Rand for BillowNoise {
     }
 }
 
+impl BillowNoise {
+    /// Sums the octaves of `noise` sampled around `point`, where `noise` is
+    /// given the sample and the octave. The z coordinate is ignored by
+    /// two-dimensional noise functions, which should set `planar`.
+    fn fractal(&self, point: Vector3<f32>, planar: bool,
+               noise: |Vector3<f32>, uint| -> f32)
+        -> Result<f32, &'static str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let mut result: f32 = 0.0;
+        let mut sample = point.mul_s(self.frequency);
+        let mut persistence = 1.0;
+
+        for octave in range(0, count.ceil() as uint) {
+            let domain = octave_domain(sample, self.seed, octave,
+                                       self.rotate_octaves,
+                                       self.offset_octaves, planar);
+            result += octave_weight(count, octave) * persistence *
+                (noise(domain, octave) + self.offset).abs();
+            sample = sample.mul_s(self.lacunarity);
+            persistence *= self.persistence;
+        }
+
+        Ok(self.output(result))
+    }
+
+    /// A conservative range for the sum of the octaves.
+    fn raw_bounds(&self) -> (f32, f32) {
+        let signal = (0.0, SNOISE_BOUND + self.offset.abs());
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        // Each octave adds a signal that is never negative, but the sign of
+        // its amplitude alternates if the persistence is negative.
+        let mut amplitude = 1.0;
+        let mut result = (0.0, 0.0);
+
+        for octave in range(0, count.ceil() as uint) {
+            let scale = octave_weight(count, octave) * amplitude;
+            result = interval_add(result, interval_mul((scale, scale), signal));
+            amplitude *= self.persistence;
+        }
+
+        result
+    }
+
+    /// Scales the sum of the octaves to the module's output.
+    fn output(&self, result: f32) -> f32 {
+        if self.normalize {
+            normalize(result, self.raw_bounds())
+        } else {
+            result * BILLOWNOISE_SCALE * 2.0 - 1.0
+        }
+    }
+}
+
 impl NoiseModule for BillowNoise {
     fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
-        if self.octaves <= 1 {
-            return Err("The number of octaves must be two or greater.");
-        } else if self.octaves > 30 {
-            return Err("The number of octaves must be less than 30.");
+        let point = Vector3::new(x, y, 0.0);
+        match self.flow {
+            Some(ref flow) => self.fractal(point, true, |p, octave| {
+                sflow_2d(p.x, p.y, self.seed + octave, flow.angle(octave))
+            }),
+            None => self.fractal(point, true, |p, octave| {
+                snoise_2d(p.x, p.y, self.seed + octave)
+            })
         }
+    }
 
-        let mut result: f32 = 0.0;
-        let mut sample_x = self.frequency * x;
-        let mut sample_y = self.frequency * y;
-        let mut persistence = 1.0;
+    /// Flow noise is not supported for three-dimensional input.
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        if self.flow.is_some() {
+            return Err(FLOW_3D_ERROR);
+        }
+
+        self.fractal(Vector3::new(x, y, z), false, |p, octave| {
+            snoise_3d(p.x, p.y, p.z, self.seed + octave)
+        })
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (lower, upper) = self.raw_bounds();
+        (self.output(lower), self.output(upper))
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
 
-        for octave in range(0, self.octaves) {
-            result += persistence *
-                (snoise_2d(sample_x, sample_y, self.seed + octave) + self.offset).abs();
-            sample_x *= self.lacunarity;
-            sample_y *= self.lacunarity;
+        let frequency = program.constant(self.frequency);
+        let lacunarity = program.constant(self.lacunarity);
+        let offset = program.constant(self.offset);
+        let mut sample = scale_shader(program, (x, y), frequency);
+        let mut result = program.constant(0.0);
+        let mut persistence = 1.0f32;
+
+        for octave in range(0, count.ceil() as uint) {
+            let noise = octave_shader(program, sample, self.seed, octave,
+                                      self.rotate_octaves, self.offset_octaves,
+                                      &self.flow);
+            let amplitude = program.constant(octave_weight(count, octave) *
+                                             persistence);
+            let shifted = program.add(noise, offset);
+            let magnitude = program.abs(shifted);
+            let term = program.mul(amplitude, magnitude);
+            result = program.add(result, term);
+            sample = scale_shader(program, sample, lacunarity);
             persistence *= self.persistence;
         }
 
-        Ok(result * BILLOWNOISE_SCALE * 2.0 - 1.0)
+        Ok(if self.normalize {
+            normalize_shader(program, result, self.raw_bounds())
+        } else {
+            let scale = program.constant(BILLOWNOISE_SCALE);
+            let (two, one) = (program.constant(2.0), program.constant(1.0));
+            let scaled = program.mul(result, scale);
+            let doubled = program.mul(scaled, two);
+            program.sub(doubled, one)
+        })
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let mut fields = vec![
+            ("seed", self.seed.to_json()),
+            ("frequency", number(self.frequency)),
+            ("persistence", number(self.persistence)),
+            ("lacunarity", number(self.lacunarity)),
+            ("octaves", number(self.octaves)),
+            ("max_frequency", number(self.max_frequency)),
+            ("rotate_octaves", self.rotate_octaves.to_json()),
+            ("offset_octaves", self.offset_octaves.to_json()),
+            ("offset", number(self.offset)),
+            ("normalize", self.normalize.to_json())
+        ];
+        fields.extend(self.flow.iter().map(|flow| ("flow", flow.to_json())));
+        Ok(describe("billow", fields))
     }
 }
 
@@ -243,7 +758,31 @@ pub struct RidgedMultifractalNoise {
     /// The number of octaves is the number of successive additive samples of
     /// the noise function this module will use to generate output. It is
     ///  essentially a measure of the level of "detail" in the output.
-    pub octaves: uint,
+    ///
+    /// The number of octaves may be fractional, in which case the last octave
+    /// is weighted by the fractional part.
+    pub octaves: f32,
+
+    /// The highest frequency to sample. Octaves above this frequency are
+    /// dropped, and the highest octave below it is faded out smoothly, which
+    /// avoids aliasing. This is usually the Nyquist limit of the sampling
+    /// grid, i.e. half the number of samples per unit distance.
+    pub max_frequency: f32,
+
+    /// If set, the gradients of the noise function rotate over time, so that
+    /// animating the time makes the output swirl rather than slide. Flow noise
+    /// only supports two-dimensional input.
+    pub flow: Option<Flow>,
+
+    /// Whether to rotate the input of each octave by a different angle, derived
+    /// from the seed. This hides the alignment of the noise function's lattice
+    /// with the axes, which is otherwise reinforced by every octave. The first
+    /// octave is rotated too.
+    pub rotate_octaves: bool,
+
+    /// Whether to offset the input of each octave by a different amount,
+    /// derived from the seed, so that the octaves do not share an origin.
+    pub offset_octaves: bool,
 
     /// The offset from zero, used to reduce visual artifacts when using the
     /// absolute value function.
@@ -254,7 +793,10 @@ pub struct RidgedMultifractalNoise {
     pub power: f32,
 
     /// The contribution of each successive octave to the weighting of the next.
-    pub gain: f32
+    pub gain: f32,
+
+    /// Whether to normalize the output to [-1, 1]; see the module docs.
+    pub normalize: bool
 }
 
 impl RidgedMultifractalNoise {
@@ -269,7 +811,9 @@ impl Default for RidgedMultifractalNoise {
     fn default() -> RidgedMultifractalNoise {
         RidgedMultifractalNoise {
             seed: 0, frequency: 1.0, lacunarity: 2.0,
-            offset: 1.0, power: -1.0, gain: 2.0, octaves: 10
+            offset: 1.0, power: -1.0, gain: 2.0, octaves: 10.0,
+            max_frequency: Float::infinity(), flow: None, rotate_octaves: false,
+            offset_octaves: false, normalize: false
         }
     }
 }
@@ -280,38 +824,1181 @@ impl Rand for RidgedMultifractalNoise {
     }
 }
 
-impl NoiseModule for RidgedMultifractalNoise {
-    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
-        if self.octaves <= 1 {
-            return Err("The number of octaves must be two or greater.");
-        } else if self.octaves > 30 {
-            return Err("The number of octaves must be less than 30.");
-        }
+impl RidgedMultifractalNoise {
+    /// Sums the octaves of `noise` sampled around `point`, where `noise` is
+    /// given the sample and the octave. The z coordinate is ignored by
+    /// two-dimensional noise functions, which should set `planar`.
+    fn fractal(&self, point: Vector3<f32>, planar: bool,
+               noise: |Vector3<f32>, uint| -> f32)
+        -> Result<f32, &'static str> {
+        try!(check_octaves(self.octaves, 2.0));
 
-        let mut sample_x = self.frequency * x;
-        let mut sample_y = self.frequency * y;
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let mut sample = point.mul_s(self.frequency);
 
         // Start with the default noise and weight values
         let mut result: f32 = 0.0;
         let mut weight = 1.0;
 
-        for octave in range(0, self.octaves) {
-            let mut signal = self.offset - RMULTINOISE_SCALE * snoise_2d(sample_x, sample_y, self.seed + octave).abs();
+        for octave in range(0, count.ceil() as uint) {
+            let domain = octave_domain(sample, self.seed, octave,
+                                       self.rotate_octaves,
+                                       self.offset_octaves, planar);
+            let mut signal = self.offset -
+                RMULTINOISE_SCALE * noise(domain, octave).abs();
             signal *= signal * weight;
 
-            result += signal * self.lacunarity.powf(octave as f32).powf(self.power);
+            result += octave_weight(count, octave) * signal *
+                self.lacunarity.powf(octave as f32).powf(self.power);
 
             // Shift sample
-            sample_x *= self.lacunarity;
-            sample_y *= self.lacunarity;
+            sample = sample.mul_s(self.lacunarity);
 
             // Set weights for the next iteration
             weight = signal * self.gain;
             weight = if weight > 1.0 { 1.0 } else if weight < 0.0 { 0.0 } else { weight };
         }
 
-        Ok(result * 1.25 - 1.0)
+        Ok(self.output(result))
+    }
+
+    /// A conservative range for the sum of the octaves.
+    fn raw_bounds(&self) -> (f32, f32) {
+        // Each signal is the square of a value in the following range,
+        // multiplied by a weight in [0, 1].
+        let lower = self.offset - RMULTINOISE_SCALE * SNOISE_BOUND;
+        let upper = self.offset;
+        let signal = (lower * lower).max(upper * upper);
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+        let weights = range(0, count.ceil() as uint).fold(0.0, |sum, octave| {
+            sum + octave_weight(count, octave) *
+                self.lacunarity.powf(octave as f32).powf(self.power)
+        });
+
+        (0.0, signal * weights)
+    }
+
+    /// Scales the sum of the octaves to the module's output.
+    fn output(&self, result: f32) -> f32 {
+        if self.normalize {
+            normalize(result, self.raw_bounds())
+        } else {
+            result * 1.25 - 1.0
+        }
+    }
+}
+
+impl NoiseModule for RidgedMultifractalNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let point = Vector3::new(x, y, 0.0);
+        match self.flow {
+            Some(ref flow) => self.fractal(point, true, |p, octave| {
+                sflow_2d(p.x, p.y, self.seed + octave, flow.angle(octave))
+            }),
+            None => self.fractal(point, true, |p, octave| {
+                snoise_2d(p.x, p.y, self.seed + octave)
+            })
+        }
+    }
+
+    /// Flow noise is not supported for three-dimensional input.
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        if self.flow.is_some() {
+            return Err(FLOW_3D_ERROR);
+        }
+
+        self.fractal(Vector3::new(x, y, z), false, |p, octave| {
+            snoise_3d(p.x, p.y, p.z, self.seed + octave)
+        })
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (lower, upper) = self.raw_bounds();
+        (self.output(lower), self.output(upper))
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let frequency = program.constant(self.frequency);
+        let lacunarity = program.constant(self.lacunarity);
+        let offset = program.constant(self.offset);
+        let scale = program.constant(RMULTINOISE_SCALE);
+        let gain = program.constant(self.gain);
+        let (zero, one) = (program.constant(0.0), program.constant(1.0));
+        let mut sample = scale_shader(program, (x, y), frequency);
+        let mut result = program.constant(0.0);
+        let mut weight = one;
+
+        for octave in range(0, count.ceil() as uint) {
+            let noise = octave_shader(program, sample, self.seed, octave,
+                                      self.rotate_octaves, self.offset_octaves,
+                                      &self.flow);
+            let magnitude = program.abs(noise);
+            let scaled = program.mul(scale, magnitude);
+            let signal = program.sub(offset, scaled);
+            let weighted = program.mul(signal, weight);
+            let signal = program.mul(signal, weighted);
+
+            let fade = program.constant(octave_weight(count, octave));
+            let spectral = program.constant(
+                self.lacunarity.powf(octave as f32).powf(self.power));
+            let term = program.mul(fade, signal);
+            let term = program.mul(term, spectral);
+            result = program.add(result, term);
+
+            sample = scale_shader(program, sample, lacunarity);
+
+            let next = program.mul(signal, gain);
+            let next = program.max(next, zero);
+            weight = program.min(next, one);
+        }
+
+        Ok(if self.normalize {
+            normalize_shader(program, result, self.raw_bounds())
+        } else {
+            let (scale, one) = (program.constant(1.25), program.constant(1.0));
+            let scaled = program.mul(result, scale);
+            program.sub(scaled, one)
+        })
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let mut fields = vec![
+            ("seed", self.seed.to_json()),
+            ("frequency", number(self.frequency)),
+            ("lacunarity", number(self.lacunarity)),
+            ("octaves", number(self.octaves)),
+            ("max_frequency", number(self.max_frequency)),
+            ("rotate_octaves", self.rotate_octaves.to_json()),
+            ("offset_octaves", self.offset_octaves.to_json()),
+            ("offset", number(self.offset)),
+            ("power", number(self.power)),
+            ("gain", number(self.gain)),
+            ("normalize", self.normalize.to_json())
+        ];
+        fields.extend(self.flow.iter().map(|flow| ("flow", flow.to_json())));
+        Ok(describe("ridged", fields))
     }
 }
 
 impl Modifiable for RidgedMultifractalNoise {}
+
+/// HybridMultifractalNoise weights the contribution of each octave by the
+/// output of the octaves before it, so that smooth regions (such as valleys)
+/// stay smooth while rough regions (such as peaks) get rougher. It sits
+/// somewhere between PinkNoise, which is homogeneous, and a true multifractal.
+///
+/// This implementation is based on Musgrave's "hybrid multifractal" [1].
+///
+/// 1. Musgrave's original website is gone, but the code is available [here]
+///    (https://engineering.purdue.edu/~ebertd/texture/1stEdition/musgrave/musgrave.c).
+#[deriving(Clone)]
+pub struct HybridMultifractalNoise {
+    /// The "seed" used to ensure reproducibility and variation in the output of
+    /// the module.
+    pub seed: uint,
+
+    /// The scale of the noise. Setting this value is equivalent to scaling all
+    /// input coordinates by the same value.
+    pub frequency: f32,
+
+    /// The frequency multiplier between successive octaves.
+    pub lacunarity: f32,
+
+    /// The number of octaves is the number of successive additive samples of
+    /// the noise function this module will use to generate output. It is
+    /// essentially a measure of the level of "detail" in the output.
+    ///
+    /// The number of octaves may be fractional, in which case the last octave
+    /// is weighted by the fractional part.
+    pub octaves: f32,
+
+    /// The highest frequency to sample. Octaves above this frequency are
+    /// dropped, and the highest octave below it is faded out smoothly, which
+    /// avoids aliasing. This is usually the Nyquist limit of the sampling
+    /// grid, i.e. half the number of samples per unit distance.
+    pub max_frequency: f32,
+
+    /// If set, the gradients of the noise function rotate over time, so that
+    /// animating the time makes the output swirl rather than slide. Flow noise
+    /// only supports two-dimensional input.
+    pub flow: Option<Flow>,
+
+    /// The offset added to each octave's noise sample, which controls how
+    /// quickly the weights grow.
+    pub offset: f32,
+
+    /// The exponent multiplier. This is used to calculate what Musgrave calls
+    /// 'spectral weights', and is the negative of his parameter $H$.
+    pub power: f32,
+
+    /// Whether to normalize the output to [-1, 1]; see the module docs.
+    pub normalize: bool
+}
+
+impl HybridMultifractalNoise {
+    /// Create a new object with the seed `seed` and all parameters set to their
+    /// default values.
+    pub fn new(seed: uint) -> HybridMultifractalNoise {
+        HybridMultifractalNoise { seed: seed, .. Default::default() }
+    }
+
+    /// Sums the octaves of `noise` sampled around `point`, where `noise` is
+    /// given the sample and the octave. The z coordinate is ignored by
+    /// two-dimensional noise functions.
+    fn fractal(&self, point: Vector3<f32>, noise: |Vector3<f32>, uint| -> f32)
+        -> Result<f32, &'static str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let mut sample = point.mul_s(self.frequency);
+
+        // The first octave is not weighted.
+        let mut result = SIGNAL_SCALE * noise(sample, 0)
+            + self.offset;
+        let mut weight = result;
+
+        for octave in range(1, count.ceil() as uint) {
+            sample = sample.mul_s(self.lacunarity);
+
+            // Prevent divergence
+            if weight > 1.0 { weight = 1.0; }
+
+            let signal = (SIGNAL_SCALE *
+                          noise(sample, octave) + self.offset) *
+                self.lacunarity.powf(octave as f32).powf(self.power);
+
+            result += octave_weight(count, octave) * weight * signal;
+            weight *= signal;
+        }
+
+        Ok(self.output(result))
+    }
+
+    /// A conservative range for the sum of the octaves.
+    fn raw_bounds(&self) -> (f32, f32) {
+        let bound = SIGNAL_SCALE * SNOISE_BOUND;
+        let noise = (self.offset - bound, self.offset + bound);
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let mut result = noise;
+        let mut weight = noise;
+
+        for octave in range(1, count.ceil() as uint) {
+            let (lower, upper) = weight;
+            weight = (lower.min(1.0), upper.min(1.0));
+
+            let spectral = self.lacunarity.powf(octave as f32).powf(self.power);
+            let signal = interval_mul(noise, (spectral, spectral));
+            let fade = octave_weight(count, octave);
+
+            result = interval_add(result, interval_mul(
+                interval_mul(weight, signal), (fade, fade)));
+            weight = interval_mul(weight, signal);
+        }
+
+        result
+    }
+
+    /// Scales the sum of the octaves to the module's output.
+    fn output(&self, result: f32) -> f32 {
+        if self.normalize {
+            normalize(result, self.raw_bounds())
+        } else {
+            result * HYBRIDNOISE_SCALE - 1.0
+        }
+    }
+}
+
+impl Default for HybridMultifractalNoise {
+    fn default() -> HybridMultifractalNoise {
+        HybridMultifractalNoise {
+            seed: 0, frequency: 1.0, lacunarity: 2.0,
+            offset: 0.7, power: -0.25, octaves: 6.0,
+            max_frequency: Float::infinity(), flow: None, normalize: false
+        }
+    }
+}
+
+impl Rand for HybridMultifractalNoise {
+    fn rand<R: Rng>(rng: &mut R) -> HybridMultifractalNoise {
+        HybridMultifractalNoise { seed: rng.gen(), .. Default::default() }
+    }
+}
+
+impl NoiseModule for HybridMultifractalNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let point = Vector3::new(x, y, 0.0);
+        match self.flow {
+            Some(ref flow) => self.fractal(point, |p, octave| {
+                sflow_2d(p.x, p.y, self.seed + octave, flow.angle(octave))
+            }),
+            None => self.fractal(point, |p, octave| {
+                snoise_2d(p.x, p.y, self.seed + octave)
+            })
+        }
+    }
+
+    /// Flow noise is not supported for three-dimensional input.
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        if self.flow.is_some() {
+            return Err(FLOW_3D_ERROR);
+        }
+
+        self.fractal(Vector3::new(x, y, z), |p, octave| {
+            snoise_3d(p.x, p.y, p.z, self.seed + octave)
+        })
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (lower, upper) = self.raw_bounds();
+        (self.output(lower), self.output(upper))
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let frequency = program.constant(self.frequency);
+        let lacunarity = program.constant(self.lacunarity);
+        let offset = program.constant(self.offset);
+        let scale = program.constant(SIGNAL_SCALE);
+        let one = program.constant(1.0);
+        let mut sample = scale_shader(program, (x, y), frequency);
+
+        // The first octave is not weighted.
+        let noise = octave_shader(program, sample, self.seed, 0, false, false,
+                                  &self.flow);
+        let scaled = program.mul(scale, noise);
+        let mut result = program.add(scaled, offset);
+        let mut weight = result;
+
+        for octave in range(1, count.ceil() as uint) {
+            sample = scale_shader(program, sample, lacunarity);
+            weight = program.min(weight, one);
+
+            let noise = octave_shader(program, sample, self.seed, octave,
+                                      false, false, &self.flow);
+            let scaled = program.mul(scale, noise);
+            let signal = program.add(scaled, offset);
+            let spectral = program.constant(
+                self.lacunarity.powf(octave as f32).powf(self.power));
+            let signal = program.mul(signal, spectral);
+
+            let fade = program.constant(octave_weight(count, octave));
+            let term = program.mul(fade, weight);
+            let term = program.mul(term, signal);
+            result = program.add(result, term);
+            weight = program.mul(weight, signal);
+        }
+
+        Ok(if self.normalize {
+            normalize_shader(program, result, self.raw_bounds())
+        } else {
+            let scale = program.constant(HYBRIDNOISE_SCALE);
+            let scaled = program.mul(result, scale);
+            program.sub(scaled, one)
+        })
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let mut fields = vec![
+            ("seed", self.seed.to_json()),
+            ("frequency", number(self.frequency)),
+            ("lacunarity", number(self.lacunarity)),
+            ("octaves", number(self.octaves)),
+            ("max_frequency", number(self.max_frequency)),
+            ("offset", number(self.offset)),
+            ("power", number(self.power)),
+            ("normalize", self.normalize.to_json())
+        ];
+        fields.extend(self.flow.iter().map(|flow| ("flow", flow.to_json())));
+        Ok(describe("hybrid", fields))
+    }
+}
+
+impl Modifiable for HybridMultifractalNoise {}
+
+/// HeteroTerrainNoise scales the contribution of each octave by the value
+/// accumulated so far, so that low-lying areas stay smooth while higher areas
+/// become increasingly rough. This is a simple "statistics by altitude" model
+/// of real terrain.
+///
+/// This implementation is based on Musgrave's "heterogeneous terrain" [1].
+///
+/// 1. Musgrave's original website is gone, but the code is available [here]
+///    (https://engineering.purdue.edu/~ebertd/texture/1stEdition/musgrave/musgrave.c).
+#[deriving(Clone)]
+pub struct HeteroTerrainNoise {
+    /// The "seed" used to ensure reproducibility and variation in the output of
+    /// the module.
+    pub seed: uint,
+
+    /// The scale of the noise. Setting this value is equivalent to scaling all
+    /// input coordinates by the same value.
+    pub frequency: f32,
+
+    /// The frequency multiplier between successive octaves.
+    pub lacunarity: f32,
+
+    /// The number of octaves is the number of successive additive samples of
+    /// the noise function this module will use to generate output. It is
+    /// essentially a measure of the level of "detail" in the output.
+    ///
+    /// The number of octaves may be fractional, in which case the last octave
+    /// is weighted by the fractional part.
+    pub octaves: f32,
+
+    /// The highest frequency to sample. Octaves above this frequency are
+    /// dropped, and the highest octave below it is faded out smoothly, which
+    /// avoids aliasing. This is usually the Nyquist limit of the sampling
+    /// grid, i.e. half the number of samples per unit distance.
+    pub max_frequency: f32,
+
+    /// If set, the gradients of the noise function rotate over time, so that
+    /// animating the time makes the output swirl rather than slide. Flow noise
+    /// only supports two-dimensional input.
+    pub flow: Option<Flow>,
+
+    /// The offset added to each octave's noise sample, which raises the
+    /// "sea level" of the terrain.
+    pub offset: f32,
+
+    /// The exponent multiplier. This is used to calculate what Musgrave calls
+    /// 'spectral weights', and is the negative of his parameter $H$.
+    pub power: f32,
+
+    /// Whether to normalize the output to [-1, 1]; see the module docs.
+    pub normalize: bool
+}
+
+impl HeteroTerrainNoise {
+    /// Create a new object with the seed `seed` and all parameters set to their
+    /// default values.
+    pub fn new(seed: uint) -> HeteroTerrainNoise {
+        HeteroTerrainNoise { seed: seed, .. Default::default() }
+    }
+
+    /// Sums the octaves of `noise` sampled around `point`, where `noise` is
+    /// given the sample and the octave. The z coordinate is ignored by
+    /// two-dimensional noise functions.
+    fn fractal(&self, point: Vector3<f32>, noise: |Vector3<f32>, uint| -> f32)
+        -> Result<f32, &'static str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let mut sample = point.mul_s(self.frequency);
+
+        // The first octave is not weighted.
+        let mut result = self.offset +
+            SIGNAL_SCALE * noise(sample, 0);
+
+        for octave in range(1, count.ceil() as uint) {
+            sample = sample.mul_s(self.lacunarity);
+
+            // Scale the contribution of this octave by the current altitude.
+            let increment = (SIGNAL_SCALE *
+                             noise(sample, octave) + self.offset) *
+                self.lacunarity.powf(octave as f32).powf(self.power) * result;
+
+            result += octave_weight(count, octave) * increment;
+        }
+
+        Ok(self.output(result))
+    }
+
+    /// A conservative range for the sum of the octaves.
+    fn raw_bounds(&self) -> (f32, f32) {
+        let bound = SIGNAL_SCALE * SNOISE_BOUND;
+        let noise = (self.offset - bound, self.offset + bound);
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        // Each octave multiplies the result by one plus its (weighted) signal.
+        let mut result = noise;
+
+        for octave in range(1, count.ceil() as uint) {
+            let spectral = self.lacunarity.powf(octave as f32).powf(self.power) *
+                octave_weight(count, octave);
+            let signal = interval_mul(noise, (spectral, spectral));
+            result = interval_mul(result, interval_add(signal, (1.0, 1.0)));
+        }
+
+        result
+    }
+
+    /// Scales the sum of the octaves to the module's output.
+    fn output(&self, result: f32) -> f32 {
+        if self.normalize {
+            normalize(result, self.raw_bounds())
+        } else {
+            result * HETERONOISE_SCALE - 1.0
+        }
+    }
+}
+
+impl Default for HeteroTerrainNoise {
+    fn default() -> HeteroTerrainNoise {
+        HeteroTerrainNoise {
+            seed: 0, frequency: 1.0, lacunarity: 2.0,
+            offset: 0.7, power: -0.9, octaves: 6.0,
+            max_frequency: Float::infinity(), flow: None, normalize: false
+        }
+    }
+}
+
+impl Rand for HeteroTerrainNoise {
+    fn rand<R: Rng>(rng: &mut R) -> HeteroTerrainNoise {
+        HeteroTerrainNoise { seed: rng.gen(), .. Default::default() }
+    }
+}
+
+impl NoiseModule for HeteroTerrainNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let point = Vector3::new(x, y, 0.0);
+        match self.flow {
+            Some(ref flow) => self.fractal(point, |p, octave| {
+                sflow_2d(p.x, p.y, self.seed + octave, flow.angle(octave))
+            }),
+            None => self.fractal(point, |p, octave| {
+                snoise_2d(p.x, p.y, self.seed + octave)
+            })
+        }
+    }
+
+    /// Flow noise is not supported for three-dimensional input.
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        if self.flow.is_some() {
+            return Err(FLOW_3D_ERROR);
+        }
+
+        self.fractal(Vector3::new(x, y, z), |p, octave| {
+            snoise_3d(p.x, p.y, p.z, self.seed + octave)
+        })
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (lower, upper) = self.raw_bounds();
+        (self.output(lower), self.output(upper))
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let frequency = program.constant(self.frequency);
+        let lacunarity = program.constant(self.lacunarity);
+        let offset = program.constant(self.offset);
+        let scale = program.constant(SIGNAL_SCALE);
+        let mut sample = scale_shader(program, (x, y), frequency);
+
+        // The first octave is not weighted.
+        let noise = octave_shader(program, sample, self.seed, 0, false, false,
+                                  &self.flow);
+        let scaled = program.mul(scale, noise);
+        let mut result = program.add(offset, scaled);
+
+        for octave in range(1, count.ceil() as uint) {
+            sample = scale_shader(program, sample, lacunarity);
+
+            // Scale the contribution of this octave by the current altitude.
+            let noise = octave_shader(program, sample, self.seed, octave,
+                                      false, false, &self.flow);
+            let scaled = program.mul(scale, noise);
+            let signal = program.add(scaled, offset);
+            let spectral = program.constant(
+                self.lacunarity.powf(octave as f32).powf(self.power));
+            let signal = program.mul(signal, spectral);
+            let increment = program.mul(signal, result);
+
+            let fade = program.constant(octave_weight(count, octave));
+            let term = program.mul(fade, increment);
+            result = program.add(result, term);
+        }
+
+        Ok(if self.normalize {
+            normalize_shader(program, result, self.raw_bounds())
+        } else {
+            let scale = program.constant(HETERONOISE_SCALE);
+            let one = program.constant(1.0);
+            let scaled = program.mul(result, scale);
+            program.sub(scaled, one)
+        })
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let mut fields = vec![
+            ("seed", self.seed.to_json()),
+            ("frequency", number(self.frequency)),
+            ("lacunarity", number(self.lacunarity)),
+            ("octaves", number(self.octaves)),
+            ("max_frequency", number(self.max_frequency)),
+            ("offset", number(self.offset)),
+            ("power", number(self.power)),
+            ("normalize", self.normalize.to_json())
+        ];
+        fields.extend(self.flow.iter().map(|flow| ("flow", flow.to_json())));
+        Ok(describe("hetero", fields))
+    }
+}
+
+impl Modifiable for HeteroTerrainNoise {}
+
+/// Samples the two-dimensional noise function of the given octave and its
+/// gradient, scaled to within about [-1, 1].
+fn signal_grad_2d(point: Vector2<f32>, seed: uint, octave: uint,
+                  flow: &Option<Flow>) -> (f32, Vector2<f32>) {
+    let (value, grad) = match *flow {
+        Some(ref flow) => sflow_2d_grad(point.x, point.y, seed + octave,
+                                        flow.angle(octave)),
+        None => snoise_2d_grad(point.x, point.y, seed + octave)
+    };
+    (value * SIGNAL_SCALE, grad.mul_s(SIGNAL_SCALE))
+}
+
+/// SwissTurbulenceNoise is a ridged fractal in which the derivatives of each
+/// octave are accumulated and used to displace the input of the octaves that
+/// follow. Ridges are thereby swept along their slopes, which gives the output
+/// an eroded, "alpine" appearance.
+///
+/// This implementation follows the one given by Giliam de Carpentier [1]. It
+/// only supports two-dimensional input.
+///
+/// 1. de Carpentier, Giliam and Rafael Bidarra. (2009). _Interactive GPU-based
+///    Procedural Heightfield Brushes_. Proceedings of the 4th International
+///    Conference on Foundations of Digital Games. See also [the accompanying
+///    article](http://www.decarpentier.nl/scape-procedural-extensions).
+#[deriving(Clone)]
+pub struct SwissTurbulenceNoise {
+    /// The "seed" used to ensure reproducibility and variation in the output of
+    /// the module.
+    pub seed: uint,
+
+    /// The scale of the noise. Setting this value is equivalent to scaling all
+    /// input coordinates by the same value.
+    pub frequency: f32,
+
+    /// The frequency multiplier between successive octaves.
+    pub lacunarity: f32,
+
+    /// The number of octaves is the number of successive additive samples of
+    /// the noise function this module will use to generate output. It is
+    /// essentially a measure of the level of "detail" in the output.
+    ///
+    /// The number of octaves may be fractional, in which case the last octave
+    /// is weighted by the fractional part.
+    pub octaves: f32,
+
+    /// The highest frequency to sample. Octaves above this frequency are
+    /// dropped, and the highest octave below it is faded out smoothly, which
+    /// avoids aliasing. This is usually the Nyquist limit of the sampling
+    /// grid, i.e. half the number of samples per unit distance.
+    pub max_frequency: f32,
+
+    /// If set, the gradients of the noise function rotate over time, so that
+    /// animating the time makes the output swirl rather than slide.
+    pub flow: Option<Flow>,
+
+    /// The amplitude multiplier between successive octaves, which is further
+    /// scaled by the (clamped) sum of the previous octaves.
+    pub gain: f32,
+
+    /// The amount by which the accumulated derivatives displace the input of
+    /// successive octaves.
+    pub warp: f32,
+
+    /// Whether to normalize the output to [-1, 1]; see the module docs.
+    pub normalize: bool
+}
+
+impl SwissTurbulenceNoise {
+    /// Create a new object with the seed `seed` and all parameters set to their
+    /// default values.
+    pub fn new(seed: uint) -> SwissTurbulenceNoise {
+        SwissTurbulenceNoise { seed: seed, .. Default::default() }
+    }
+}
+
+impl Default for SwissTurbulenceNoise {
+    fn default() -> SwissTurbulenceNoise {
+        SwissTurbulenceNoise {
+            seed: 0, frequency: 1.0, lacunarity: 2.0,
+            gain: 0.5, warp: 0.15, octaves: 6.0,
+            max_frequency: Float::infinity(), flow: None, normalize: false
+        }
+    }
+}
+
+impl Rand for SwissTurbulenceNoise {
+    fn rand<R: Rng>(rng: &mut R) -> SwissTurbulenceNoise {
+        SwissTurbulenceNoise { seed: rng.gen(), .. Default::default() }
+    }
+}
+
+impl NoiseModule for SwissTurbulenceNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let point = Vector2::new(x, y).mul_s(self.frequency);
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let mut result: f32 = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut dsum = Vector2::new(0.0f32, 0.0);
+
+        for octave in range(0, count.ceil() as uint) {
+            let sample = (point + dsum.mul_s(self.warp)).mul_s(frequency);
+            let (n, grad) = signal_grad_2d(sample, self.seed, octave,
+                                           &self.flow);
+
+            result += octave_weight(count, octave) * amplitude *
+                (1.0 - n.abs());
+
+            // The derivative of the ridge is the derivative of |n|, which
+            // points away from the crest.
+            dsum = dsum + grad.mul_s(-amplitude * n);
+
+            frequency *= self.lacunarity;
+            amplitude *= self.gain * result.max(0.0).min(1.0);
+        }
+
+        Ok(self.output(result))
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (lower, upper) = self.raw_bounds();
+        (self.output(lower), self.output(upper))
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let mut fields = vec![
+            ("seed", self.seed.to_json()),
+            ("frequency", number(self.frequency)),
+            ("lacunarity", number(self.lacunarity)),
+            ("octaves", number(self.octaves)),
+            ("max_frequency", number(self.max_frequency)),
+            ("gain", number(self.gain)),
+            ("warp", number(self.warp)),
+            ("normalize", self.normalize.to_json())
+        ];
+        fields.extend(self.flow.iter().map(|flow| ("flow", flow.to_json())));
+        Ok(describe("swiss", fields))
+    }
+}
+
+impl SwissTurbulenceNoise {
+    /// A conservative range for the sum of the octaves.
+    fn raw_bounds(&self) -> (f32, f32) {
+        let ridge = (1.0 - SIGNAL_SCALE * SNOISE_BOUND, 1.0);
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        // The amplitude of each octave is scaled by the gain and a factor in
+        // [0, 1], so its sign may only change if the gain is negative.
+        let mut amplitude = (1.0, 1.0);
+        let mut result = (0.0, 0.0);
+
+        for octave in range(0, count.ceil() as uint) {
+            let fade = octave_weight(count, octave);
+            result = interval_add(result, interval_mul(
+                interval_mul(amplitude, ridge), (fade, fade)));
+            amplitude = interval_mul(amplitude, (self.gain.min(0.0),
+                                                 self.gain.max(0.0)));
+        }
+
+        result
+    }
+
+    /// Scales the sum of the octaves to the module's output.
+    fn output(&self, result: f32) -> f32 {
+        if self.normalize {
+            normalize(result, self.raw_bounds())
+        } else {
+            result * SWISSNOISE_SCALE + SWISSNOISE_BIAS
+        }
+    }
+}
+
+impl Modifiable for SwissTurbulenceNoise {}
+
+/// JordanTurbulenceNoise sums the squares of its octaves, using their
+/// accumulated derivatives both to displace the input of successive octaves
+/// and to damp their amplitude on steep slopes. This results in smooth,
+/// flowing ridges with detailed flats between them.
+///
+/// This implementation follows the one given by Giliam de Carpentier [1]. The
+/// first octave has its own gain, warp and damping parameters, since it sets
+/// the overall shape of the output. It only supports two-dimensional input.
+///
+/// 1. de Carpentier, Giliam and Rafael Bidarra. (2009). _Interactive GPU-based
+///    Procedural Heightfield Brushes_. Proceedings of the 4th International
+///    Conference on Foundations of Digital Games. See also [the accompanying
+///    article](http://www.decarpentier.nl/scape-procedural-extensions).
+#[deriving(Clone)]
+pub struct JordanTurbulenceNoise {
+    /// The "seed" used to ensure reproducibility and variation in the output of
+    /// the module.
+    pub seed: uint,
+
+    /// The scale of the noise. Setting this value is equivalent to scaling all
+    /// input coordinates by the same value.
+    pub frequency: f32,
+
+    /// The frequency multiplier between successive octaves.
+    pub lacunarity: f32,
+
+    /// The number of octaves is the number of successive additive samples of
+    /// the noise function this module will use to generate output. It is
+    /// essentially a measure of the level of "detail" in the output.
+    ///
+    /// The number of octaves may be fractional, in which case the last octave
+    /// is weighted by the fractional part.
+    pub octaves: f32,
+
+    /// The highest frequency to sample. Octaves above this frequency are
+    /// dropped, and the highest octave below it is faded out smoothly, which
+    /// avoids aliasing. This is usually the Nyquist limit of the sampling
+    /// grid, i.e. half the number of samples per unit distance.
+    pub max_frequency: f32,
+
+    /// If set, the gradients of the noise function rotate over time, so that
+    /// animating the time makes the output swirl rather than slide.
+    pub flow: Option<Flow>,
+
+    /// The amplitude of the second octave, relative to the first.
+    pub first_gain: f32,
+
+    /// The amplitude multiplier between successive octaves after the second.
+    pub gain: f32,
+
+    /// The amount by which the derivatives of the first octave displace the
+    /// input of successive octaves.
+    pub first_warp: f32,
+
+    /// The amount by which the derivatives of later octaves displace the input
+    /// of successive octaves.
+    pub warp: f32,
+
+    /// The contribution of the derivatives of the first octave to the damping
+    /// of successive octaves.
+    pub first_damp: f32,
+
+    /// The contribution of the derivatives of later octaves to the damping of
+    /// successive octaves.
+    pub damp: f32,
+
+    /// The strength of the damping, from `0.0` (none) to `1.0` (full).
+    pub damp_scale: f32,
+
+    /// Whether to normalize the output to [-1, 1]; see the module docs.
+    pub normalize: bool
+}
+
+impl JordanTurbulenceNoise {
+    /// Create a new object with the seed `seed` and all parameters set to their
+    /// default values.
+    pub fn new(seed: uint) -> JordanTurbulenceNoise {
+        JordanTurbulenceNoise { seed: seed, .. Default::default() }
+    }
+}
+
+impl Default for JordanTurbulenceNoise {
+    fn default() -> JordanTurbulenceNoise {
+        JordanTurbulenceNoise {
+            seed: 0, frequency: 1.0, lacunarity: 2.0, octaves: 6.0,
+            first_gain: 0.8, gain: 0.5, first_warp: 0.4, warp: 0.35,
+            first_damp: 1.0, damp: 0.8, damp_scale: 1.0,
+            max_frequency: Float::infinity(), flow: None, normalize: false
+        }
+    }
+}
+
+impl Rand for JordanTurbulenceNoise {
+    fn rand<R: Rng>(rng: &mut R) -> JordanTurbulenceNoise {
+        JordanTurbulenceNoise { seed: rng.gen(), .. Default::default() }
+    }
+}
+
+impl NoiseModule for JordanTurbulenceNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let point = Vector2::new(x, y).mul_s(self.frequency);
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        // The first octave sets the overall shape.
+        let (n, grad) = signal_grad_2d(point, self.seed, 0, &self.flow);
+        let mut result = n * n;
+        let mut dsum_warp = grad.mul_s(self.first_warp * n);
+        let mut dsum_damp = grad.mul_s(self.first_damp * n);
+
+        let mut frequency = self.lacunarity;
+        let mut amplitude = self.first_gain;
+        let mut damped_amplitude = amplitude * self.gain;
+
+        for octave in range(1, count.ceil() as uint) {
+            let sample = point.mul_s(frequency) + dsum_warp;
+            let (n, grad) = signal_grad_2d(sample, self.seed, octave,
+                                           &self.flow);
+
+            // The derivative of n^2 is 2n times the derivative of n; the
+            // factor of two is absorbed into the warp and damp parameters.
+            result += octave_weight(count, octave) * damped_amplitude * n * n;
+            dsum_warp = dsum_warp + grad.mul_s(self.warp * n);
+            dsum_damp = dsum_damp + grad.mul_s(self.damp * n);
+
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+            damped_amplitude = amplitude *
+                (1.0 - self.damp_scale / (1.0 + dot(dsum_damp, dsum_damp)));
+        }
+
+        Ok(self.output(result))
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (lower, upper) = self.raw_bounds();
+        (self.output(lower), self.output(upper))
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let mut fields = vec![
+            ("seed", self.seed.to_json()),
+            ("frequency", number(self.frequency)),
+            ("lacunarity", number(self.lacunarity)),
+            ("octaves", number(self.octaves)),
+            ("max_frequency", number(self.max_frequency)),
+            ("first_gain", number(self.first_gain)),
+            ("gain", number(self.gain)),
+            ("first_warp", number(self.first_warp)),
+            ("warp", number(self.warp)),
+            ("first_damp", number(self.first_damp)),
+            ("damp", number(self.damp)),
+            ("damp_scale", number(self.damp_scale)),
+            ("normalize", self.normalize.to_json())
+        ];
+        fields.extend(self.flow.iter().map(|flow| ("flow", flow.to_json())));
+        Ok(describe("jordan", fields))
+    }
+}
+
+impl JordanTurbulenceNoise {
+    /// A conservative range for the sum of the octaves.
+    fn raw_bounds(&self) -> (f32, f32) {
+        let bound = SIGNAL_SCALE * SNOISE_BOUND;
+        let square = (0.0, bound * bound);
+
+        // The damping factor lies between 1 - damp_scale and 1.
+        let damping = ((1.0 - self.damp_scale).min(1.0),
+                       (1.0 - self.damp_scale).max(1.0));
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let mut result = square;
+        let mut amplitude = self.first_gain;
+
+        for octave in range(1, count.ceil() as uint) {
+            let fade = octave_weight(count, octave);
+            let damped = if octave == 1 {
+                (fade * amplitude * self.gain, fade * amplitude * self.gain)
+            } else {
+                interval_mul((fade * amplitude, fade * amplitude), damping)
+            };
+            result = interval_add(result, interval_mul(damped, square));
+            amplitude *= self.gain;
+        }
+
+        result
+    }
+
+    /// Scales the sum of the octaves to the module's output.
+    fn output(&self, result: f32) -> f32 {
+        if self.normalize {
+            normalize(result, self.raw_bounds())
+        } else {
+            result * JORDANNOISE_SCALE - 1.0
+        }
+    }
+}
+
+impl Modifiable for JordanTurbulenceNoise {}
+
+/// ErodedNoise is a variation on PinkNoise in which the gradient of each
+/// octave is accumulated, and the contribution of each octave is divided by
+/// $1 + |\sum \nabla N|\^2$. Detail is thereby suppressed on slopes, but
+/// left intact on flats and peaks, which cheaply imitates the effect of
+/// erosion.
+///
+/// This implementation follows the one given by Inigo Quilez [1], and uses the
+/// analytic derivatives of the noise function. It has the same parameters as
+/// PinkNoise, and wherever the accumulated slope is zero its output is that of
+/// PinkNoise, but it only supports two-dimensional input. The parameters are
+/// repeated rather than shared with PinkNoise because not all of its options
+/// carry over: rotated octaves would need their gradients rotated back before
+/// they could be summed.
+///
+/// 1. Quilez, Inigo. (2008). [_Value Noise Derivatives_]
+///    (http://www.iquilezles.org/www/articles/morenoise/morenoise.htm).
+#[deriving(Clone)]
+pub struct ErodedNoise {
+    /// The "seed" used to ensure reproducibility and variation in the output of
+    /// the module.
+    pub seed: uint,
+
+    /// The scale of the noise. Setting this value is equivalent to scaling all
+    /// input coordinates by the same value.
+    pub frequency: f32,
+
+    /// The apparent "roughness" of the noise. This value controls the amplitude
+    /// falloff of the successive octaves, so that `0.5` will scale the first
+    /// octave by `1.0`, the second by `0.5`, the third by `0.25`, and so on.
+    pub persistence: f32,
+
+    /// The frequency multiplier between successive octaves.
+    pub lacunarity: f32,
+
+    /// The number of octaves is the number of successive additive samples of
+    /// the noise function this module will use to generate output. It is
+    /// essentially a measure of the level of "detail" in the output.
+    ///
+    /// The number of octaves may be fractional, in which case the last octave
+    /// is weighted by the fractional part.
+    pub octaves: f32,
+
+    /// The highest frequency to sample. Octaves above this frequency are
+    /// dropped, and the highest octave below it is faded out smoothly, which
+    /// avoids aliasing. This is usually the Nyquist limit of the sampling
+    /// grid, i.e. half the number of samples per unit distance.
+    pub max_frequency: f32,
+
+    /// If set, the gradients of the noise function rotate over time, so that
+    /// animating the time makes the output swirl rather than slide.
+    pub flow: Option<Flow>,
+
+    /// Whether to normalize the output to [-1, 1]; see the module docs.
+    pub normalize: bool
+}
+
+impl ErodedNoise {
+    /// Create a new object with the seed `seed` and all parameters set to their
+    /// default values.
+    pub fn new(seed: uint) -> ErodedNoise {
+        ErodedNoise { seed: seed, .. Default::default() }
+    }
+}
+
+impl Default for ErodedNoise {
+    fn default() -> ErodedNoise {
+        ErodedNoise {
+            seed: 0, frequency: 1.0, persistence: 0.5,
+            lacunarity: 2.0, octaves: 6.0,
+            max_frequency: Float::infinity(), flow: None, normalize: false
+        }
+    }
+}
+
+impl Rand for ErodedNoise {
+    fn rand<R: Rng>(rng: &mut R) -> ErodedNoise {
+        ErodedNoise { seed: rng.gen(), .. Default::default() }
+    }
+}
+
+impl NoiseModule for ErodedNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        try!(check_octaves(self.octaves, 2.0));
+
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+
+        let mut result: f32 = 0.0;
+        let mut sample = Vector2::new(x, y).mul_s(self.frequency);
+        let mut persistence = 1.0;
+        let mut dsum = Vector2::new(0.0f32, 0.0);
+
+        for octave in range(0, count.ceil() as uint) {
+            let (n, grad) = signal_grad_2d(sample, self.seed, octave,
+                                           &self.flow);
+
+            // As in Quilez's version, the gradients are summed in the
+            // coordinates of each octave, so that high-frequency octaves do
+            // not dominate the damping.
+            dsum = dsum + grad;
+            result += octave_weight(count, octave) * persistence * n /
+                (1.0 + dot(dsum, dsum));
+
+            sample = sample.mul_s(self.lacunarity);
+            persistence *= self.persistence;
+        }
+
+        Ok(self.output(result))
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (lower, upper) = self.raw_bounds();
+        (self.output(lower), self.output(upper))
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let mut fields = vec![
+            ("seed", self.seed.to_json()),
+            ("frequency", number(self.frequency)),
+            ("persistence", number(self.persistence)),
+            ("lacunarity", number(self.lacunarity)),
+            ("octaves", number(self.octaves)),
+            ("max_frequency", number(self.max_frequency)),
+            ("normalize", self.normalize.to_json())
+        ];
+        fields.extend(self.flow.iter().map(|flow| ("flow", flow.to_json())));
+        Ok(describe("eroded", fields))
+    }
+}
+
+impl ErodedNoise {
+    /// A conservative range for the sum of the octaves.
+    fn raw_bounds(&self) -> (f32, f32) {
+        // Damping only ever reduces the contribution of each octave.
+        let count = octave_count(self.octaves, self.frequency,
+                                 self.lacunarity, self.max_frequency);
+        let bound = amplitude_sum(self.persistence, count) *
+            SIGNAL_SCALE * SNOISE_BOUND;
+        (-bound, bound)
+    }
+
+    /// Scales the sum of the octaves to the module's output.
+    fn output(&self, result: f32) -> f32 {
+        if self.normalize {
+            normalize(result, self.raw_bounds())
+        } else {
+            // Match the scale of PinkNoise where there is no damping.
+            result * PINKNOISE_SCALE / SIGNAL_SCALE
+        }
+    }
+}
+
+impl Modifiable for ErodedNoise {}
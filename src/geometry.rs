@@ -7,10 +7,16 @@
 //! While these types do not produce "coherent" noise per se, they can be very
 //! useful when constructing complex, multi-component noise.
 
+use std::sync::Arc;
+
 use cgmath::vector::{Vector2, Vector, EuclideanVector};
 
+use serialize::json::{Json, ToJson};
+
 use primitives::NoiseModule;
 use modifiers::Modifiable;
+use serial::{describe, number};
+use shader::{Program, Register};
 
 /// ConstNoise will generate the same value of noise for any input coordinate.
 ///
@@ -44,6 +50,26 @@ impl NoiseModule for ConstNoise {
     fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
         Ok(self.value)
     }
+
+    #[allow(unused_variable)]
+    #[inline]
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        Ok(self.value)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        (self.value, self.value)
+    }
+
+    #[allow(unused_variable)]
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        Ok(program.constant(self.value))
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("const", vec![("value", number(self.value))]))
+    }
 }
 
 impl Modifiable for ConstNoise {}
@@ -59,6 +85,14 @@ impl NoiseModule for CheckerboardNoise {
         if ((x as int) & 1 ^ (y as int) & 1) != 0
             { Ok(-1.0) } else { Ok(1.0) }
     }
+
+    fn bounds(&self) -> (f32, f32) {
+        (-1.0, 1.0)
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("checkerboard", vec![]))
+    }
 }
 
 impl Modifiable for CheckerboardNoise {}
@@ -99,6 +133,14 @@ impl NoiseModule for CylinderNoise {
         let fract = v.mul_s(self.frequency).length().fract();
         Ok(1.0 - fract.min(1.0 - fract) * 4.0)
     }
+
+    fn bounds(&self) -> (f32, f32) {
+        (-1.0, 1.0)
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("cylinder", vec![("frequency", number(self.frequency))]))
+    }
 }
 
 impl Modifiable for CylinderNoise {}
@@ -107,10 +149,17 @@ impl Modifiable for CylinderNoise {}
 pub type SphereNoise = CylinderNoise;
 
 /// Functions applicable for passing to FunctionNoise.
-pub type FunctionNoiseFunction = fn<'a>(x: f32, y: f32) -> Result<f32, &'a str>;
+pub type FunctionNoiseFunction =
+    Fn(f32, f32) -> Result<f32, &'static str> + Send + Sync;
 
 /// FunctionNoise allows the use of an arbitrary function to generate noise.
 ///
+/// The function may be any closure, and so can capture the parameters of the
+/// noise. It is held in an Arc, which clones of the module share. A function
+/// cannot itself be written out, so only modules created from a
+/// [Registry](../serial/struct.Registry.html), which know the name the
+/// function is registered under, can be described.
+///
 /// ## Example
 ///
 /// Implementing a "Gaussian" (Multivariate Normal) Noise generator.
@@ -118,37 +167,51 @@ pub type FunctionNoiseFunction = fn<'a>(x: f32, y: f32) -> Result<f32, &'a str>;
 /// ```rust
 /// use grunge::modules::{NoiseModule, FunctionNoise};
 ///
-/// fn gaussian<'a>(x: f32, y: f32) -> Result<f32, &'a str> {
-///     Ok(1.0 / (2.0 * Float::pi()) * (- 0.5 * (x.powi(2) + y.powi(2))).exp())
-/// }
-///
-/// let func = gaussian;
-/// let gauss = FunctionNoise::new(&func);
+/// let variance = 0.15;
+/// let gauss = FunctionNoise::new(move |x: f32, y: f32| {
+///     Ok(1.0 / (2.0 * Float::pi() * variance) *
+///        (-0.5 / variance * (x.powi(2) + y.powi(2))).exp())
+/// });
 /// println!("{}", gauss.generate_2d(1.0, 1.0));
 /// ```
 #[experimental]
-pub struct FunctionNoise<'a> {
+pub struct FunctionNoise {
     /// The function which maps points to a noise value.
-    pub func: &'a FunctionNoiseFunction
+    pub func: Arc<FunctionNoiseFunction>,
+
+    /// The name the function is registered under, if any.
+    pub name: Option<String>
 }
 
-impl<'a> FunctionNoise<'a> {
+impl FunctionNoise {
     /// Create a new FunctionNoise with the given function.
     #[inline]
-    pub fn new(func: &'a FunctionNoiseFunction)
-        -> FunctionNoise<'a> { FunctionNoise { func: func } }
-
+    pub fn new<F: Fn(f32, f32) -> Result<f32, &'static str> + Send + Sync>
+        (func: F) -> FunctionNoise {
+        FunctionNoise { func: Arc::new(func), name: None }
+    }
 }
 
-impl<'a> Clone for FunctionNoise<'a> {
-    fn clone(&self) -> FunctionNoise<'a> {
-        FunctionNoise { func: self.func }
+impl Clone for FunctionNoise {
+    fn clone(&self) -> FunctionNoise {
+        FunctionNoise { func: self.func.clone(), name: self.name.clone() }
     }
 }
 
-impl<'a> NoiseModule for FunctionNoise<'a> {
+impl NoiseModule for FunctionNoise {
     #[inline]
     fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
         (*self.func)(x, y)
     }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        match self.name {
+            Some(ref name) => Ok(describe("function", vec![
+                ("name", name.to_json())
+            ])),
+            None => Err("Only registered functions can be serialized.")
+        }
+    }
 }
+
+impl Modifiable for FunctionNoise {}
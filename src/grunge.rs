@@ -18,8 +18,8 @@
 //! ## Current Design & Features
 //!
 //! All currently implemented noise generators are re-exported in the [modules]
-//! (modules/index.html) module. At present, only two-dimensional simplex noise
-//! is implemented.
+//! (modules/index.html) module. Simplex noise is implemented in two and three
+//! dimensions, although not every module supports three-dimensional input yet.
 //!
 //! ## Example
 //!
@@ -71,6 +71,7 @@
 #![unstable]
 
 extern crate cgmath;
+extern crate serialize;
 
 pub mod modules;
 
@@ -78,5 +79,13 @@ pub mod primitives;
 pub mod vectors;
 
 pub mod fractal;
+pub mod fields;
 pub mod geometry;
 pub mod modifiers;
+pub mod serial;
+pub mod expr;
+pub mod shader;
+
+mod octaves;
+mod boxes;
+mod reader;
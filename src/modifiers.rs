@@ -9,22 +9,46 @@
 //! detail on their use.
 
 use cgmath::angle::rad;
-use cgmath::vector::Vector2;
+use cgmath::matrix::{Matrix, Matrix3, Matrix4};
+use cgmath::quaternion::Quaternion;
+use cgmath::vector::{Vector2, Vector3, Vector4};
 use cgmath::rotation::{Rotation, Rotation2, Basis2};
 
+use std::default::Default;
+use std::sync::Arc;
+
+use serialize::json::{Json, ToJson};
+
 use primitives::NoiseModule;
+use fractal::PinkNoise;
+use serial::{describe, number, numbers};
+use shader::{Program, Register, CompiledNoise};
+use boxes::clone;
 
-// Dirty little hacks for dealing with boxes and trait type-erasure
-fn clone<T: Clone>(t: &T) -> T { t.clone() }
+/// Linear interpolation between `a` and `b`, where `alpha` is in [0, 1].
+#[inline]
+fn lerp(a: f32, b: f32, alpha: f32) -> f32 {
+    a + alpha * (b - a)
+}
 
-impl Clone for Box<NoiseModule> {
-    fn clone(&self) -> Box<NoiseModule> { self.to_box() }
+/// The cubic "s-curve" $3a\^2 - 2a\^3$, which eases `alpha` in and out of the
+/// interval [0, 1].
+#[inline]
+fn scurve3(alpha: f32) -> f32 {
+    alpha * alpha * (3.0 - 2.0 * alpha)
 }
 
 /// This trait provides a nice way of turning unmodified noise into modified
 /// noise. All NoiseModule implementations also implement this trait, so that
 /// you can usually call `noise.clamp(0.0, 1.0)` instead of instantiating the
-/// ClampedNoise type directly.
+/// ClampedNoise type directly. The methods `clamp()`, `scalebias()`,
+/// `translate()`, `rotate()` and `scale_input()` return a generic modifier
+/// (e.g. Clamped) holding a copy of the source by value. The rest still return
+/// types that hold their source as a `Box<NoiseModule>`: Rotated3Noise from
+/// `rotate_3d()`, AffineNoise from `affine()`, TurbulenceNoise from
+/// `turbulence()`, DisplaceNoise from `displace()`, TerraceNoise from
+/// `terrace()` and CurveNoise from `curve()`, as do the modules with several
+/// sources, such as SelectNoise and BlendNoise.
 ///
 /// ## Example
 ///
@@ -41,42 +65,124 @@ impl Clone for Box<NoiseModule> {
 pub trait Modifiable : NoiseModule {
     /// Modifies a source noise module by bounding its output between a `min`
     ///  and `max` value.
-    fn clamp(&self, min: f32, max: f32) -> ClampedNoise {
-        ClampedNoise { source: self.to_box(), min: min, max: max }
+    fn clamp(&self, min: f32, max: f32) -> Clamped<Self> {
+        Clamped { source: self.clone(), min: min, max: max }
     }
 
     /// Modifies a source noise module by multiplying its output by a constant
     /// `scale` and applying a constant `bias` shift up or downwards.
-    fn scalebias(&self, scale: f32, bias: f32) -> ScaledBiasedNoise {
-        ScaledBiasedNoise { source: self.to_box(), scale: scale, bias: bias }
+    fn scalebias(&self, scale: f32, bias: f32) -> ScaledBiased<Self> {
+        ScaledBiased { source: self.clone(), scale: scale, bias: bias }
     }
 
     /// Modifies a source noise module by translating its input by a constant
     /// vector.
-    fn translate(&self, translation: Vector2<f32>) -> TranslatedNoise {
-        TranslatedNoise { source: self.to_box(), translation: translation }
+    fn translate(&self, translation: Vector2<f32>) -> Translated<Self> {
+        Translated { source: self.clone(), translation: translation }
     }
 
     /// Modifies a source noise module by rotating its input by an angle.
     // Note that the angle is assumed to be in radians.
-    fn rotate(&self, rotation: f32) -> RotatedNoise {
-        RotatedNoise {
-            source: self.to_box(),
-            rotation: Rotation2::from_angle(rad(rotation))
+    fn rotate(&self, rotation: f32) -> Rotated<Self> {
+        rotated(self.clone(), rotation)
+    }
+
+    /// Modifies a source noise module by rotating its input in three
+    /// dimensions. Two-dimensional input is taken from the rotated plane.
+    fn rotate_3d(&self, rotation: Quaternion<f32>) -> Rotated3Noise {
+        Rotated3Noise { source: self.to_box(), rotation: rotation }
+    }
+
+    /// Modifies a source noise module by multiplying its input coordinates by
+    /// a constant `scale` along each axis.
+    fn scale_input(&self, scale: Vector3<f32>) -> ScaledInput<Self> {
+        ScaledInput { source: self.clone(), scale: scale }
+    }
+
+    /// Modifies a source noise module by applying an affine transformation,
+    /// given as a homogeneous `matrix`, to its input coordinates.
+    fn affine(&self, matrix: Matrix4<f32>) -> AffineNoise {
+        AffineNoise { source: self.to_box(), matrix: matrix }
+    }
+
+    /// Modifies a source noise module by displacing its input coordinates
+    /// with fractal noise, using the given `frequency`, `power` and
+    /// `roughness` (number of octaves) for the distortion. The roughness must
+    /// be at least two.
+    ///
+    /// The distortion modules are always seeded from zero, so every module
+    /// built this way with the same frequency is displaced by the same field.
+    /// Use `TurbulenceNoise::new()` to give each its own seed.
+    fn turbulence(&self, frequency: f32, power: f32, roughness: uint)
+        -> Result<TurbulenceNoise, &'static str> {
+        TurbulenceNoise::from_box(self.to_box(), 0, frequency, power,
+                                  roughness)
+    }
+
+    /// Modifies a source noise module by displacing its input coordinates by
+    /// the output of the modules `x` and `y`.
+    fn displace(&self, x: &NoiseModule, y: &NoiseModule) -> DisplaceNoise {
+        DisplaceNoise {
+            source: self.to_box(), x_displace: x.to_box(),
+            y_displace: y.to_box(), z_displace: None
+        }
+    }
+
+    /// Modifies a source noise module by mapping its output onto a series of
+    /// terraces, whose edges are given by the control `points`.
+    fn terrace(&self, points: &[f32]) -> TerraceNoise {
+        TerraceNoise {
+            source: self.to_box(), points: sorted_points(points),
+            invert: false, smoothness: 0.5
         }
     }
+
+    /// Modifies a source noise module by remapping its output through a
+    /// monotone cubic spline passing through the given (input, output)
+    /// control `points`. See CurveNoise for the requirements on the points.
+    fn curve(&self, points: &[(f32, f32)])
+        -> Result<CurveNoise, &'static str> {
+        CurveNoise::from_box(self.to_box(), points, MonotoneCubic)
+    }
+
+    /// Compiles a source noise module into a flat program that computes the
+    /// same two-dimensional output faster. See CompiledNoise.
+    fn compile(&self) -> CompiledNoise {
+        CompiledNoise::from_box(self.to_box())
+    }
 }
 
 /// Modifies a source noise module by bounding its output between a `min` and
-/// `max` value.
+/// `max` value. This is the type returned by `clamp()`.
+///
+/// The modifiers returned by the methods of Modifiable own their source by
+/// value, so that calls through a chain of them are statically dispatched and
+/// can be inlined by the compiler. For graphs whose structure is only known at
+/// run time, such as those loaded from descriptions, the source may instead be
+/// a `Box<NoiseModule>`; ClampedNoise names that case. Both are described the
+/// same.
+#[deriving(Clone)]
+pub struct Clamped<S> {
+    /// The source module.
+    pub source: S,
+
+    /// The absolute lower bound for the noise output.
+    pub min: f32,
+
+    /// The absolute upper bound for the noise output.
+    pub max: f32,
+}
+
+/// Clamped with a boxed source.
 ///
 /// ## Example
 ///
-/// ClampedNoise can be created one of three ways: with `new()`, which uses the
-/// default min and max values of `-1.0` and `1.0`, respectively; with a struct
-/// literal, or with the `clamp()` method on a source module itself. Note that
-/// when using the struct literal, you will need to convert the source module to
-/// a boxed representation using NoiseModule's `to_box()` method.
+/// ClampedNoise can be created with `new()`, which uses the default min and
+/// max values of `-1.0` and `1.0`, respectively, or with a struct literal. Note
+/// that when using the struct literal, you will need to convert the source
+/// module to a boxed representation using NoiseModule's `to_box()` method. The
+/// `clamp()` method on a source module itself gives the same output, but
+/// returns a Clamped that holds the source by value.
 ///
 /// ```rust
 /// use grunge::modules::{NoiseModule, Modifiable, PinkNoise, ClampedNoise};
@@ -90,52 +196,65 @@ pub trait Modifiable : NoiseModule {
 /// assert_eq!(other_clamp.generate_2d(1.0, -1.0),
 ///            final_clamp.generate_2d(1.0, -1.0));
 /// ```
-pub struct ClampedNoise {
-    /// The source module.
-    pub source: Box<NoiseModule>,
+pub type ClampedNoise = Clamped<Box<NoiseModule>>;
 
-    /// The absolute lower bound for the noise output.
-    pub min: f32,
-
-    /// The absolute upper bound for the noise output.
-    pub max: f32,
-}
-
-impl ClampedNoise {
+impl Clamped<Box<NoiseModule>> {
     /// Creates a new ClampedNoise with a default min and max value of `-1.0`
     /// and `1.0`, respectively.
     pub fn new(source: &NoiseModule) -> ClampedNoise {
-        ClampedNoise { source: source.to_box(), min: -1.0, max: 1.0 }
+        Clamped { source: source.to_box(), min: -1.0, max: 1.0 }
     }
 }
 
-impl Clone for ClampedNoise {
-    fn clone(&self) -> ClampedNoise {
-        ClampedNoise {
-            source: clone(&self.source),
-            min: self.min.clone(), max: self.max.clone()
-        }
+impl<S: NoiseModule> NoiseModule for Clamped<S> {
+    #[inline]
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let val = try!(self.source.generate_2d(x, y));
+        Ok(if val > self.max { self.max }
+           else if val < self.min { self.min }
+           else { val })
     }
-}
 
-impl NoiseModule for ClampedNoise {
-    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
-        match self.source.generate_2d(x, y) {
-            Ok(val) => if val > self.max { Ok(self.max) }
-                       else if val < self.min { Ok(self.min) }
-                       else { Ok(val) },
-            err => err
-        }
+    #[inline]
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        let val = try!(self.source.generate_3d(x, y, z));
+        Ok(if val > self.max { self.max }
+           else if val < self.min { self.min }
+           else { val })
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (lower, upper) = self.source.bounds();
+        (lower.max(self.min).min(self.max), upper.min(self.max).max(self.min))
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        let value = try!(program.lower(&self.source, x, y));
+        let min = program.constant(self.min);
+        let max = program.constant(self.max);
+        let value = program.max(value, min);
+        Ok(program.min(value, max))
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("clamp", vec![
+            ("source", try!(self.source.to_json())),
+            ("min", number(self.min)),
+            ("max", number(self.max))
+        ]))
     }
 }
 
-impl Modifiable for ClampedNoise {}
+impl<S: NoiseModule> Modifiable for Clamped<S> {}
 
 /// Modifies a source noise module by multiplying its output by a constant and
-/// applying a constant shift up or downwards.
-pub struct ScaledBiasedNoise {
+/// applying a constant shift up or downwards. This is the type returned by
+/// `scalebias()`; see Clamped.
+#[deriving(Clone)]
+pub struct ScaledBiased<S> {
     /// The source module.
-    pub source: Box<NoiseModule>,
+    pub source: S,
 
     /// The linear scaling to apply to noise output.
     pub scale: f32,
@@ -144,136 +263,523 @@ pub struct ScaledBiasedNoise {
     pub bias: f32,
 }
 
-impl ScaledBiasedNoise {
+/// ScaledBiased with a boxed source.
+pub type ScaledBiasedNoise = ScaledBiased<Box<NoiseModule>>;
+
+impl ScaledBiased<Box<NoiseModule>> {
     /// Creates a new ScaledBiasedNoise with a default scale and bias values of
     /// `1.0` and `0.0`, respectively (i.e. no change in output).
     pub fn new(source: &NoiseModule) -> ScaledBiasedNoise {
-        ScaledBiasedNoise { source: source.to_box(), scale: 1.0, bias: 0.0 }
+        ScaledBiased { source: source.to_box(), scale: 1.0, bias: 0.0 }
     }
 }
 
-impl Clone for ScaledBiasedNoise {
-    fn clone(&self) -> ScaledBiasedNoise {
-        ScaledBiasedNoise {
-            source: clone(&self.source),
-            scale: self.scale.clone(), bias: self.bias.clone()
-        }
+impl<S: NoiseModule> NoiseModule for ScaledBiased<S> {
+    #[inline]
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        Ok(try!(self.source.generate_2d(x, y)) * self.scale + self.bias)
     }
-}
 
-impl NoiseModule for ScaledBiasedNoise {
-    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
-        match self.source.generate_2d(x, y) {
-            Ok(val) => Ok(val * self.scale + self.bias),
-            err => err
+    #[inline]
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        Ok(try!(self.source.generate_3d(x, y, z)) * self.scale + self.bias)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (lower, upper) = self.source.bounds();
+        if self.scale == 0.0 {
+            (self.bias, self.bias)
+        } else if self.scale > 0.0 {
+            (lower * self.scale + self.bias, upper * self.scale + self.bias)
+        } else {
+            (upper * self.scale + self.bias, lower * self.scale + self.bias)
         }
     }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        let value = try!(program.lower(&self.source, x, y));
+        let scale = program.constant(self.scale);
+        let bias = program.constant(self.bias);
+        let scaled = program.mul(value, scale);
+        Ok(program.add(scaled, bias))
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("scalebias", vec![
+            ("source", try!(self.source.to_json())),
+            ("scale", number(self.scale)),
+            ("bias", number(self.bias))
+        ]))
+    }
 }
 
-impl Modifiable for ScaledBiasedNoise {}
+impl<S: NoiseModule> Modifiable for ScaledBiased<S> {}
 
-/// Modifies a source noise module by multiplying its input by a constant vector
-/// shift.
-pub struct TranslatedNoise {
+/// Modifies a source noise module by translating its input by a constant
+/// vector. This is the type returned by `translate()`; see Clamped.
+#[deriving(Clone)]
+pub struct Translated<S> {
     /// The source module.
-    pub source: Box<NoiseModule>,
+    pub source: S,
 
     /// The linear transformation to apply to input coordinates.
     pub translation: Vector2<f32>,
 }
 
-impl TranslatedNoise {
+/// Translated with a boxed source.
+pub type TranslatedNoise = Translated<Box<NoiseModule>>;
+
+impl Translated<Box<NoiseModule>> {
     /// Creates a new TranslatedNoise with the given source and translation.
     pub fn new(source: &NoiseModule, translation: Vector2<f32>)
         -> TranslatedNoise {
-        TranslatedNoise { source: source.to_box(), translation: translation }
+        Translated { source: source.to_box(), translation: translation }
     }
 }
 
-impl Clone for TranslatedNoise {
-    fn clone(&self) -> TranslatedNoise {
-        TranslatedNoise {
-            source: clone(&self.source), translation: self.translation.clone()
-        }
+impl<S: NoiseModule> NoiseModule for Translated<S> {
+    #[inline]
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        self.source.generate_2d(x + self.translation.x, y + self.translation.y)
+    }
+
+    /// The translation is applied in the x-y plane only.
+    #[inline]
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        self.source.generate_3d(x + self.translation.x,
+                                y + self.translation.y, z)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        self.source.bounds()
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        let dx = program.constant(self.translation.x);
+        let dy = program.constant(self.translation.y);
+        let (x, y) = (program.add(x, dx), program.add(y, dy));
+        program.lower(&self.source, x, y)
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let t = self.translation;
+        Ok(describe("translate", vec![
+            ("source", try!(self.source.to_json())),
+            ("translation", numbers(&[t.x, t.y]))
+        ]))
+    }
+}
+
+impl<S: NoiseModule> Modifiable for Translated<S> {}
+
+/// Modifies a source noise module by rotating its input by some angle. This is
+/// the type returned by `rotate()`; see Clamped.
+#[deriving(Clone)]
+pub struct Rotated<S> {
+    /// The source module.
+    pub source: S,
+
+    /// The rotation to apply to input coordinates.
+    rotation: Basis2<f32>,
+
+    /// The angle of the rotation, in radians, kept so that the module can be
+    /// described exactly.
+    angle: f32,
+}
+
+/// Rotated with a boxed source.
+#[experimental]
+pub type RotatedNoise = Rotated<Box<NoiseModule>>;
+
+impl Rotated<Box<NoiseModule>> {
+    /// Creates a new RotatedNoise with the given source and rotation.
+    pub fn new(source: &NoiseModule, rotation: f32) -> RotatedNoise {
+        rotated(source.to_box(), rotation)
+    }
+}
+
+/// Rotates `source` by `rotation` radians.
+fn rotated<S>(source: S, rotation: f32) -> Rotated<S> {
+    Rotated {
+        source: source,
+        rotation: Rotation2::from_angle(rad(rotation)),
+        angle: rotation
     }
 }
 
-impl NoiseModule for TranslatedNoise {
+impl<S: NoiseModule> NoiseModule for Rotated<S> {
+    #[inline]
     fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
-        self.source.generate_2d(x + self.translation.x, y + self.translation.y)
+        let n = self.rotation.rotate_vector(&Vector2::new(x, y));
+        self.source.generate_2d(n.x, n.y)
+    }
+
+    /// The rotation is applied about the z axis.
+    #[inline]
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        let n = self.rotation.rotate_vector(&Vector2::new(x, y));
+        self.source.generate_3d(n.x, n.y, z)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        self.source.bounds()
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        // The same products and sums as the rotation matrix.
+        let (sin, cos) = self.angle.sin_cos();
+        let (sin, neg_sin) = (program.constant(sin), program.constant(-sin));
+        let cos = program.constant(cos);
+        let (xc, ys) = (program.mul(cos, x), program.mul(neg_sin, y));
+        let (xs, yc) = (program.mul(sin, x), program.mul(cos, y));
+        let (x, y) = (program.add(xc, ys), program.add(xs, yc));
+        program.lower(&self.source, x, y)
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("rotate", vec![
+            ("source", try!(self.source.to_json())),
+            ("rotation", number(self.angle))
+        ]))
     }
 }
 
-impl Modifiable for TranslatedNoise {}
+impl<S: NoiseModule> Modifiable for Rotated<S> {}
 
-/// Modifies a source noise module by rotating its input by some angle.
-#[experimental]
-pub struct RotatedNoise {
+/// Modifies a source noise module by rotating its input in three dimensions.
+///
+/// Two-dimensional input is treated as lying in the plane z = 0, so that the
+/// source module is sampled in three dimensions on a tilted plane. Slicing
+/// three-dimensional noise this way hides the alignment of its lattice with the
+/// axes, which is otherwise visible in two-dimensional maps.
+pub struct Rotated3Noise {
     /// The source module.
     pub source: Box<NoiseModule>,
 
     /// The rotation to apply to input coordinates.
-    rotation: Basis2<f32>,
+    pub rotation: Quaternion<f32>,
 }
 
-impl RotatedNoise {
-    /// Creates a new RotatedNoise with the given source and rotation.
-    pub fn new(source: &NoiseModule, rotation: f32)
-        -> RotatedNoise {
-        RotatedNoise {
-            source: source.to_box(),
-            rotation: Rotation2::from_angle(rad(rotation))
-        }
+/// The quaternion rotating by `angle` radians about the unit vector `axis`.
+fn axis_angle(axis: Vector3<f32>, angle: f32) -> Quaternion<f32> {
+    let (sin, cos) = (angle * 0.5).sin_cos();
+    Quaternion::new(cos, axis.x * sin, axis.y * sin, axis.z * sin)
+}
+
+impl Rotated3Noise {
+    /// Creates a new Rotated3Noise with the given source and rotation.
+    pub fn new(source: &NoiseModule, rotation: Quaternion<f32>)
+        -> Rotated3Noise {
+        Rotated3Noise { source: source.to_box(), rotation: rotation }
+    }
+
+    /// Creates a new Rotated3Noise with the given source and a rotation by the
+    /// Euler angles `pitch`, `yaw` and `roll`, in radians. The input is rotated
+    /// by `roll` about the z axis, then by `pitch` about the x axis, and then
+    /// by `yaw` about the y axis.
+    pub fn from_euler(source: &NoiseModule, pitch: f32, yaw: f32, roll: f32)
+        -> Rotated3Noise {
+        let pitch = axis_angle(Vector3::new(1.0, 0.0, 0.0), pitch);
+        let yaw = axis_angle(Vector3::new(0.0, 1.0, 0.0), yaw);
+        let roll = axis_angle(Vector3::new(0.0, 0.0, 1.0), roll);
+        Rotated3Noise::new(source, yaw.mul_q(&pitch).mul_q(&roll))
     }
 }
 
-impl Clone for RotatedNoise {
-    fn clone(&self) -> RotatedNoise {
-        RotatedNoise {
+impl Clone for Rotated3Noise {
+    fn clone(&self) -> Rotated3Noise {
+        Rotated3Noise {
             source: clone(&self.source), rotation: self.rotation.clone()
         }
     }
 }
 
-impl NoiseModule for RotatedNoise {
+impl NoiseModule for Rotated3Noise {
     fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
-        let v = Vector2::new(x, y);
-        let n = self.rotation.rotate_vector(&v);
-        self.source.generate_2d(n.x, n.y)
+        self.generate_3d(x, y, 0.0)
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        let n = self.rotation.rotate_vector(&Vector3::new(x, y, z));
+        self.source.generate_3d(n.x, n.y, n.z)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        self.source.bounds()
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let q = self.rotation;
+        Ok(describe("rotate_3d", vec![
+            ("source", try!(self.source.to_json())),
+            ("rotation", numbers(&[q.s, q.v.x, q.v.y, q.v.z]))
+        ]))
+    }
+}
+
+impl Modifiable for Rotated3Noise {}
+
+/// Modifies a source noise module by multiplying its input by a constant scale
+/// along each axis. Stretching the input along one axis turns noise into
+/// strata or grain. This is the type returned by `scale_input()`; see Clamped.
+#[deriving(Clone)]
+pub struct ScaledInput<S> {
+    /// The source module.
+    pub source: S,
+
+    /// The scale to apply to each input coordinate.
+    pub scale: Vector3<f32>,
+}
+
+/// ScaledInput with a boxed source.
+pub type ScaledInputNoise = ScaledInput<Box<NoiseModule>>;
+
+impl ScaledInput<Box<NoiseModule>> {
+    /// Creates a new ScaledInputNoise with the given source and scale.
+    pub fn new(source: &NoiseModule, scale: Vector3<f32>) -> ScaledInputNoise {
+        ScaledInput { source: source.to_box(), scale: scale }
+    }
+}
+
+impl<S: NoiseModule> NoiseModule for ScaledInput<S> {
+    /// The z component of the scale is ignored.
+    #[inline]
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        self.source.generate_2d(x * self.scale.x, y * self.scale.y)
+    }
+
+    #[inline]
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        self.source.generate_3d(x * self.scale.x, y * self.scale.y,
+                                z * self.scale.z)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        self.source.bounds()
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        let sx = program.constant(self.scale.x);
+        let sy = program.constant(self.scale.y);
+        let (x, y) = (program.mul(x, sx), program.mul(y, sy));
+        program.lower(&self.source, x, y)
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let v = self.scale;
+        Ok(describe("scale_input", vec![
+            ("source", try!(self.source.to_json())),
+            ("scale", numbers(&[v.x, v.y, v.z]))
+        ]))
+    }
+}
+
+impl<S: NoiseModule> Modifiable for ScaledInput<S> {}
+
+/// Modifies a source noise module by applying an affine transformation to its
+/// input, so that the source is sampled at $Mp$ for the input point $p$.
+///
+/// The transformation is stored as a homogeneous 4x4 matrix, i.e. a 3x4 affine
+/// matrix with an implied last row of (0, 0, 0, 1). Two-dimensional input is
+/// treated as lying in the plane z = 0, and only the x and y components of the
+/// result are used.
+///
+/// ## Example
+///
+/// The `translate()`, `rotate()`, `scale_input()` and `affine()` methods of
+/// AffineNoise compose their transformation with its own, rather than wrapping
+/// it in another module, so that a chain of them costs a single matrix
+/// multiplication per sample. Only the inherent methods do this: the same
+/// chain on any other module nests Translated, Rotated and ScaledInput
+/// wrappers, as do calls made through the Modifiable trait in generic code.
+/// To collapse a chain, start it with `affine(Matrix4::identity())` or
+/// `AffineNoise::new()`; the result samples the same points as the nested
+/// wrappers would:
+///
+/// ```rust
+/// use grunge::vectors::{Matrix4, Vector2, Vector3};
+/// use grunge::modules::{NoiseModule, Modifiable, PinkNoise};
+///
+/// let noise = PinkNoise::new(0).affine(Matrix4::identity())
+///                              .scale_input(Vector3::new(8.0, 1.0, 1.0))
+///                              .rotate(0.3)
+///                              .translate(Vector2::new(10.0, -4.0));
+/// println!("{}", noise.generate_2d(1.0, -1.0));
+/// ```
+pub struct AffineNoise {
+    /// The source module.
+    pub source: Box<NoiseModule>,
+
+    /// The homogeneous transformation to apply to input coordinates.
+    pub matrix: Matrix4<f32>,
+}
+
+impl AffineNoise {
+    /// Creates a new AffineNoise with the given source and homogeneous
+    /// three-dimensional transformation.
+    pub fn new(source: &NoiseModule, matrix: Matrix4<f32>) -> AffineNoise {
+        AffineNoise { source: source.to_box(), matrix: matrix }
+    }
+
+    /// Creates a new AffineNoise with the given source and homogeneous
+    /// two-dimensional transformation, i.e. a 2x3 affine matrix with an
+    /// implied last row of (0, 0, 1). The z coordinate is left unchanged.
+    pub fn new_2d(source: &NoiseModule, matrix: Matrix3<f32>) -> AffineNoise {
+        let m = matrix;
+        AffineNoise::new(source, Matrix4::new(m.x.x, m.x.y, 0.0, 0.0,
+                                              m.y.x, m.y.y, 0.0, 0.0,
+                                              0.0,   0.0,   1.0, 0.0,
+                                              m.z.x, m.z.y, 0.0, 1.0))
+    }
+
+    /// Composes the transformation with a translation by `translation`.
+    pub fn translate(&self, translation: Vector2<f32>) -> AffineNoise {
+        let t = translation;
+        self.affine(Matrix4::new(1.0, 0.0, 0.0, 0.0,
+                                 0.0, 1.0, 0.0, 0.0,
+                                 0.0, 0.0, 1.0, 0.0,
+                                 t.x, t.y, 0.0, 1.0))
+    }
+
+    /// Composes the transformation with a rotation about the z axis by the
+    /// angle `rotation`, in radians.
+    pub fn rotate(&self, rotation: f32) -> AffineNoise {
+        let (sin, cos) = rotation.sin_cos();
+        self.affine(Matrix4::new(cos, sin, 0.0, 0.0,
+                                 -sin, cos, 0.0, 0.0,
+                                 0.0, 0.0, 1.0, 0.0,
+                                 0.0, 0.0, 0.0, 1.0))
+    }
+
+    /// Composes the transformation with a scale by `scale` along each axis.
+    pub fn scale_input(&self, scale: Vector3<f32>) -> AffineNoise {
+        let s = scale;
+        self.affine(Matrix4::new(s.x, 0.0, 0.0, 0.0,
+                                 0.0, s.y, 0.0, 0.0,
+                                 0.0, 0.0, s.z, 0.0,
+                                 0.0, 0.0, 0.0, 1.0))
+    }
+
+    /// Composes the transformation with the homogeneous transformation
+    /// `matrix`, which is applied to the input first.
+    pub fn affine(&self, matrix: Matrix4<f32>) -> AffineNoise {
+        AffineNoise {
+            source: clone(&self.source), matrix: self.matrix.mul_m(&matrix)
+        }
+    }
+}
+
+impl Clone for AffineNoise {
+    fn clone(&self) -> AffineNoise {
+        AffineNoise { source: clone(&self.source), matrix: self.matrix.clone() }
+    }
+}
+
+impl NoiseModule for AffineNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let p = self.matrix.mul_v(&Vector4::new(x, y, 0.0, 1.0));
+        self.source.generate_2d(p.x, p.y)
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        let p = self.matrix.mul_v(&Vector4::new(x, y, z, 1.0));
+        self.source.generate_3d(p.x, p.y, p.z)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        self.source.bounds()
+    }
+
+    /// Two-dimensional input lies in the plane z = 0, so only the x, y and w
+    /// columns of the matrix take part.
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        let m = self.matrix;
+        let row = |program: &mut Program, (a, b, c): (f32, f32, f32)| {
+            let (a, b, c) = (program.constant(a), program.constant(b),
+                             program.constant(c));
+            let (ax, by) = (program.mul(a, x), program.mul(b, y));
+            let sum = program.add(ax, by);
+            program.add(sum, c)
+        };
+        let px = row(program, (m.x.x, m.y.x, m.w.x));
+        let py = row(program, (m.x.y, m.y.y, m.w.y));
+        program.lower(&*self.source, px, py)
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let m = self.matrix;
+        Ok(describe("affine", vec![
+            ("source", try!(self.source.to_json())),
+            ("matrix", numbers(&[m.x.x, m.x.y, m.x.z, m.x.w,
+                                 m.y.x, m.y.y, m.y.z, m.y.w,
+                                 m.z.x, m.z.y, m.z.z, m.z.w,
+                                 m.w.x, m.w.y, m.w.z, m.w.w]))
+        ]))
     }
 }
 
-impl Modifiable for RotatedNoise {}
+impl Modifiable for AffineNoise {}
 
 /// Functions applicable for passing to ModifierNoise.
-pub type ModifierNoiseFunction = fn<'a>(x: f32, y: f32, out: f32) -> Result<f32, &'a str>;
+pub type ModifierNoiseFunction =
+    Fn(f32, f32, f32) -> Result<f32, &'static str> + Send + Sync;
 
 /// ModifierNoise allows the use of an arbitrary function to modify noise.
+///
+/// The function is given the input point and the output of the source. Like
+/// that of FunctionNoise, it may be any closure and is shared between clones,
+/// and the module can only be described if it was created from a Registry.
+///
+/// ## Example
+///
+/// ```rust
+/// use grunge::modules::{NoiseModule, PinkNoise, ModifierNoise};
+///
+/// let power = 3;
+/// let sharpened = ModifierNoise::new(&PinkNoise::new(7),
+///                                    move |_: f32, _: f32, out: f32| {
+///     Ok(out.powi(power))
+/// });
+/// println!("{}", sharpened.generate_2d(1.0, -1.0));
+/// ```
 #[experimental]
-pub struct ModifierNoise<'a> {
+pub struct ModifierNoise {
     /// The source module.
     pub source: Box<NoiseModule>,
 
     /// The function which maps points and initial output to a noise value.
-    pub func: &'a ModifierNoiseFunction
+    pub func: Arc<ModifierNoiseFunction>,
+
+    /// The name the function is registered under, if any.
+    pub name: Option<String>
 }
 
-impl<'a> ModifierNoise<'a> {
+impl ModifierNoise {
     /// Create a new ModifierNoise with the given function.
     #[inline]
-    pub fn new(source: &NoiseModule, func: &'a ModifierNoiseFunction)
-        -> ModifierNoise<'a> {
-            ModifierNoise { source: source.to_box(), func: func }
+    pub fn new<F: Fn(f32, f32, f32) -> Result<f32, &'static str> + Send + Sync>
+        (source: &NoiseModule, func: F) -> ModifierNoise {
+        ModifierNoise {
+            source: source.to_box(), func: Arc::new(func), name: None
+        }
     }
 }
 
-impl<'a> Clone for ModifierNoise<'a> {
-    fn clone(&self) -> ModifierNoise<'a> {
-        ModifierNoise { source: clone(&self.source), func: self.func }
+impl Clone for ModifierNoise {
+    fn clone(&self) -> ModifierNoise {
+        ModifierNoise {
+            source: clone(&self.source), func: self.func.clone(),
+            name: self.name.clone()
+        }
     }
 }
 
-impl<'a> NoiseModule for ModifierNoise<'a> {
+impl NoiseModule for ModifierNoise {
     #[inline]
     fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
         match self.source.generate_2d(x, y) {
@@ -281,6 +787,1151 @@ impl<'a> NoiseModule for ModifierNoise<'a> {
             err => err
         }
     }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        match self.name {
+            Some(ref name) => Ok(describe("modifier", vec![
+                ("source", try!(self.source.to_json())),
+                ("name", name.to_json())
+            ])),
+            None => Err("Only registered functions can be serialized.")
+        }
+    }
+}
+
+impl Modifiable for ModifierNoise {}
+
+/// Modifies two source noise modules by choosing between their output based on
+/// the value of a third, "control" module.
+///
+/// If the output of the `control` module lies within the range given by
+/// `lower_bound` and `upper_bound`, the output of the `inside` module is used;
+/// otherwise, the output of the `outside` module is used. When `edge_falloff`
+/// is greater than zero, the two are blended smoothly in a band of that
+/// half-width around each bound. The falloff is limited to half of the distance
+/// between the bounds.
+///
+/// This mirrors the `Select` module of libnoise, and is commonly used to carve
+/// features such as rivers, or to separate land from ocean.
+///
+/// ## Example
+///
+/// ```rust
+/// use std::default::Default;
+/// use grunge::modules::{NoiseModule, PinkNoise, ConstNoise, SelectNoise};
+///
+/// let land = PinkNoise::new(3);
+/// let ocean = ConstNoise::new(-1.0);
+/// let mask = PinkNoise { seed: 4, frequency: 0.25, .. Default::default() };
+///
+/// let mut world = SelectNoise::new(&ocean, &land, &mask, 0.0, 1.0);
+/// world.edge_falloff = 0.125;
+/// println!("{}", world.generate_2d(1.0, -1.0));
+/// ```
+pub struct SelectNoise {
+    /// The module used when the control value is outside the bounds.
+    pub outside: Box<NoiseModule>,
+
+    /// The module used when the control value is inside the bounds.
+    pub inside: Box<NoiseModule>,
+
+    /// The module whose output determines which source is used.
+    pub control: Box<NoiseModule>,
+
+    /// The lower bound of the selection range.
+    pub lower_bound: f32,
+
+    /// The upper bound of the selection range.
+    pub upper_bound: f32,
+
+    /// The half-width of the transition band around each bound.
+    pub edge_falloff: f32,
+}
+
+impl SelectNoise {
+    /// Creates a new SelectNoise with the given sources, control module and
+    /// bounds, and no edge falloff.
+    pub fn new(outside: &NoiseModule, inside: &NoiseModule,
+               control: &NoiseModule, lower_bound: f32, upper_bound: f32)
+        -> SelectNoise {
+        SelectNoise {
+            outside: outside.to_box(), inside: inside.to_box(),
+            control: control.to_box(),
+            lower_bound: lower_bound, upper_bound: upper_bound,
+            edge_falloff: 0.0
+        }
+    }
+}
+
+impl Clone for SelectNoise {
+    fn clone(&self) -> SelectNoise {
+        SelectNoise {
+            outside: clone(&self.outside), inside: clone(&self.inside),
+            control: clone(&self.control),
+            lower_bound: self.lower_bound.clone(),
+            upper_bound: self.upper_bound.clone(),
+            edge_falloff: self.edge_falloff.clone()
+        }
+    }
+}
+
+impl SelectNoise {
+    /// Chooses or blends between the sources for the given `control` value,
+    /// using `sample` to evaluate them.
+    fn select<'a>(&'a self, control: f32,
+                  sample: |&'a Box<NoiseModule>| -> Result<f32, &'a str>)
+        -> Result<f32, &'a str> {
+        let falloff = self.edge_falloff
+            .min((self.upper_bound - self.lower_bound) * 0.5);
+
+        if falloff <= 0.0 {
+            return if control < self.lower_bound || control > self.upper_bound {
+                sample(&self.outside)
+            } else {
+                sample(&self.inside)
+            };
+        }
+
+        if control < self.lower_bound - falloff {
+            sample(&self.outside)
+        } else if control < self.lower_bound + falloff {
+            // Blend from the outside module into the inside module.
+            let alpha = scurve3((control - self.lower_bound + falloff) /
+                                (2.0 * falloff));
+            let a = try!(sample(&self.outside));
+            let b = try!(sample(&self.inside));
+            Ok(lerp(a, b, alpha))
+        } else if control < self.upper_bound - falloff {
+            sample(&self.inside)
+        } else if control < self.upper_bound + falloff {
+            // Blend from the inside module back into the outside module.
+            let alpha = scurve3((control - self.upper_bound + falloff) /
+                                (2.0 * falloff));
+            let a = try!(sample(&self.inside));
+            let b = try!(sample(&self.outside));
+            Ok(lerp(a, b, alpha))
+        } else {
+            sample(&self.outside)
+        }
+    }
+}
+
+impl NoiseModule for SelectNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let control = try!(self.control.generate_2d(x, y));
+        self.select(control, |source| source.generate_2d(x, y))
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        let control = try!(self.control.generate_3d(x, y, z));
+        self.select(control, |source| source.generate_3d(x, y, z))
+    }
+
+    /// Since blending is a weighted average, the output lies within the
+    /// union of the bounds of the two sources.
+    fn bounds(&self) -> (f32, f32) {
+        let (outside_lower, outside_upper) = self.outside.bounds();
+        let (inside_lower, inside_upper) = self.inside.bounds();
+        (outside_lower.min(inside_lower), outside_upper.max(inside_upper))
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("select", vec![
+            ("outside", try!(self.outside.to_json())),
+            ("inside", try!(self.inside.to_json())),
+            ("control", try!(self.control.to_json())),
+            ("lower_bound", number(self.lower_bound)),
+            ("upper_bound", number(self.upper_bound)),
+            ("edge_falloff", number(self.edge_falloff))
+        ]))
+    }
+}
+
+impl Modifiable for SelectNoise {}
+
+/// Modifies two source noise modules by blending between their output based on
+/// the value of a third, "control" module.
+///
+/// A control value of `-1.0` gives the output of the `first` module, and `1.0`
+/// that of the `second`, with values in between blended linearly. Control
+/// values outside [-1, 1] are clamped to it. This mirrors the `Blend` module of
+/// libnoise.
+pub struct BlendNoise {
+    /// The module used when the control value is `-1.0`.
+    pub first: Box<NoiseModule>,
+
+    /// The module used when the control value is `1.0`.
+    pub second: Box<NoiseModule>,
+
+    /// The module whose output determines the weight of each source.
+    pub control: Box<NoiseModule>,
+}
+
+impl BlendNoise {
+    /// Creates a new BlendNoise with the given sources and control module.
+    pub fn new(first: &NoiseModule, second: &NoiseModule,
+               control: &NoiseModule) -> BlendNoise {
+        BlendNoise {
+            first: first.to_box(), second: second.to_box(),
+            control: control.to_box()
+        }
+    }
+}
+
+impl Clone for BlendNoise {
+    fn clone(&self) -> BlendNoise {
+        BlendNoise {
+            first: clone(&self.first), second: clone(&self.second),
+            control: clone(&self.control)
+        }
+    }
+}
+
+impl NoiseModule for BlendNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let alpha = try!(self.control.generate_2d(x, y)) * 0.5 + 0.5;
+        let a = try!(self.first.generate_2d(x, y));
+        let b = try!(self.second.generate_2d(x, y));
+        Ok(lerp(a, b, alpha.max(0.0).min(1.0)))
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        let alpha = try!(self.control.generate_3d(x, y, z)) * 0.5 + 0.5;
+        let a = try!(self.first.generate_3d(x, y, z));
+        let b = try!(self.second.generate_3d(x, y, z));
+        Ok(lerp(a, b, alpha.max(0.0).min(1.0)))
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (first_lower, first_upper) = self.first.bounds();
+        let (second_lower, second_upper) = self.second.bounds();
+        (first_lower.min(second_lower), first_upper.max(second_upper))
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        let control = try!(program.lower(&*self.control, x, y));
+        let a = try!(program.lower(&*self.first, x, y));
+        let b = try!(program.lower(&*self.second, x, y));
+        let (half, zero) = (program.constant(0.5), program.constant(0.0));
+        let one = program.constant(1.0);
+
+        let alpha = program.mul(control, half);
+        let alpha = program.add(alpha, half);
+        let alpha = program.max(alpha, zero);
+        let alpha = program.min(alpha, one);
+        let difference = program.sub(b, a);
+        let offset = program.mul(alpha, difference);
+        Ok(program.add(a, offset))
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("blend", vec![
+            ("first", try!(self.first.to_json())),
+            ("second", try!(self.second.to_json())),
+            ("control", try!(self.control.to_json()))
+        ]))
+    }
+}
+
+impl Modifiable for BlendNoise {}
+
+/// The ways in which CombinedNoise can combine the output of its sources.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Operation {
+    /// The sum of the two outputs.
+    Sum,
+
+    /// The output of the first source minus that of the second.
+    Difference,
+
+    /// The product of the two outputs.
+    Product,
+
+    /// The smaller of the two outputs.
+    Minimum,
+
+    /// The larger of the two outputs.
+    Maximum,
+}
+
+/// Combines the output of two source noise modules with an arithmetic
+/// operation, such as their sum or product. This mirrors the `Add`,
+/// `Multiply`, `Min` and `Max` modules of libnoise.
+///
+/// ## Example
+///
+/// ```rust
+/// use grunge::modules::{NoiseModule, PinkNoise, CylinderNoise, CombinedNoise,
+///                       Product};
+///
+/// let rings = CylinderNoise::new(2.0);
+/// let noisy = CombinedNoise::new(Product, &PinkNoise::new(4), &rings);
+/// println!("{}", noisy.generate_2d(1.0, -1.0));
+/// ```
+pub struct CombinedNoise {
+    /// The operation used to combine the outputs.
+    pub operation: Operation,
+
+    /// The first source module.
+    pub first: Box<NoiseModule>,
+
+    /// The second source module.
+    pub second: Box<NoiseModule>,
+}
+
+impl CombinedNoise {
+    /// Creates a new CombinedNoise applying `operation` to the given sources.
+    pub fn new(operation: Operation, first: &NoiseModule, second: &NoiseModule)
+        -> CombinedNoise {
+        CombinedNoise {
+            operation: operation, first: first.to_box(),
+            second: second.to_box()
+        }
+    }
+
+    fn combine(&self, a: f32, b: f32) -> f32 {
+        match self.operation {
+            Sum => a + b,
+            Difference => a - b,
+            Product => a * b,
+            Minimum => a.min(b),
+            Maximum => a.max(b)
+        }
+    }
+}
+
+impl Clone for CombinedNoise {
+    fn clone(&self) -> CombinedNoise {
+        CombinedNoise {
+            operation: self.operation, first: clone(&self.first),
+            second: clone(&self.second)
+        }
+    }
+}
+
+impl NoiseModule for CombinedNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let a = try!(self.first.generate_2d(x, y));
+        let b = try!(self.second.generate_2d(x, y));
+        Ok(self.combine(a, b))
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        let a = try!(self.first.generate_3d(x, y, z));
+        let b = try!(self.second.generate_3d(x, y, z));
+        Ok(self.combine(a, b))
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        let (a0, a1) = self.first.bounds();
+        let (b0, b1) = self.second.bounds();
+        match self.operation {
+            Sum => (a0 + b0, a1 + b1),
+            Difference => (a0 - b1, a1 - b0),
+            Minimum => (a0.min(b0), a1.min(b1)),
+            Maximum => (a0.max(b0), a1.max(b1)),
+            Product => {
+                // The extremes of a product lie at the corners, unless an
+                // infinite bound meets a zero one.
+                let corners = [a0 * b0, a0 * b1, a1 * b0, a1 * b1];
+                if corners.iter().any(|c| c.is_nan()) {
+                    return (Float::neg_infinity(), Float::infinity());
+                }
+                let lower = corners.iter().fold(Float::infinity(),
+                                                |a: f32, &b| a.min(b));
+                let upper = corners.iter().fold(Float::neg_infinity(),
+                                                |a: f32, &b| a.max(b));
+                (lower, upper)
+            }
+        }
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        let a = try!(program.lower(&*self.first, x, y));
+        let b = try!(program.lower(&*self.second, x, y));
+        Ok(match self.operation {
+            Sum => program.add(a, b),
+            Difference => program.sub(a, b),
+            Product => program.mul(a, b),
+            Minimum => program.min(a, b),
+            Maximum => program.max(a, b)
+        })
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let kind = match self.operation {
+            Sum => "add",
+            Difference => "subtract",
+            Product => "multiply",
+            Minimum => "min",
+            Maximum => "max"
+        };
+        Ok(describe(kind, vec![
+            ("first", try!(self.first.to_json())),
+            ("second", try!(self.second.to_json()))
+        ]))
+    }
+}
+
+impl Modifiable for CombinedNoise {}
+
+/// Shares a single source noise module between several parents, rather than
+/// each of them holding its own copy. Cloning a SharedNoise (as happens when it
+/// is passed to another module) only clones a reference to the source, which
+/// is counted atomically so that the graph can still be sent between threads.
+///
+/// Noise graphs loaded with the [serial](../serial/index.html) module use this
+/// for nodes that are referred to by more than one parent.
+pub struct SharedNoise {
+    /// The shared source module.
+    pub source: Arc<Box<NoiseModule>>,
+}
+
+impl SharedNoise {
+    /// Creates a new SharedNoise with the given source.
+    pub fn new(source: &NoiseModule) -> SharedNoise {
+        SharedNoise { source: Arc::new(source.to_box()) }
+    }
+}
+
+impl Clone for SharedNoise {
+    fn clone(&self) -> SharedNoise {
+        SharedNoise { source: self.source.clone() }
+    }
+}
+
+impl NoiseModule for SharedNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        self.source.generate_2d(x, y)
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        self.source.generate_3d(x, y, z)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        self.source.bounds()
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        program.lower(&**self.source, x, y)
+    }
+
+    /// The description of the source is written in full at each use; see
+    /// `serial::to_graph()` to write it once.
+    fn to_json(&self) -> Result<Json, &'static str> {
+        self.source.to_json()
+    }
+}
+
+impl Modifiable for SharedNoise {}
+
+/// Modifies a source noise module by randomly displacing its input coordinates
+/// before sampling it, which produces a "turbulent" distortion of the output.
+///
+/// The displacement along each axis is given by a separate PinkNoise module,
+/// whose output is multiplied by `power`. This follows the `Turbulence` module
+/// of libnoise: the three distortion modules share a frequency and number of
+/// octaves (the "roughness"), but are seeded differently, and are sampled at
+/// slightly offset coordinates so that their output is uncorrelated.
+///
+/// ## Example
+///
+/// ```rust
+/// use grunge::modules::{NoiseModule, Modifiable, CylinderNoise};
+///
+/// // Wood grain: concentric rings with a little turbulence.
+/// let wood = CylinderNoise::new(4.0).turbulence(2.0, 0.0625, 3).unwrap();
+/// println!("{}", wood.generate_2d(1.0, -1.0));
+/// ```
+pub struct TurbulenceNoise {
+    /// The source module.
+    pub source: Box<NoiseModule>,
+
+    /// The scaling applied to the distortion module output, i.e. the
+    /// approximate distance that input coordinates are displaced.
+    pub power: f32,
+
+    /// The module used to displace the x coordinate.
+    pub x_distort: PinkNoise,
+
+    /// The module used to displace the y coordinate.
+    pub y_distort: PinkNoise,
+
+    /// The module used to displace the z coordinate of three-dimensional
+    /// input.
+    pub z_distort: PinkNoise,
+}
+
+impl TurbulenceNoise {
+    /// Creates a new TurbulenceNoise with the given source, and distortion
+    /// modules seeded from `seed` with the given `frequency` and `roughness`.
+    ///
+    /// The distortion modules are PinkNoise, which needs at least two octaves,
+    /// so a roughness below two is an error. (libnoise allows a single one.)
+    pub fn new(source: &NoiseModule, seed: uint, frequency: f32, power: f32,
+               roughness: uint) -> Result<TurbulenceNoise, &'static str> {
+        TurbulenceNoise::from_box(source.to_box(), seed, frequency, power,
+                                  roughness)
+    }
+
+    fn from_box(source: Box<NoiseModule>, seed: uint, frequency: f32,
+                power: f32, roughness: uint)
+        -> Result<TurbulenceNoise, &'static str> {
+        if roughness < 2 {
+            return Err("Turbulence must have a roughness of at least 2.");
+        }
+        let distort = PinkNoise {
+            frequency: frequency, octaves: roughness as f32,
+            .. Default::default()
+        };
+        Ok(TurbulenceNoise {
+            source: source, power: power,
+            x_distort: PinkNoise { seed: seed, .. distort },
+            y_distort: PinkNoise { seed: seed + 1, .. distort },
+            z_distort: PinkNoise { seed: seed + 2, .. distort }
+        })
+    }
+}
+
+impl Clone for TurbulenceNoise {
+    fn clone(&self) -> TurbulenceNoise {
+        TurbulenceNoise {
+            source: clone(&self.source), power: self.power.clone(),
+            x_distort: self.x_distort.clone(),
+            y_distort: self.y_distort.clone(),
+            z_distort: self.z_distort.clone()
+        }
+    }
+}
+
+// The offsets used by libnoise to decorrelate the distortion modules, which
+// are sampled at (x0, y0, z0), (x1, y1, z1) and (x2, y2, z2), respectively.
+static TURBULENCE_X0: f32 = 12414.0 / 65536.0;
+static TURBULENCE_Y0: f32 = 65124.0 / 65536.0;
+static TURBULENCE_Z0: f32 = 31337.0 / 65536.0;
+static TURBULENCE_X1: f32 = 26519.0 / 65536.0;
+static TURBULENCE_Y1: f32 = 18128.0 / 65536.0;
+static TURBULENCE_Z1: f32 = 60493.0 / 65536.0;
+static TURBULENCE_X2: f32 = 53820.0 / 65536.0;
+static TURBULENCE_Y2: f32 = 11213.0 / 65536.0;
+static TURBULENCE_Z2: f32 = 44845.0 / 65536.0;
+
+impl NoiseModule for TurbulenceNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let dx = try!(self.x_distort.generate_2d(x + TURBULENCE_X0,
+                                                 y + TURBULENCE_Y0));
+        let dy = try!(self.y_distort.generate_2d(x + TURBULENCE_X1,
+                                                 y + TURBULENCE_Y1));
+        self.source.generate_2d(x + dx * self.power, y + dy * self.power)
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        let dx = try!(self.x_distort.generate_3d(x + TURBULENCE_X0,
+                                                 y + TURBULENCE_Y0,
+                                                 z + TURBULENCE_Z0));
+        let dy = try!(self.y_distort.generate_3d(x + TURBULENCE_X1,
+                                                 y + TURBULENCE_Y1,
+                                                 z + TURBULENCE_Z1));
+        let dz = try!(self.z_distort.generate_3d(x + TURBULENCE_X2,
+                                                 y + TURBULENCE_Y2,
+                                                 z + TURBULENCE_Z2));
+        self.source.generate_3d(x + dx * self.power, y + dy * self.power,
+                                z + dz * self.power)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        self.source.bounds()
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        let (ox0, oy0) = (program.constant(TURBULENCE_X0),
+                          program.constant(TURBULENCE_Y0));
+        let (ox1, oy1) = (program.constant(TURBULENCE_X1),
+                          program.constant(TURBULENCE_Y1));
+        let (x0, y0) = (program.add(x, ox0), program.add(y, oy0));
+        let (x1, y1) = (program.add(x, ox1), program.add(y, oy1));
+        let dx = try!(self.x_distort.to_shader(program, x0, y0));
+        let dy = try!(self.y_distort.to_shader(program, x1, y1));
+
+        let power = program.constant(self.power);
+        let (dx, dy) = (program.mul(dx, power), program.mul(dy, power));
+        let (x, y) = (program.add(x, dx), program.add(y, dy));
+        program.lower(&*self.source, x, y)
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("turbulence", vec![
+            ("source", try!(self.source.to_json())),
+            ("power", number(self.power)),
+            ("x_distort", try!(self.x_distort.to_json())),
+            ("y_distort", try!(self.y_distort.to_json())),
+            ("z_distort", try!(self.z_distort.to_json()))
+        ]))
+    }
+}
+
+impl Modifiable for TurbulenceNoise {}
+
+/// Modifies a source noise module by displacing its input coordinates by the
+/// output of other modules, so that its output at $\mathbf{x}$ is
+///
+/// $$M(\mathbf{x}) = S(x + D_x(\mathbf{x}), y + D_y(\mathbf{x}), \ldots)$$
+///
+/// where $S$ is the source module and $D_x, D_y, \ldots$ are the displacement
+/// modules. This generalizes TranslatedNoise from a constant vector to a
+/// vector field, and TurbulenceNoise to arbitrary displacement modules.
+///
+/// Since the displacement modules may themselves be displaced, this can be used
+/// to warp the domain recursively, as described by Inigo Quilez [1].
+///
+/// 1. Quilez, Inigo. (2002). [_Domain Warping_]
+///    (http://www.iquilezles.org/www/articles/warp/warp.htm).
+///
+/// ## Example
+///
+/// Computing $f(\mathbf{p} + 4\mathbf{r})$, where
+/// $\mathbf{r} = (f(\mathbf{p} + 4\mathbf{q} + \mathbf{a}),
+///     f(\mathbf{p} + 4\mathbf{q} + \mathbf{b}))$ and
+/// $\mathbf{q} = (f(\mathbf{p}), f(\mathbf{p} + \mathbf{c}))$.
+///
+/// ```rust
+/// use grunge::vectors::Vector2;
+/// use grunge::modules::{NoiseModule, Modifiable, PinkNoise};
+///
+/// let fbm = PinkNoise::new(0);
+///
+/// let qx = fbm.scalebias(4.0, 0.0);
+/// let qy = fbm.translate(Vector2::new(5.2, 1.3)).scalebias(4.0, 0.0);
+///
+/// let rx = fbm.translate(Vector2::new(1.7, 9.2)).displace(&qx, &qy)
+///     .scalebias(4.0, 0.0);
+/// let ry = fbm.translate(Vector2::new(8.3, 2.8)).displace(&qx, &qy)
+///     .scalebias(4.0, 0.0);
+///
+/// let warped = fbm.displace(&rx, &ry);
+///
+/// // The same, one sample at a time.
+/// let f = |x: f32, y: f32| fbm.generate_2d(x, y).unwrap();
+/// let (x, y) = (1.0f32, -1.0f32);
+/// let (q1, q2) = (4.0 * f(x, y), 4.0 * f(x + 5.2, y + 1.3));
+/// let (r1, r2) = (4.0 * f(x + q1 + 1.7, y + q2 + 9.2),
+///                 4.0 * f(x + q1 + 8.3, y + q2 + 2.8));
+/// let expected = f(x + r1, y + r2);
+/// assert!((warped.generate_2d(x, y).unwrap() - expected).abs() < 1e-4);
+/// ```
+pub struct DisplaceNoise {
+    /// The source module.
+    pub source: Box<NoiseModule>,
+
+    /// The module whose output is added to the x coordinate.
+    pub x_displace: Box<NoiseModule>,
+
+    /// The module whose output is added to the y coordinate.
+    pub y_displace: Box<NoiseModule>,
+
+    /// The module whose output is added to the z coordinate of
+    /// three-dimensional input. If this is `None`, the z coordinate is left
+    /// unchanged.
+    pub z_displace: Option<Box<NoiseModule>>,
+}
+
+impl DisplaceNoise {
+    /// Creates a new DisplaceNoise with the given source and displacement
+    /// modules for the x and y coordinates.
+    pub fn new(source: &NoiseModule, x: &NoiseModule, y: &NoiseModule)
+        -> DisplaceNoise {
+        DisplaceNoise {
+            source: source.to_box(), x_displace: x.to_box(),
+            y_displace: y.to_box(), z_displace: None
+        }
+    }
+
+    /// Creates a new DisplaceNoise with the given source and displacement
+    /// modules for each of the x, y and z coordinates.
+    pub fn new_3d(source: &NoiseModule, x: &NoiseModule, y: &NoiseModule,
+                  z: &NoiseModule) -> DisplaceNoise {
+        DisplaceNoise {
+            source: source.to_box(), x_displace: x.to_box(),
+            y_displace: y.to_box(), z_displace: Some(z.to_box())
+        }
+    }
+}
+
+impl Clone for DisplaceNoise {
+    fn clone(&self) -> DisplaceNoise {
+        DisplaceNoise {
+            source: clone(&self.source),
+            x_displace: clone(&self.x_displace),
+            y_displace: clone(&self.y_displace),
+            z_displace: self.z_displace.clone()
+        }
+    }
+}
+
+impl NoiseModule for DisplaceNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let dx = try!(self.x_displace.generate_2d(x, y));
+        let dy = try!(self.y_displace.generate_2d(x, y));
+        self.source.generate_2d(x + dx, y + dy)
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        let dx = try!(self.x_displace.generate_3d(x, y, z));
+        let dy = try!(self.y_displace.generate_3d(x, y, z));
+        let dz = match self.z_displace {
+            Some(ref module) => try!(module.generate_3d(x, y, z)),
+            None => 0.0
+        };
+        self.source.generate_3d(x + dx, y + dy, z + dz)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        self.source.bounds()
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        let dx = try!(program.lower(&*self.x_displace, x, y));
+        let dy = try!(program.lower(&*self.y_displace, x, y));
+        let (x, y) = (program.add(x, dx), program.add(y, dy));
+        program.lower(&*self.source, x, y)
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let mut fields = vec![
+            ("source", try!(self.source.to_json())),
+            ("x_displace", try!(self.x_displace.to_json())),
+            ("y_displace", try!(self.y_displace.to_json()))
+        ];
+        match self.z_displace {
+            Some(ref z) => fields.push(("z_displace", try!(z.to_json()))),
+            None => ()
+        }
+        Ok(describe("displace", fields))
+    }
+}
+
+impl Modifiable for DisplaceNoise {}
+
+/// Sorts control points into ascending order, removing duplicates and points
+/// that are infinite or NaN, which cannot bound a terrace.
+fn sorted_points(points: &[f32]) -> Vec<f32> {
+    let mut points: Vec<f32> = points.iter().map(|&point| point)
+        .filter(|point| point.is_finite()).collect();
+    points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    points.dedup();
+    points
+}
+
+/// Modifies a source noise module by mapping its output onto a series of
+/// terraces.
+///
+/// The terraces are defined by a sorted list of control `points`. Output that
+/// falls between two control points is mapped onto a curve which is flat near
+/// the lower point and steep near the upper one, and output outside of the
+/// control points is clamped to the first or last point. If `invert` is set,
+/// the curve is flipped so that the terraces are flat near the upper point
+/// instead.
+///
+/// The shape of the curve is controlled by `smoothness`, which lies in
+/// [0, 1]. At `0.5` (the default) the curve is quadratic, as in the `Terrace`
+/// module of libnoise; lower values produce flatter plateaus and sharper
+/// cliffs, with `0.0` resulting in hard steps, while `1.0` results in no
+/// terracing at all.
+///
+/// ## Example
+///
+/// Stepped plateaus for a mesa biome.
+///
+/// ```rust
+/// use grunge::modules::{NoiseModule, Modifiable, PinkNoise};
+///
+/// let mut mesa = PinkNoise::new(12).terrace(&[-1.0, -0.25, 0.1, 0.3, 1.0]);
+/// mesa.smoothness = 0.25;
+/// println!("{}", mesa.generate_2d(1.0, -1.0));
+/// ```
+pub struct TerraceNoise {
+    /// The source module.
+    pub source: Box<NoiseModule>,
+
+    /// The control points defining the terraces, which must be sorted in
+    /// ascending order.
+    pub points: Vec<f32>,
+
+    /// Whether the terrace curves are inverted.
+    pub invert: bool,
+
+    /// The smoothness of the terrace curves, from `0.0` (hard steps) to `1.0`
+    /// (no terracing).
+    pub smoothness: f32,
+}
+
+impl TerraceNoise {
+    /// Creates a new TerraceNoise with the given source and control points,
+    /// which are sorted and stripped of duplicates and non-finite values.
+    pub fn new(source: &NoiseModule, points: &[f32]) -> TerraceNoise {
+        TerraceNoise {
+            source: source.to_box(), points: sorted_points(points),
+            invert: false, smoothness: 0.5
+        }
+    }
+
+    /// Maps a value from the source module onto the terraces.
+    fn terrace(&self, value: f32) -> Result<f32, &'static str> {
+        let count = self.points.len();
+        if count < 2 {
+            return Err("TerraceNoise requires at least two control points.");
+        }
+
+        // Find the pair of control points surrounding the value.
+        let mut index = 0u;
+        while index < count && value >= self.points[index] {
+            index += 1;
+        }
+        let i0 = if index == 0 { 0 } else { (index - 1).min(count - 1) };
+        let i1 = index.min(count - 1);
+
+        if i0 == i1 {
+            return Ok(self.points[i1]);
+        }
+
+        let mut v0 = self.points[i0];
+        let mut v1 = self.points[i1];
+        let mut alpha = (value - v0) / (v1 - v0);
+        if self.invert {
+            alpha = 1.0 - alpha;
+            let tmp = v0;
+            v0 = v1;
+            v1 = tmp;
+        }
+
+        alpha = if self.smoothness <= 0.0 {
+            if alpha < 1.0 { 0.0 } else { 1.0 }
+        } else {
+            alpha.powf(1.0 / self.smoothness.min(1.0))
+        };
+
+        Ok(lerp(v0, v1, alpha))
+    }
+}
+
+impl Clone for TerraceNoise {
+    fn clone(&self) -> TerraceNoise {
+        TerraceNoise {
+            source: clone(&self.source), points: self.points.clone(),
+            invert: self.invert, smoothness: self.smoothness.clone()
+        }
+    }
+}
+
+impl NoiseModule for TerraceNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        match self.source.generate_2d(x, y) {
+            Ok(val) => self.terrace(val),
+            err => err
+        }
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        match self.source.generate_3d(x, y, z) {
+            Ok(val) => self.terrace(val),
+            err => err
+        }
+    }
+
+    /// Output is always mapped between the first and last control points.
+    fn bounds(&self) -> (f32, f32) {
+        if self.points.len() < 2 {
+            (Float::neg_infinity(), Float::infinity())
+        } else {
+            (self.points[0], self.points[self.points.len() - 1])
+        }
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Ok(describe("terrace", vec![
+            ("source", try!(self.source.to_json())),
+            ("points", numbers(self.points.as_slice())),
+            ("invert", self.invert.to_json()),
+            ("smoothness", number(self.smoothness))
+        ]))
+    }
+}
+
+impl Modifiable for TerraceNoise {}
+
+/// Methods for interpolating between the control points of CurveNoise.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Interpolation {
+    /// A Catmull-Rom spline, which passes smoothly through each control point,
+    /// but may overshoot the output values of its neighbours.
+    CatmullRom,
+
+    /// A monotone cubic Hermite spline, which passes smoothly through each
+    /// control point and is monotonic wherever the control points are. Its
+    /// output never leaves the range of its neighbouring control points.
+    MonotoneCubic,
+}
+
+/// Modifies a source noise module by remapping its output through a cubic
+/// spline, which is defined by a list of (input, output) control points.
+///
+/// This can be used to sculpt the distribution of output values in ways that
+/// ScaledBiasedNoise cannot -- for instance, flattening valleys while
+/// sharpening peaks. Output from the source module outside the range of the
+/// control points is mapped to the output of the first or last point.
+///
+/// The tangents of the spline are computed from the control points when the
+/// module is created. For the `MonotoneCubic` interpolation they are limited
+/// using the method of Fritsch and Carlson (1980) [1], so that monotone
+/// control points give a monotone curve.
+///
+/// 1. Fritsch, F. N. and R. E. Carlson. (1980). _Monotone Piecewise Cubic
+///    Interpolation_. SIAM Journal on Numerical Analysis 17(2): 238-246.
+///
+/// ## Example
+///
+/// ```rust
+/// use grunge::modules::{NoiseModule, Modifiable, PinkNoise};
+///
+/// let terrain = PinkNoise::new(5).curve(&[
+///     (-1.0, -1.0), (-0.25, -0.1), (0.25, 0.1), (1.0, 1.0)
+/// ]).unwrap();
+/// println!("{}", terrain.generate_2d(1.0, -1.0));
+///
+/// // At least two points with distinct inputs are required.
+/// assert!(PinkNoise::new(5).curve(&[(0.0, 0.0), (0.0, 1.0)]).is_err());
+/// ```
+pub struct CurveNoise {
+    /// The source module.
+    pub source: Box<NoiseModule>,
+
+    /// The control points, sorted by input value.
+    points: Vec<(f32, f32)>,
+
+    /// The tangent of the spline at each control point.
+    tangents: Vec<f32>,
+
+    /// The method used to compute the tangents.
+    interpolation: Interpolation,
+}
+
+impl CurveNoise {
+    /// Creates a new CurveNoise with the given source, control points and
+    /// interpolation method.
+    ///
+    /// The points need not be sorted, but there must be at least two of them,
+    /// they must be finite, and no two may share the same input value.
+    pub fn new(source: &NoiseModule, points: &[(f32, f32)],
+               interpolation: Interpolation)
+        -> Result<CurveNoise, &'static str> {
+        CurveNoise::from_box(source.to_box(), points, interpolation)
+    }
+
+    fn from_box(source: Box<NoiseModule>, points: &[(f32, f32)],
+                interpolation: Interpolation)
+        -> Result<CurveNoise, &'static str> {
+        if points.len() < 2 {
+            return Err("CurveNoise requires at least two control points.");
+        }
+        if points.iter().any(|&(x, y)| !x.is_finite() || !y.is_finite()) {
+            return Err("CurveNoise control points must be finite.");
+        }
+
+        let mut points = Vec::from_slice(points);
+        points.sort_by(|&(a, _), &(b, _)| a.partial_cmp(&b).unwrap());
+        for i in range(1, points.len()) {
+            if points[i].val0() <= points[i - 1].val0() {
+                return Err("CurveNoise control points must have distinct \
+                            input values.");
+            }
+        }
+
+        let tangents = match interpolation {
+            CatmullRom => catmull_rom_tangents(points.as_slice()),
+            MonotoneCubic => monotone_tangents(points.as_slice())
+        };
+
+        Ok(CurveNoise {
+            source: source, points: points, tangents: tangents,
+            interpolation: interpolation
+        })
+    }
+
+    /// The control points of the curve, sorted by input value.
+    pub fn points<'a>(&'a self) -> &'a [(f32, f32)] {
+        self.points.as_slice()
+    }
+
+    /// The method used to interpolate between control points.
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    /// Maps a value from the source module through the curve.
+    fn curve(&self, value: f32) -> f32 {
+        let last = self.points.len() - 1;
+        let (x_first, y_first) = self.points[0];
+        let (x_last, y_last) = self.points[last];
+        if value <= x_first {
+            return y_first;
+        } else if value >= x_last {
+            return y_last;
+        }
+
+        // Find the segment containing the value.
+        let mut k = 0u;
+        while value >= self.points[k + 1].val0() {
+            k += 1;
+        }
+
+        let (x0, y0) = self.points[k];
+        let (x1, y1) = self.points[k + 1];
+        let h = x1 - x0;
+        let t = (value - x0) / h;
+        let t2 = t * t;
+        let t3 = t2 * t;
+
+        (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * h * self.tangents[k]
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * h * self.tangents[k + 1]
+    }
+}
+
+/// The slopes of the straight lines between successive control points.
+fn secants(points: &[(f32, f32)]) -> Vec<f32> {
+    range(0, points.len() - 1).map(|k| {
+        let (x0, y0) = points[k];
+        let (x1, y1) = points[k + 1];
+        (y1 - y0) / (x1 - x0)
+    }).collect()
+}
+
+/// Tangents for a Catmull-Rom spline with non-uniformly spaced control points,
+/// using one-sided differences at the end points.
+fn catmull_rom_tangents(points: &[(f32, f32)]) -> Vec<f32> {
+    let last = points.len() - 1;
+    let d = secants(points);
+    range(0, points.len()).map(|k| {
+        if k == 0 {
+            d[0]
+        } else if k == last {
+            d[last - 1]
+        } else {
+            let (x0, y0) = points[k - 1];
+            let (x1, y1) = points[k + 1];
+            (y1 - y0) / (x1 - x0)
+        }
+    }).collect()
+}
+
+/// Tangents for a monotone cubic spline, following Fritsch and Carlson (1980).
+fn monotone_tangents(points: &[(f32, f32)]) -> Vec<f32> {
+    let last = points.len() - 1;
+    let d = secants(points);
+
+    // Start with the average of the neighbouring secants, or zero at local
+    // extrema.
+    let mut m: Vec<f32> = range(0, points.len()).map(|k| {
+        if k == 0 {
+            d[0]
+        } else if k == last {
+            d[last - 1]
+        } else if d[k - 1] * d[k] <= 0.0 {
+            0.0
+        } else {
+            (d[k - 1] + d[k]) * 0.5
+        }
+    }).collect();
+
+    // Then limit the tangents on each segment to prevent overshoot.
+    for k in range(0, last) {
+        if d[k] == 0.0 {
+            *m.get_mut(k) = 0.0;
+            *m.get_mut(k + 1) = 0.0;
+            continue;
+        }
+
+        let a = m[k] / d[k];
+        let b = m[k + 1] / d[k];
+        let s = a * a + b * b;
+        if s > 9.0 {
+            let t = 3.0 / s.sqrt();
+            *m.get_mut(k) = t * a * d[k];
+            *m.get_mut(k + 1) = t * b * d[k];
+        }
+    }
+
+    m
+}
+
+impl Clone for CurveNoise {
+    fn clone(&self) -> CurveNoise {
+        CurveNoise {
+            source: clone(&self.source), points: self.points.clone(),
+            tangents: self.tangents.clone(),
+            interpolation: self.interpolation
+        }
+    }
+}
+
+impl NoiseModule for CurveNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        match self.source.generate_2d(x, y) {
+            Ok(val) => Ok(self.curve(val)),
+            err => err
+        }
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        match self.source.generate_3d(x, y, z) {
+            Ok(val) => Ok(self.curve(val)),
+            err => err
+        }
+    }
+
+    /// Each segment of the curve lies within the convex hull of the control
+    /// points of its equivalent Bezier curve, which gives a bound for any
+    /// overshoot.
+    fn bounds(&self) -> (f32, f32) {
+        let mut lower: f32 = Float::infinity();
+        let mut upper: f32 = Float::neg_infinity();
+
+        for k in range(0, self.points.len() - 1) {
+            let (x0, y0) = self.points[k];
+            let (x1, y1) = self.points[k + 1];
+            let third = (x1 - x0) / 3.0;
+            let hull = [y0, y0 + third * self.tangents[k],
+                        y1 - third * self.tangents[k + 1], y1];
+            for &y in hull.iter() {
+                lower = lower.min(y);
+                upper = upper.max(y);
+            }
+        }
+
+        (lower, upper)
+    }
+
+    fn to_json(&self) -> Result<Json, &'static str> {
+        let points: Vec<Json> = self.points.iter().map(|&(x, y)| {
+            numbers(&[x, y])
+        }).collect();
+        let interpolation = match self.interpolation {
+            CatmullRom => "catmull_rom",
+            MonotoneCubic => "monotone_cubic"
+        };
+        Ok(describe("curve", vec![
+            ("source", try!(self.source.to_json())),
+            ("points", points.to_json()),
+            ("interpolation", interpolation.to_string().to_json())
+        ]))
+    }
 }
 
-impl<'a> Modifiable for ModifierNoise<'a> {}
+impl Modifiable for CurveNoise {}
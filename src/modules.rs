@@ -2,16 +2,23 @@
     This file is part of grunge, a coherent noise generation library.
 */
 
-//! Re-exports NoiseModule and all types that implement it.
+//! Re-exports NoiseModule, VectorNoiseModule and all types that implement
+//! them.
 //!
 //! This is intended to serve as the primary API for the library.
 
-pub use primitives::NoiseModule;
+pub use primitives::{NoiseModule, VectorNoiseModule};
 pub use modifiers::Modifiable;
 pub use fractal::{
     PinkNoise,
     BillowNoise,
-    RidgedMultifractalNoise
+    RidgedMultifractalNoise,
+    HybridMultifractalNoise,
+    HeteroTerrainNoise,
+    SwissTurbulenceNoise,
+    JordanTurbulenceNoise,
+    ErodedNoise,
+    Flow
 };
 pub use geometry::{
     ConstNoise,
@@ -22,8 +29,42 @@ pub use geometry::{
 };
 pub use modifiers::{
     ClampedNoise,
+    Clamped,
     ScaledBiasedNoise,
+    ScaledBiased,
     TranslatedNoise,
+    Translated,
     RotatedNoise,
-    ModifierNoise
+    Rotated,
+    Rotated3Noise,
+    ScaledInputNoise,
+    ScaledInput,
+    AffineNoise,
+    ModifierNoise,
+    SelectNoise,
+    BlendNoise,
+    CombinedNoise,
+    Operation,
+    Sum,
+    Difference,
+    Product,
+    Minimum,
+    Maximum,
+    SharedNoise,
+    TurbulenceNoise,
+    DisplaceNoise,
+    TerraceNoise,
+    CurveNoise,
+    Interpolation,
+    CatmullRom,
+    MonotoneCubic
+};
+pub use shader::CompiledNoise;
+pub use fields::{
+    VectorModifiable,
+    CurlNoise,
+    ComposedVectorNoise,
+    SharedVectorNoise,
+    MagnitudeNoise,
+    ComponentNoise
 };
@@ -0,0 +1,54 @@
+/*
+    This file is part of grunge, a coherent noise generation library.
+*/
+
+//! Helpers shared by the modules that sum octaves of noise, i.e. the fractal
+//! modules and CurlNoise's potential.
+
+/// Checks that the number of octaves is within the supported range, where
+/// `minimum` is the fewest octaves the module can sum (either one or two).
+pub fn check_octaves(octaves: f32, minimum: f32) -> Result<(), &'static str> {
+    if octaves < minimum {
+        Err(if minimum > 1.0 {
+            "The number of octaves must be two or greater."
+        } else {
+            "The number of octaves must be one or greater."
+        })
+    } else if octaves > 30.0 {
+        Err("The number of octaves must be less than 30.")
+    } else {
+        Ok(())
+    }
+}
+
+/// The number of octaves to sample: `octaves`, less any whose frequency would
+/// exceed `max_frequency`. The first octave is always sampled.
+///
+/// Octave $i$ has frequency $fl\^i$, so those at or below $m / l$ are
+/// sampled in full, and the one between $m / l$ and $m$ is weighted by how
+/// close (in log-frequency) it is to the former. This makes the output change
+/// continuously with the maximum frequency.
+pub fn octave_count(octaves: f32, frequency: f32, lacunarity: f32,
+                    max_frequency: f32) -> f32 {
+    if max_frequency.is_infinite() || lacunarity <= 1.0 {
+        return octaves;
+    }
+
+    let limit = (max_frequency / frequency.abs()).ln() / lacunarity.ln();
+    octaves.min(limit).max(1.0)
+}
+
+/// The weight of the given octave when sampling `count` octaves, which is one
+/// for every octave but a fractional last one.
+pub fn octave_weight(count: f32, octave: uint) -> f32 {
+    (count - octave as f32).min(1.0)
+}
+
+/// The sum of the absolute amplitudes of `count` octaves, where each octave
+/// has `persistence` times the amplitude of the one before it.
+pub fn amplitude_sum(persistence: f32, count: f32) -> f32 {
+    range(0, count.ceil() as uint).fold(0.0, |sum, octave| {
+        sum + octave_weight(count, octave) *
+            persistence.abs().powi(octave as i32)
+    })
+}
@@ -10,23 +10,92 @@
 
 use cgmath::vector::{Vector, Vector2, Vector3, Vector4, dot};
 
+use serialize::json::Json;
+
+use shader::{Program, Register};
+
 /// NoiseModules are objects that can be asked to generate procedural noise
 /// values for a given coordinate.
 ///
 /// These are the primary interfaces provided by the library for working with
-/// noise, although more primitive functions are available.
-pub trait NoiseModule: Clone {
+/// noise, although more primitive functions are available. Modules can be
+/// sent and shared between threads, so that a graph can be sampled from
+/// several of them at once.
+pub trait NoiseModule: Clone + Send + Sync {
     /// Generates a noise value for the given coordinates. It is possible for
     /// this method to fail or be impossible, and in this case the Result will
     /// contain an appropriate error message.
     fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str>;
 
+    /// Generates a noise value for the given coordinates in three dimensions.
+    /// Modules that only support two-dimensional input will return an error.
+    #[allow(unused_variable)]
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        Err("This module does not support three-dimensional input.")
+    }
+
+    /// Returns a conservative range `(min, max)` for the output of this
+    /// module, for any input coordinates. The true range of output values may
+    /// be considerably narrower. Modules that cannot determine their range
+    /// return an infinite one.
+    fn bounds(&self) -> (f32, f32) {
+        (Float::neg_infinity(), Float::infinity())
+    }
+
+    /// Describes this module as JSON, so that it can be saved and loaded again
+    /// with the [serial](../serial/index.html) module. Modules that cannot be
+    /// described, such as those wrapping unregistered functions, return an
+    /// error.
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Err("This module cannot be serialized.")
+    }
+
+    /// Appends instructions computing this module's two-dimensional output at
+    /// the point (`x`, `y`) to a shader [program](../shader/index.html), and
+    /// returns the register holding the result. Modules with no equivalent
+    /// shader code return an error.
+    #[allow(unused_variable)]
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        Err("This module cannot be compiled to a shader.")
+    }
+
     #[experimental]
     fn to_box(&self) -> Box<NoiseModule> {
         box self.clone() as Box<NoiseModule>
     }
 }
 
+/// VectorNoiseModules are the vector-valued counterpart of NoiseModules. They
+/// generate a vector, rather than a single value, for a given coordinate, for
+/// use as velocity fields, displacement vectors and the like. Like
+/// NoiseModules, they can be sent and shared between threads.
+pub trait VectorNoiseModule: Clone + Send + Sync {
+    /// Generates a noise vector for the given coordinates. It is possible for
+    /// this method to fail or be impossible, and in this case the Result will
+    /// contain an appropriate error message.
+    fn generate_2d(&self, x: f32, y: f32) -> Result<Vector2<f32>, &str>;
+
+    /// Generates a noise vector for the given coordinates in three dimensions.
+    /// Modules that only support two-dimensional input will return an error.
+    #[allow(unused_variable)]
+    fn generate_3d(&self, x: f32, y: f32, z: f32)
+        -> Result<Vector3<f32>, &str> {
+        Err("This module does not support three-dimensional input.")
+    }
+
+    /// Describes this module as JSON, in the same way as NoiseModule's
+    /// `to_json()` method.
+    fn to_json(&self) -> Result<Json, &'static str> {
+        Err("This module cannot be serialized.")
+    }
+
+    #[experimental]
+    fn to_box(&self) -> Box<VectorNoiseModule> {
+        box self.clone() as Box<VectorNoiseModule>
+    }
+}
+
 /// The factor needed to skew x-y coordinates to coordinates on the grid of
 /// simplexes in two dimensions. Approximates $\frac{\sqrt{3} - 1}{2}$.
 static HAIRY_2D: f32 = 0.366025403784439;
@@ -35,6 +104,12 @@ static HAIRY_2D: f32 = 0.366025403784439;
 /// coordinates in two dimensions. Approximates $\frac{3 - \sqrt{3}}{6}$.
 static SKEW_2D: f32 = 0.211324865405187;
 
+/// A bound on the magnitude of the output of `snoise_2d`, `snoise_3d` and
+/// `sflow_2d`, i.e. their output always lies within
+/// [-SNOISE_BOUND, SNOISE_BOUND]. This was determined numerically, and
+/// includes a small margin.
+pub static SNOISE_BOUND: f32 = 3.2;
+
 /// Generate the coherent noise value for a point using the Simplex Noise
 /// method proposed by Ken Perlin [1].
 ///
@@ -57,6 +132,125 @@ static SKEW_2D: f32 = 0.211324865405187;
 ///    (http://www.itn.liu.se/~stegu/simplexnoise/simplexnoise.pdf).
 ///    Technical Report. Linköping University, Sweden.
 pub fn snoise_2d(x: f32, y: f32, seed: uint) -> f32 {
+    let (i0, i1, x0, x1, x2) = simplex_cell_2d(x, y);
+
+    let mut m = Vector3::new(
+        (0.5f32 - dot(x0, x0)).max(0.0f32),
+        (0.5f32 - dot(x1, x1)).max(0.0f32),
+        (0.5f32 - dot(x2, x2)).max(0.0f32)
+    );
+    m = m * m * m;
+
+    let (h4, h2, norm) = gradients_2d(i0, i1, seed);
+    m = m * norm;
+
+    let g = Vector3::new(
+        h4.x * x0.x + h2.x * x0.y,
+        h4.y * x1.x + h2.y * x1.y,
+        h4.z * x2.x + h2.z * x2.y
+    );
+
+    // Scale the result to within about [-1, 1]
+    130.0 * dot(m, g)
+}
+
+/// Generate the coherent noise value for a point using the Simplex Noise
+/// method, along with its analytic gradient (i.e. the partial derivatives of
+/// the noise with respect to x and y).
+///
+/// The value is the same as that of `snoise_2d`, up to rounding error. The
+/// gradient is considerably cheaper to compute this way than by finite
+/// differences, and is exact.
+pub fn snoise_2d_grad(x: f32, y: f32, seed: uint) -> (f32, Vector2<f32>) {
+    sflow_2d_grad(x, y, seed, 0.0)
+}
+
+/// Generate the coherent noise value for a point using the Flow Noise method
+/// of Perlin and Neyret [1], in which the gradients at the corners of each
+/// simplex are rotated by `angle` radians.
+///
+/// With an angle of zero, the value is the same as that of `snoise_2d`, up to
+/// rounding error. Varying the angle smoothly over time makes the noise swirl
+/// in place, rather than sliding or morphing uniformly.
+///
+/// 1. Perlin, Ken and Fabrice Neyret. (2001). [_Flow Noise_]
+///    (http://evasion.imag.fr/Publications/2001/PN01/). SIGGRAPH Technical
+///    Sketches and Applications.
+pub fn sflow_2d(x: f32, y: f32, seed: uint, angle: f32) -> f32 {
+    let (i0, i1, x0, x1, x2) = simplex_cell_2d(x, y);
+    let (h4, h2, norm) = gradients_2d(i0, i1, seed);
+    let (sin, cos) = angle.sin_cos();
+
+    let mut m = Vector3::new(
+        (0.5f32 - dot(x0, x0)).max(0.0f32),
+        (0.5f32 - dot(x1, x1)).max(0.0f32),
+        (0.5f32 - dot(x2, x2)).max(0.0f32)
+    );
+    m = m * m * m;
+    m = m * norm;
+
+    // Rotate each gradient (h4, h2) by the angle.
+    let gx = h4.mul_s(cos) - h2.mul_s(sin);
+    let gy = h4.mul_s(sin) + h2.mul_s(cos);
+
+    let g = Vector3::new(
+        gx.x * x0.x + gy.x * x0.y,
+        gx.y * x1.x + gy.y * x1.y,
+        gx.z * x2.x + gy.z * x2.y
+    );
+
+    130.0 * dot(m, g)
+}
+
+/// Generate the coherent noise value for a point using the Flow Noise method,
+/// along with its analytic gradient, in the same way as `snoise_2d_grad`.
+///
+/// The value is the same as that of `sflow_2d`, up to rounding error. Rotating
+/// the gradients at the corners does not change the form of the derivative,
+/// so this costs no more than `snoise_2d_grad`.
+pub fn sflow_2d_grad(x: f32, y: f32, seed: uint, angle: f32)
+    -> (f32, Vector2<f32>) {
+    let (i0, i1, x0, x1, x2) = simplex_cell_2d(x, y);
+    let (h4, h2, norm) = gradients_2d(i0, i1, seed);
+    let (sin, cos) = angle.sin_cos();
+
+    // Rotate each gradient (h4, h2) by the angle, as in `sflow_2d`.
+    let gx = h4.mul_s(cos) - h2.mul_s(sin);
+    let gy = h4.mul_s(sin) + h2.mul_s(cos);
+
+    let corners = [x0, x1, x2];
+    let gradients = [
+        Vector2::new(gx.x, gy.x).mul_s(norm.x),
+        Vector2::new(gx.y, gy.y).mul_s(norm.y),
+        Vector2::new(gx.z, gy.z).mul_s(norm.z)
+    ];
+
+    let mut value = 0.0f32;
+    let mut deriv = Vector2::new(0.0f32, 0.0);
+
+    for i in range(0u, 3) {
+        let t = (0.5f32 - dot(corners[i], corners[i])).max(0.0f32);
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let g = dot(gradients[i], corners[i]);
+
+        // Each corner contributes $t\^3 (g \cdot x)$, where
+        // $t = 0.5 - x \cdot x$, so its derivative is
+        // $t\^3 g - 6 t\^2 (g \cdot x) x$.
+        value += t3 * g;
+        deriv = deriv + gradients[i].mul_s(t3) +
+            corners[i].mul_s(-6.0 * t2 * g);
+    }
+
+    (130.0 * value, deriv.mul_s(130.0))
+}
+
+/// Finds the simplex containing the point (x, y). Returns the skewed
+/// coordinates of the cell, the offset of the simplex's middle corner within
+/// the cell, and the vectors from each of the three corners to the point.
+fn simplex_cell_2d(x: f32, y: f32) -> (Vector2<f32>, Vector2<f32>,
+                                       Vector2<f32>, Vector2<f32>,
+                                       Vector2<f32>) {
     let v = Vector2::new(x, y);
     // First, determine which cell of N! = 2 simplexes we are in, and where
     // in that cell we are.
@@ -91,16 +285,20 @@ pub fn snoise_2d(x: f32, y: f32, seed: uint) -> f32 {
     let x1 = x0 - i1 + Vector2::new(SKEW_2D, SKEW_2D);
     let x2 = x0 + Vector2::new(-1.0 + 2.0 * SKEW_2D, -1.0 + 2.0 * SKEW_2D);
 
-    let mut m = Vector3::new(
-        (0.5f32 - dot(x0, x0)).max(0.0f32),
-        (0.5f32 - dot(x1, x1)).max(0.0f32),
-        (0.5f32 - dot(x2, x2)).max(0.0f32)
-    );
-    m = m * m * m;
+    (i0, i1, x0, x1, x2)
+}
 
-    // If you expect to have large values for the input point, it may be a
-    // good idea to mark i0 as mutable and take $i_0 = i_0 \% 289$:
-    // i0 = Vector2::new(i0.x % 289, i0.y % 289);
+/// Computes the pseudo-random gradients at the corners of the simplex with
+/// cell coordinates `i0` and middle corner offset `i1`. Returns the x and y
+/// components of the (unnormalized) gradients, and the factors needed to
+/// normalize them, with one corner per vector component.
+fn gradients_2d(i0: Vector2<f32>, i1: Vector2<f32>, seed: uint)
+    -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+    // The hash polynomial has period 289, but only stays exact in an f32 for
+    // arguments up to about 700, so reduce the cell coordinates first. This
+    // changes no hash, and keeps those of distant cells identical to the
+    // shaders', whose floored modulus would otherwise round differently.
+    let i0 = Vector2::new(i0.x % 289.0, i0.y % 289.0);
     let fseed = seed as f32;
 
     let p = ((Vector3::new(i0.y, i0.y + i1.y, i0.y + 1.0).permutation_hash()
@@ -120,20 +318,140 @@ pub fn snoise_2d(x: f32, y: f32, seed: uint) -> f32 {
     );
     let h4 = h1 - h3;
 
-    m = m * Vector3::new(
+    let norm = Vector3::new(
         1.79284291400159 - 0.85373472095314 * ( h4.x * h4.x + h2.x * h2.x ),
         1.79284291400159 - 0.85373472095314 * ( h4.y * h4.y + h2.y * h2.y ),
         1.79284291400159 - 0.85373472095314 * ( h4.z * h4.z + h2.z * h2.z )
     );
 
+    (h4, h2, norm)
+}
+
+/// The factor needed to skew x-y-z coordinates to coordinates on the grid of
+/// simplexes in three dimensions, i.e. $\frac{1}{3}$.
+static HAIRY_3D: f32 = 0.333333333333333;
+
+/// The factor needed to unskew coordinates in the grid of simplexes to x-y-z
+/// coordinates in three dimensions, i.e. $\frac{1}{6}$.
+static SKEW_3D: f32 = 0.166666666666667;
+
+/// Generate the coherent noise value for a point in three dimensions using the
+/// Simplex Noise method.
+///
+/// This works in the same way as `snoise_2d`, except that space is divided into
+/// tetrahedra (3-simplexes), six of which make up each skewed cube of the grid.
+/// Gradients are picked from the surface of an octahedron, as in the GLSL code
+/// of McEwan et al. (2012) [1].
+///
+/// 1. McEwan, Ian, David Sheets, Stefan Gustavson, and Mark Richardson. (2012).
+///    [_Efficient Computational Noise in GLSL_]
+///    (http://dx.doi.org/10.1080/2151237X.2012.649621). Journal of Graphics
+///    Tools 16(2): 85-94.
+pub fn snoise_3d(x: f32, y: f32, z: f32, seed: uint) -> f32 {
+    let (corners, hashes) = simplex_cell_3d(x, y, z, seed);
+    let mut result = 0.0f32;
+
+    for i in range(0u, 4) {
+        let m = (0.6f32 - dot(corners[i], corners[i])).max(0.0f32);
+        result += m * m * m * m * dot(gradient_3d(hashes[i]), corners[i]);
+    }
+
+    // Scale the result to within about [-1, 1]
+    42.0 * result
+}
+
+/// Generate the coherent noise value for a point in three dimensions using the
+/// Simplex Noise method, along with its analytic gradient.
+///
+/// The value is the same as that of `snoise_3d`, up to rounding error.
+pub fn snoise_3d_grad(x: f32, y: f32, z: f32, seed: uint)
+    -> (f32, Vector3<f32>) {
+    let (corners, hashes) = simplex_cell_3d(x, y, z, seed);
+
+    let mut value = 0.0f32;
+    let mut deriv = Vector3::new(0.0f32, 0.0, 0.0);
+
+    for i in range(0u, 4) {
+        let t = (0.6f32 - dot(corners[i], corners[i])).max(0.0f32);
+        let t3 = t * t * t;
+        let t4 = t3 * t;
+        let gradient = gradient_3d(hashes[i]);
+        let g = dot(gradient, corners[i]);
+
+        // Each corner contributes $t\^4 (g \cdot x)$, where
+        // $t = 0.6 - x \cdot x$, so its derivative is
+        // $t\^4 g - 8 t\^3 (g \cdot x) x$.
+        value += t4 * g;
+        deriv = deriv + gradient.mul_s(t4) + corners[i].mul_s(-8.0 * t3 * g);
+    }
+
+    (42.0 * value, deriv.mul_s(42.0))
+}
+
+/// Finds the simplex containing the point (x, y, z). Returns the vectors from
+/// each of its four corners to the point, and the permutation hash of each
+/// corner.
+fn simplex_cell_3d(x: f32, y: f32, z: f32, seed: uint)
+    -> ([Vector3<f32>, ..4], [f32, ..4]) {
+    let v = Vector3::new(x, y, z);
+    // Skew the input to find the cell of N! = 6 simplexes we are in, and the
+    // vector from the cell's origin to the point.
+    let s = (v.x + v.y + v.z) * HAIRY_3D;
+    let i0 = Vector3::new((v.x + s).floor(), (v.y + s).floor(), (v.z + s).floor());
+    let t = (i0.x + i0.y + i0.z) * SKEW_3D;
+    let x0 = v - i0 + Vector3::new(t, t, t);
+
+    // Which simplex we are in is determined by the ordering of the components
+    // of x0. The second and third corners are found by stepping along the
+    // largest component, and then the two largest components.
     let g = Vector3::new(
-        h4.x * x0.x + h2.x * x0.y,
-        h4.y * x1.x + h2.y * x1.y,
-        h4.z * x2.x + h2.z * x2.y
+        if x0.x >= x0.y { 1.0f32 } else { 0.0 },
+        if x0.y >= x0.z { 1.0f32 } else { 0.0 },
+        if x0.z >= x0.x { 1.0f32 } else { 0.0 }
     );
+    let l = Vector3::new(1.0 - g.z, 1.0 - g.x, 1.0 - g.y);
+    let i1 = Vector3::new(g.x.min(l.x), g.y.min(l.y), g.z.min(l.z));
+    let i2 = Vector3::new(g.x.max(l.x), g.y.max(l.y), g.z.max(l.z));
 
-    // Scale the result to within about [-1, 1]
-    130.0 * dot(m, g)
+    let x1 = x0 - i1 + Vector3::new(SKEW_3D, SKEW_3D, SKEW_3D);
+    let x2 = x0 - i2 + Vector3::new(HAIRY_3D, HAIRY_3D, HAIRY_3D);
+    let x3 = x0 - Vector3::new(0.5, 0.5, 0.5);
+
+    let fseed = seed as f32;
+
+    let p = (((Vector4::new(i0.z, i0.z + i1.z, i0.z + i2.z, i0.z + 1.0)
+            .permutation_hash()
+            + Vector4::new(i0.y, i0.y + i1.y, i0.y + i2.y, i0.y + 1.0))
+            .permutation_hash()
+            + Vector4::new(i0.x, i0.x + i1.x, i0.x + i2.x, i0.x + 1.0))
+            .permutation_hash()
+            + Vector4::new(fseed, fseed, fseed, fseed))
+            .permutation_hash();
+
+    ([x0, x1, x2, x3], [p.x, p.y, p.z, p.w])
+}
+
+/// Maps a permutation hash to a normalized gradient vector on the surface of
+/// an octahedron, following McEwan et al. (2012).
+fn gradient_3d(hash: f32) -> Vector3<f32> {
+    // Split the hash into a 7x7 grid of points in [-1, 1]^2, and fold the
+    // points outside the diamond |x| + |y| <= 1 onto the lower half of the
+    // octahedron.
+    let j = hash - 49.0 * (hash * 0.020408163265306).floor();
+    let gx = (j * 0.142857142857143).floor();
+    let gy = (j - 7.0 * gx).floor();
+    let mut g = Vector3::new(
+        gx * 0.285714285714286 - 0.928571428571429,
+        gy * 0.285714285714286 - 0.928571428571429,
+        0.0
+    );
+    g.z = 1.0 - g.x.abs() - g.y.abs();
+    if g.z <= 0.0 {
+        g.x -= g.x.floor() * 2.0 + 1.0;
+        g.y -= g.y.floor() * 2.0 + 1.0;
+    }
+
+    g.mul_s(1.79284291400159 - 0.85373472095314 * dot(g, g))
 }
 
 /// For convenience, this trait is implemented by float-valued vectors in order
@@ -0,0 +1,357 @@
+/*
+    This file is part of grunge, a coherent noise generation library.
+*/
+
+//! Building modules from named fields, shared by the [serial]
+//! (../serial/index.html) and [expr](../expr/index.html) modules. Both read
+//! the same fields with the same defaults, and differ only in where the
+//! fields come from and how errors are reported, which is left to the
+//! FieldReader they implement.
+
+use std::default::Default;
+
+use cgmath::matrix::Matrix4;
+use cgmath::quaternion::Quaternion;
+use cgmath::vector::{Vector2, Vector3};
+
+use primitives::{NoiseModule, VectorNoiseModule};
+use fractal::{PinkNoise, BillowNoise, RidgedMultifractalNoise,
+              HybridMultifractalNoise, HeteroTerrainNoise,
+              SwissTurbulenceNoise, JordanTurbulenceNoise, ErodedNoise, Flow};
+use geometry::{ConstNoise, CheckerboardNoise, CylinderNoise};
+use modifiers::{ClampedNoise, ScaledBiasedNoise, TranslatedNoise, RotatedNoise,
+                Rotated3Noise, ScaledInputNoise, AffineNoise, SelectNoise,
+                BlendNoise, CombinedNoise, Operation, Sum, Difference, Product,
+                Minimum, Maximum, DisplaceNoise, TerraceNoise, CurveNoise,
+                Interpolation};
+use fields::{CurlNoise, ComposedVectorNoise, MagnitudeNoise, ComponentNoise};
+
+/// A module read from fields, which may be vector-valued.
+pub enum Module {
+    ScalarModule(Box<NoiseModule>),
+    VectorModule(Box<VectorNoiseModule>),
+}
+
+/// Reads the fields of a module description by name, reporting errors of
+/// type `E`. Fields that are not given take the defaults passed in.
+pub trait FieldReader<E> {
+    /// An error for a module type that does not exist.
+    fn unknown(&self, kind: &str) -> E;
+
+    /// An error for a required field that was not given.
+    fn missing(&self, name: &str) -> E;
+
+    /// An error for a field with the wrong type or an invalid value.
+    fn invalid(&self, name: &str) -> E;
+
+    fn number(&self, name: &str) -> Result<f32, E>;
+
+    fn number_or(&self, name: &str, default: f32) -> Result<f32, E>;
+
+    fn uint_or(&self, name: &str, default: uint) -> Result<uint, E>;
+
+    fn bool_or(&self, name: &str, default: bool) -> Result<bool, E>;
+
+    fn numbers(&self, name: &str) -> Result<Vec<f32>, E>;
+
+    /// Reads a list of (input, output) pairs.
+    fn points(&self, name: &str) -> Result<Vec<(f32, f32)>, E>;
+
+    fn interpolation(&self, name: &str) -> Result<Interpolation, E>;
+
+    fn module(&self, name: &str) -> Result<Box<NoiseModule>, E>;
+
+    fn optional_module(&self, name: &str)
+        -> Result<Option<Box<NoiseModule>>, E>;
+
+    fn vector_module(&self, name: &str) -> Result<Box<VectorNoiseModule>, E>;
+
+    /// Reads the optional flow parameters of a fractal module.
+    fn flow(&self) -> Result<Option<Flow>, E>;
+
+    /// Reads a list of exactly `count` numbers.
+    fn numbers_of(&self, name: &str, count: uint) -> Result<Vec<f32>, E> {
+        let result = try!(self.numbers(name));
+        if result.len() == count { Ok(result) } else { Err(self.invalid(name)) }
+    }
+}
+
+/// Builds the module of the given type from its fields. TurbulenceNoise,
+/// FunctionNoise and ModifierNoise, whose fields are read differently by
+/// each format, are left to the caller.
+pub fn read<E, F: FieldReader<E>>(kind: &str, f: &F) -> Result<Module, E> {
+    Ok(match kind {
+        "pink" => ScalarModule(box try!(pink(f)) as Box<NoiseModule>),
+        "billow" => {
+            let d: BillowNoise = Default::default();
+            ScalarModule(box BillowNoise {
+                seed: try!(f.uint_or("seed", d.seed)),
+                frequency: try!(f.number_or("frequency", d.frequency)),
+                persistence: try!(f.number_or("persistence", d.persistence)),
+                lacunarity: try!(f.number_or("lacunarity", d.lacunarity)),
+                offset: try!(f.number_or("offset", d.offset)),
+                octaves: try!(f.number_or("octaves", d.octaves)),
+                max_frequency: try!(f.number_or("max_frequency",
+                                                d.max_frequency)),
+                flow: try!(f.flow()),
+                rotate_octaves: try!(f.bool_or("rotate_octaves",
+                                               d.rotate_octaves)),
+                offset_octaves: try!(f.bool_or("offset_octaves",
+                                               d.offset_octaves)),
+                normalize: try!(f.bool_or("normalize", d.normalize))
+            } as Box<NoiseModule>)
+        },
+        "ridged" => {
+            let d: RidgedMultifractalNoise = Default::default();
+            ScalarModule(box RidgedMultifractalNoise {
+                seed: try!(f.uint_or("seed", d.seed)),
+                frequency: try!(f.number_or("frequency", d.frequency)),
+                lacunarity: try!(f.number_or("lacunarity", d.lacunarity)),
+                octaves: try!(f.number_or("octaves", d.octaves)),
+                max_frequency: try!(f.number_or("max_frequency",
+                                                d.max_frequency)),
+                flow: try!(f.flow()),
+                rotate_octaves: try!(f.bool_or("rotate_octaves",
+                                               d.rotate_octaves)),
+                offset_octaves: try!(f.bool_or("offset_octaves",
+                                               d.offset_octaves)),
+                offset: try!(f.number_or("offset", d.offset)),
+                power: try!(f.number_or("power", d.power)),
+                gain: try!(f.number_or("gain", d.gain)),
+                normalize: try!(f.bool_or("normalize", d.normalize))
+            } as Box<NoiseModule>)
+        },
+        "hybrid" => {
+            let d: HybridMultifractalNoise = Default::default();
+            ScalarModule(box HybridMultifractalNoise {
+                seed: try!(f.uint_or("seed", d.seed)),
+                frequency: try!(f.number_or("frequency", d.frequency)),
+                lacunarity: try!(f.number_or("lacunarity", d.lacunarity)),
+                octaves: try!(f.number_or("octaves", d.octaves)),
+                max_frequency: try!(f.number_or("max_frequency",
+                                                d.max_frequency)),
+                flow: try!(f.flow()),
+                offset: try!(f.number_or("offset", d.offset)),
+                power: try!(f.number_or("power", d.power)),
+                normalize: try!(f.bool_or("normalize", d.normalize))
+            } as Box<NoiseModule>)
+        },
+        "hetero" => {
+            let d: HeteroTerrainNoise = Default::default();
+            ScalarModule(box HeteroTerrainNoise {
+                seed: try!(f.uint_or("seed", d.seed)),
+                frequency: try!(f.number_or("frequency", d.frequency)),
+                lacunarity: try!(f.number_or("lacunarity", d.lacunarity)),
+                octaves: try!(f.number_or("octaves", d.octaves)),
+                max_frequency: try!(f.number_or("max_frequency",
+                                                d.max_frequency)),
+                flow: try!(f.flow()),
+                offset: try!(f.number_or("offset", d.offset)),
+                power: try!(f.number_or("power", d.power)),
+                normalize: try!(f.bool_or("normalize", d.normalize))
+            } as Box<NoiseModule>)
+        },
+        "swiss" => {
+            let d: SwissTurbulenceNoise = Default::default();
+            ScalarModule(box SwissTurbulenceNoise {
+                seed: try!(f.uint_or("seed", d.seed)),
+                frequency: try!(f.number_or("frequency", d.frequency)),
+                lacunarity: try!(f.number_or("lacunarity", d.lacunarity)),
+                octaves: try!(f.number_or("octaves", d.octaves)),
+                max_frequency: try!(f.number_or("max_frequency",
+                                                d.max_frequency)),
+                flow: try!(f.flow()),
+                gain: try!(f.number_or("gain", d.gain)),
+                warp: try!(f.number_or("warp", d.warp)),
+                normalize: try!(f.bool_or("normalize", d.normalize))
+            } as Box<NoiseModule>)
+        },
+        "jordan" => {
+            let d: JordanTurbulenceNoise = Default::default();
+            ScalarModule(box JordanTurbulenceNoise {
+                seed: try!(f.uint_or("seed", d.seed)),
+                frequency: try!(f.number_or("frequency", d.frequency)),
+                lacunarity: try!(f.number_or("lacunarity", d.lacunarity)),
+                octaves: try!(f.number_or("octaves", d.octaves)),
+                max_frequency: try!(f.number_or("max_frequency",
+                                                d.max_frequency)),
+                flow: try!(f.flow()),
+                first_gain: try!(f.number_or("first_gain", d.first_gain)),
+                gain: try!(f.number_or("gain", d.gain)),
+                first_warp: try!(f.number_or("first_warp", d.first_warp)),
+                warp: try!(f.number_or("warp", d.warp)),
+                first_damp: try!(f.number_or("first_damp", d.first_damp)),
+                damp: try!(f.number_or("damp", d.damp)),
+                damp_scale: try!(f.number_or("damp_scale", d.damp_scale)),
+                normalize: try!(f.bool_or("normalize", d.normalize))
+            } as Box<NoiseModule>)
+        },
+        "eroded" => {
+            let d: ErodedNoise = Default::default();
+            ScalarModule(box ErodedNoise {
+                seed: try!(f.uint_or("seed", d.seed)),
+                frequency: try!(f.number_or("frequency", d.frequency)),
+                persistence: try!(f.number_or("persistence", d.persistence)),
+                lacunarity: try!(f.number_or("lacunarity", d.lacunarity)),
+                octaves: try!(f.number_or("octaves", d.octaves)),
+                max_frequency: try!(f.number_or("max_frequency",
+                                                d.max_frequency)),
+                flow: try!(f.flow()),
+                normalize: try!(f.bool_or("normalize", d.normalize))
+            } as Box<NoiseModule>)
+        },
+        "const" => ScalarModule(box ConstNoise::new(try!(f.number("value")))
+                                as Box<NoiseModule>),
+        "checkerboard" => ScalarModule(box CheckerboardNoise
+                                       as Box<NoiseModule>),
+        "cylinder" => {
+            let frequency = try!(f.number_or("frequency", 1.0));
+            ScalarModule(box CylinderNoise::new(frequency) as Box<NoiseModule>)
+        },
+        "clamp" => ScalarModule(box ClampedNoise {
+            source: try!(f.module("source")),
+            min: try!(f.number_or("min", -1.0)),
+            max: try!(f.number_or("max", 1.0))
+        } as Box<NoiseModule>),
+        "scalebias" => ScalarModule(box ScaledBiasedNoise {
+            source: try!(f.module("source")),
+            scale: try!(f.number_or("scale", 1.0)),
+            bias: try!(f.number_or("bias", 0.0))
+        } as Box<NoiseModule>),
+        "translate" => {
+            let source = try!(f.module("source"));
+            let v = try!(f.numbers_of("translation", 2));
+            ScalarModule(box TranslatedNoise {
+                source: source, translation: Vector2::new(v[0], v[1])
+            } as Box<NoiseModule>)
+        },
+        "rotate" => {
+            let source = try!(f.module("source"));
+            let rotation = try!(f.number("rotation"));
+            ScalarModule(box RotatedNoise::new(&*source, rotation)
+                         as Box<NoiseModule>)
+        },
+        "rotate_3d" => {
+            let source = try!(f.module("source"));
+            let q = try!(f.numbers_of("rotation", 4));
+            ScalarModule(box Rotated3Noise {
+                source: source,
+                rotation: Quaternion::new(q[0], q[1], q[2], q[3])
+            } as Box<NoiseModule>)
+        },
+        "scale_input" => {
+            let source = try!(f.module("source"));
+            let v = try!(f.numbers_of("scale", 3));
+            ScalarModule(box ScaledInputNoise {
+                source: source, scale: Vector3::new(v[0], v[1], v[2])
+            } as Box<NoiseModule>)
+        },
+        "affine" => {
+            let source = try!(f.module("source"));
+            let m = try!(f.numbers_of("matrix", 16));
+            ScalarModule(box AffineNoise {
+                source: source,
+                matrix: Matrix4::new(m[0],  m[1],  m[2],  m[3],
+                                     m[4],  m[5],  m[6],  m[7],
+                                     m[8],  m[9],  m[10], m[11],
+                                     m[12], m[13], m[14], m[15])
+            } as Box<NoiseModule>)
+        },
+        "add" => ScalarModule(box try!(combined(f, Sum)) as Box<NoiseModule>),
+        "subtract" => ScalarModule(box try!(combined(f, Difference))
+                                   as Box<NoiseModule>),
+        "multiply" => ScalarModule(box try!(combined(f, Product))
+                                   as Box<NoiseModule>),
+        "min" => ScalarModule(box try!(combined(f, Minimum))
+                              as Box<NoiseModule>),
+        "max" => ScalarModule(box try!(combined(f, Maximum))
+                              as Box<NoiseModule>),
+        "blend" => ScalarModule(box BlendNoise {
+            first: try!(f.module("first")),
+            second: try!(f.module("second")),
+            control: try!(f.module("control"))
+        } as Box<NoiseModule>),
+        "select" => ScalarModule(box SelectNoise {
+            outside: try!(f.module("outside")),
+            inside: try!(f.module("inside")),
+            control: try!(f.module("control")),
+            lower_bound: try!(f.number_or("lower_bound", -1.0)),
+            upper_bound: try!(f.number_or("upper_bound", 1.0)),
+            edge_falloff: try!(f.number_or("edge_falloff", 0.0))
+        } as Box<NoiseModule>),
+        "displace" => ScalarModule(box DisplaceNoise {
+            source: try!(f.module("source")),
+            x_displace: try!(f.module("x_displace")),
+            y_displace: try!(f.module("y_displace")),
+            z_displace: try!(f.optional_module("z_displace"))
+        } as Box<NoiseModule>),
+        "terrace" => {
+            // Built through new() so that the points are sorted and cleaned.
+            let source = try!(f.module("source"));
+            let points = try!(f.numbers("points"));
+            let mut terrace = TerraceNoise::new(&*source, points.as_slice());
+            terrace.invert = try!(f.bool_or("invert", false));
+            terrace.smoothness = try!(f.number_or("smoothness", 0.5));
+            ScalarModule(box terrace as Box<NoiseModule>)
+        },
+        "curve" => {
+            let source = try!(f.module("source"));
+            let points = try!(f.points("points"));
+            let interpolation = try!(f.interpolation("interpolation"));
+            match CurveNoise::new(&*source, points.as_slice(), interpolation) {
+                Ok(curve) => ScalarModule(box curve as Box<NoiseModule>),
+                Err(_) => return Err(f.invalid("points"))
+            }
+        },
+        "magnitude" => ScalarModule(box MagnitudeNoise {
+            source: try!(f.vector_module("source"))
+        } as Box<NoiseModule>),
+        "component" => ScalarModule(box ComponentNoise {
+            source: try!(f.vector_module("source")),
+            index: try!(f.uint_or("index", 0))
+        } as Box<NoiseModule>),
+        "curl" => {
+            let d: CurlNoise = Default::default();
+            VectorModule(box CurlNoise {
+                seed: try!(f.uint_or("seed", d.seed)),
+                frequency: try!(f.number_or("frequency", d.frequency)),
+                persistence: try!(f.number_or("persistence", d.persistence)),
+                lacunarity: try!(f.number_or("lacunarity", d.lacunarity)),
+                octaves: try!(f.number_or("octaves", d.octaves))
+            } as Box<VectorNoiseModule>)
+        },
+        "composed" => VectorModule(box ComposedVectorNoise {
+            x: try!(f.module("x")),
+            y: try!(f.module("y")),
+            z: try!(f.optional_module("z"))
+        } as Box<VectorNoiseModule>),
+        kind => return Err(f.unknown(kind))
+    })
+}
+
+/// Reads PinkNoise, which is also used within TurbulenceNoise.
+pub fn pink<E, F: FieldReader<E>>(f: &F) -> Result<PinkNoise, E> {
+    let d: PinkNoise = Default::default();
+    Ok(PinkNoise {
+        seed: try!(f.uint_or("seed", d.seed)),
+        frequency: try!(f.number_or("frequency", d.frequency)),
+        persistence: try!(f.number_or("persistence", d.persistence)),
+        lacunarity: try!(f.number_or("lacunarity", d.lacunarity)),
+        octaves: try!(f.number_or("octaves", d.octaves)),
+        max_frequency: try!(f.number_or("max_frequency", d.max_frequency)),
+        flow: try!(f.flow()),
+        rotate_octaves: try!(f.bool_or("rotate_octaves", d.rotate_octaves)),
+        offset_octaves: try!(f.bool_or("offset_octaves", d.offset_octaves)),
+        normalize: try!(f.bool_or("normalize", d.normalize))
+    })
+}
+
+/// Reads CombinedNoise, whose operation is given by the type of the module.
+fn combined<E, F: FieldReader<E>>(f: &F, operation: Operation)
+    -> Result<CombinedNoise, E> {
+    Ok(CombinedNoise {
+        operation: operation,
+        first: try!(f.module("first")),
+        second: try!(f.module("second"))
+    })
+}
@@ -0,0 +1,782 @@
+/*
+    This file is part of grunge, a coherent noise generation library.
+*/
+
+//! Saving and loading noise modules as JSON descriptions.
+//!
+//! Every built-in module can describe itself with the `to_json()` method of
+//! NoiseModule, as can modules wrapping functions that have been registered by
+//! name (see below). A description
+//! is an object whose `"type"` field names the module, with one field for each
+//! of the module's parameters and nested objects for its source modules:
+//!
+//! ```ignore
+//! {
+//!     "type": "clamp",
+//!     "min": 0.0,
+//!     "max": 1.0,
+//!     "source": { "type": "pink", "seed": 12, "octaves": 4.0 }
+//! }
+//! ```
+//!
+//! When loading a description, parameters that are left out take their default
+//! values, where the module has any. Infinite values are written as the strings
+//! `"inf"` and `"-inf"`, since JSON has no representation for them. Seeds
+//! above $2\^{53}$ cannot be represented exactly.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use grunge::modules::{NoiseModule, Modifiable, PinkNoise};
+//! use grunge::serial;
+//!
+//! let noise = PinkNoise::new(12).scalebias(0.5, 0.5);
+//! let text = serial::to_string(&noise).unwrap();
+//! let loaded = serial::from_str(text.as_slice()).unwrap();
+//! assert_eq!(noise.generate_2d(0.3, 0.7), loaded.generate_2d(0.3, 0.7));
+//! ```
+//!
+//! ## Graphs
+//!
+//! A description is a tree, so a module used by several parents is written out
+//! (and loaded) once for each of them. Graph descriptions instead give each
+//! module an ID in a `"nodes"` object, and any field that takes a module may
+//! refer to a node by its ID. The `"output"` field names the node to return:
+//!
+//! ```ignore
+//! {
+//!     "output": "terrain",
+//!     "nodes": {
+//!         "mask": { "type": "pink", "seed": 4, "frequency": 0.25 },
+//!         "hills": { "type": "blend", "first": "plains", "second": "peaks",
+//!                    "control": "mask" },
+//!         "terrain": { "type": "select", "outside": "hills",
+//!                      "inside": { "type": "const", "value": -1.0 },
+//!                      "control": "mask", "lower_bound": -1.0,
+//!                      "upper_bound": -0.5 },
+//!         "plains": { "type": "billow", "seed": 2 },
+//!         "peaks": { "type": "ridged", "seed": 3 }
+//!     }
+//! }
+//! ```
+//!
+//! Each node is loaded once, and shared between its parents with SharedNoise.
+//! Every node is checked when a graph is loaded, whether or not it is used, and
+//! references that form a cycle are reported as an error.
+//!
+//! `to_graph()` writes a module as a graph, in which each description that
+//! appears more than once is written once as a node, so that the modules
+//! shared by a loaded graph stay shared when it is written out and loaded
+//! again. Descriptions, rather than modules, are compared, so equal modules
+//! built separately are merged into one node as well; this does not change
+//! the output.
+//!
+//! ## Functions
+//!
+//! FunctionNoise and ModifierNoise wrap closures, which cannot be written out.
+//! Instead, a closure can be registered by name with a Registry, which creates
+//! modules that are described by that name. Loading the description with a
+//! Registry in which the same name has been registered gives back the module:
+//!
+//! ```rust
+//! use grunge::modules::{NoiseModule, Modifiable};
+//! use grunge::serial::Registry;
+//!
+//! let mut registry = Registry::new();
+//! let radius = 2.0;
+//! registry.register_function("disc", move |x: f32, y: f32| {
+//!     Ok(if x * x + y * y < radius * radius { 1.0 } else { -1.0 })
+//! });
+//!
+//! let noise = registry.function("disc").unwrap().scalebias(0.5, 0.5);
+//! let json = noise.to_json().unwrap();
+//! let loaded = registry.from_json(&json).unwrap();
+//! assert_eq!(noise.generate_2d(1.0, 1.0), loaded.generate_2d(1.0, 1.0));
+//! ```
+//!
+//! The free functions of this module load descriptions with an empty Registry.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, TreeMap};
+use std::default::Default;
+use std::sync::Arc;
+
+use serialize::json;
+use serialize::json::{Json, ToJson};
+
+use primitives::{NoiseModule, VectorNoiseModule};
+use fractal::Flow;
+use geometry::{FunctionNoise, FunctionNoiseFunction};
+use modifiers::{SharedNoise, TurbulenceNoise, Interpolation, CatmullRom,
+                MonotoneCubic, ModifierNoise, ModifierNoiseFunction};
+use fields::SharedVectorNoise;
+use reader;
+use reader::{FieldReader, ScalarModule, VectorModule};
+
+/// The ways in which loading a module description can fail.
+#[deriving(Clone, PartialEq, Show)]
+pub enum LoadError {
+    /// The input is not valid JSON. Contains the parser's error message.
+    SyntaxError(String),
+
+    /// A module description is not an object with a string `"type"` field.
+    NotAModule,
+
+    /// A module description has a type that names no known module.
+    UnknownType(String),
+
+    /// A module description is missing a required field. Contains the type of
+    /// the module and the name of the field.
+    MissingField(String, String),
+
+    /// A field of a module description has the wrong type or an invalid value.
+    /// Contains the type of the module and the name of the field.
+    InvalidField(String, String),
+
+    /// A module refers to a node that is not in the graph, or the description
+    /// is not a graph. Contains the ID of the node.
+    UnknownNode(String),
+
+    /// The nodes of a graph refer to each other in a cycle. Contains the IDs of
+    /// the nodes in the cycle, starting and ending with the same node.
+    Cycle(Vec<String>),
+
+    /// A function or modifier module names a function that has not been
+    /// registered. Contains the name of the function.
+    UnknownFunction(String),
+}
+
+/// The functions that descriptions of FunctionNoise and ModifierNoise may
+/// refer to by name, and which are used when loading descriptions.
+pub struct Registry {
+    functions: HashMap<String, Arc<FunctionNoiseFunction>>,
+    modifiers: HashMap<String, Arc<ModifierNoiseFunction>>,
+}
+
+impl Registry {
+    /// Creates a registry with no functions.
+    pub fn new() -> Registry {
+        Registry { functions: HashMap::new(), modifiers: HashMap::new() }
+    }
+
+    /// Registers a function for FunctionNoise under the given name, replacing
+    /// any function already registered under it.
+    pub fn register_function<F>(&mut self, name: &str, func: F)
+        where F: Fn(f32, f32) -> Result<f32, &'static str> + Send + Sync {
+        self.functions.insert(name.to_string(), Arc::new(func));
+    }
+
+    /// Registers a function for ModifierNoise under the given name, replacing
+    /// any function already registered under it.
+    pub fn register_modifier<F>(&mut self, name: &str, func: F)
+        where F: Fn(f32, f32, f32) -> Result<f32, &'static str> + Send + Sync {
+        self.modifiers.insert(name.to_string(), Arc::new(func));
+    }
+
+    /// Creates a FunctionNoise with the function registered under the given
+    /// name, if there is one.
+    pub fn function(&self, name: &str) -> Option<FunctionNoise> {
+        self.functions.find(&name.to_string()).map(|func| FunctionNoise {
+            func: func.clone(), name: Some(name.to_string())
+        })
+    }
+
+    /// Creates a ModifierNoise with the given source and the function
+    /// registered under the given name, if there is one.
+    pub fn modifier(&self, name: &str, source: &NoiseModule)
+        -> Option<ModifierNoise> {
+        self.modifiers.find(&name.to_string()).map(|func| ModifierNoise {
+            source: source.to_box(), func: func.clone(),
+            name: Some(name.to_string())
+        })
+    }
+
+    /// Loads a module from its description as JSON text.
+    pub fn from_str(&self, text: &str) -> Result<Box<NoiseModule>, LoadError> {
+        match json::from_str(text) {
+            Ok(json) => self.from_json(&json),
+            Err(err) => Err(SyntaxError(format!("{}", err)))
+        }
+    }
+
+    /// Loads a module from its description.
+    pub fn from_json(&self, json: &Json)
+        -> Result<Box<NoiseModule>, LoadError> {
+        load_module(json, &Context::new(None, self))
+    }
+
+    /// Loads a vector-valued module from its description.
+    pub fn vector_from_json(&self, json: &Json)
+        -> Result<Box<VectorNoiseModule>, LoadError> {
+        load_vector(json, &Context::new(None, self))
+    }
+
+    /// Loads the output module of a graph from its description as JSON text.
+    pub fn graph_from_str(&self, text: &str)
+        -> Result<Box<NoiseModule>, LoadError> {
+        match json::from_str(text) {
+            Ok(json) => self.graph_from_json(&json),
+            Err(err) => Err(SyntaxError(format!("{}", err)))
+        }
+    }
+
+    /// Loads the output module of a graph from its description.
+    pub fn graph_from_json(&self, json: &Json)
+        -> Result<Box<NoiseModule>, LoadError> {
+        let graph = "graph".to_string();
+        let object = match json.as_object() {
+            Some(object) => object,
+            None => return Err(MissingField(graph, "nodes".to_string()))
+        };
+        let nodes = match object.find(&"nodes".to_string()) {
+            Some(nodes) => match nodes.as_object() {
+                Some(nodes) => nodes,
+                None => return Err(InvalidField(graph, "nodes".to_string()))
+            },
+            None => return Err(MissingField(graph, "nodes".to_string()))
+        };
+        let output = match object.find(&"output".to_string()) {
+            Some(output) => match output.as_string() {
+                Some(output) => output,
+                None => return Err(InvalidField(graph, "output".to_string()))
+            },
+            None => return Err(MissingField(graph, "output".to_string()))
+        };
+
+        let context = Context::new(Some(nodes), self);
+
+        // Load every node, so that errors are reported even in unused ones.
+        for (id, node) in nodes.iter() {
+            let vector = match node.find(&"type".to_string())
+                                   .and_then(|kind| kind.as_string()) {
+                Some(kind) => is_vector_kind(kind),
+                None => false
+            };
+            if vector {
+                try!(context.vector(id.as_slice()));
+            } else {
+                try!(context.module(id.as_slice()));
+            }
+        }
+
+        if context.is_vector(output) {
+            return Err(InvalidField(graph, "output".to_string()));
+        }
+        context.module(output)
+    }
+}
+
+/// Builds the description of a module of type `kind` with the given fields.
+pub fn describe(kind: &str, fields: Vec<(&str, Json)>) -> Json {
+    let mut object = TreeMap::new();
+    object.insert("type".to_string(), kind.to_string().to_json());
+    for (name, value) in fields.move_iter() {
+        object.insert(name.to_string(), value);
+    }
+    object.to_json()
+}
+
+/// Converts a number to JSON, writing infinities and NaN as strings.
+pub fn number(value: f32) -> Json {
+    if value.is_nan() {
+        "nan".to_string().to_json()
+    } else if value.is_infinite() {
+        (if value > 0.0 { "inf" } else { "-inf" }).to_string().to_json()
+    } else {
+        value.to_json()
+    }
+}
+
+/// Converts a list of numbers to JSON.
+pub fn numbers(values: &[f32]) -> Json {
+    values.iter().map(|&value| number(value)).collect::<Vec<Json>>().to_json()
+}
+
+/// Writes the description of `module` as pretty-printed JSON.
+pub fn to_string(module: &NoiseModule) -> Result<String, &'static str> {
+    Ok(try!(module.to_json()).to_pretty_str())
+}
+
+/// Writes the description of `module` as a graph, with a node for the module
+/// itself and for each description that is repeated within it.
+pub fn to_graph(module: &NoiseModule) -> Result<Json, &'static str> {
+    let tree = try!(module.to_json());
+    let mut writer = GraphWriter {
+        counts: HashMap::new(), ids: HashMap::new(), nodes: TreeMap::new()
+    };
+    writer.count(&tree);
+    let output = writer.node(&tree);
+
+    let mut graph = TreeMap::new();
+    graph.insert("output".to_string(), output.to_json());
+    graph.insert("nodes".to_string(), writer.nodes.to_json());
+    Ok(graph.to_json())
+}
+
+/// Writes the description of `module` as a graph of pretty-printed JSON.
+pub fn to_graph_string(module: &NoiseModule) -> Result<String, &'static str> {
+    Ok(try!(to_graph(module)).to_pretty_str())
+}
+
+/// Loads a module from its description as JSON text.
+pub fn from_str(text: &str) -> Result<Box<NoiseModule>, LoadError> {
+    Registry::new().from_str(text)
+}
+
+/// Loads a module from its description.
+pub fn from_json(json: &Json) -> Result<Box<NoiseModule>, LoadError> {
+    Registry::new().from_json(json)
+}
+
+/// Loads a vector-valued module from its description.
+pub fn vector_from_json(json: &Json)
+    -> Result<Box<VectorNoiseModule>, LoadError> {
+    Registry::new().vector_from_json(json)
+}
+
+/// Loads the output module of a graph from its description as JSON text.
+pub fn graph_from_str(text: &str) -> Result<Box<NoiseModule>, LoadError> {
+    Registry::new().graph_from_str(text)
+}
+
+/// Loads the output module of a graph from its description.
+pub fn graph_from_json(json: &Json) -> Result<Box<NoiseModule>, LoadError> {
+    Registry::new().graph_from_json(json)
+}
+
+/// Whether modules of the given type are vector-valued.
+fn is_vector_kind(kind: &str) -> bool {
+    kind == "curl" || kind == "composed"
+}
+
+/// The type of a module description, or None if `json` is not one. Flow
+/// parameters are described with a type, but are not modules.
+fn module_kind(json: &Json) -> Option<&str> {
+    match json.find(&"type".to_string()).and_then(|kind| kind.as_string()) {
+        Some("flow") => None,
+        kind => kind
+    }
+}
+
+/// The state of `to_graph()`: the number of times each description appears,
+/// keyed by its text, and the nodes written so far along with their IDs.
+struct GraphWriter {
+    counts: HashMap<String, uint>,
+    ids: HashMap<String, String>,
+    nodes: TreeMap<String, Json>,
+}
+
+impl GraphWriter {
+    /// Counts the descriptions within `json`. Those within a repeated
+    /// description are only counted once, so that they are not made into nodes
+    /// unless they are also used elsewhere.
+    fn count(&mut self, json: &Json) {
+        if module_kind(json).is_some() {
+            let key = format!("{}", json);
+            let count = self.counts.find(&key).map_or(0, |&count| count) + 1;
+            self.counts.insert(key, count);
+            if count > 1 {
+                return;
+            }
+        }
+
+        match json.as_object() {
+            Some(object) => for value in object.values() { self.count(value) },
+            None => ()
+        }
+        match json.as_list() {
+            Some(list) => for item in list.iter() { self.count(item) },
+            None => ()
+        }
+    }
+
+    /// Writes `json`, replacing it with the ID of a node if it is repeated.
+    fn write(&mut self, json: &Json) -> Json {
+        if module_kind(json).is_some() {
+            let key = format!("{}", json);
+            if self.counts.find(&key).map_or(false, |&count| count > 1) {
+                return self.node(json).to_json();
+            }
+        }
+        self.contents(json)
+    }
+
+    /// Writes the fields or items of `json`, replacing those that are repeated
+    /// descriptions with the IDs of nodes.
+    fn contents(&mut self, json: &Json) -> Json {
+        match json.as_object() {
+            Some(object) => {
+                let mut result = TreeMap::new();
+                for (name, value) in object.iter() {
+                    result.insert(name.clone(), self.write(value));
+                }
+                return result.to_json();
+            },
+            None => ()
+        }
+        match json.as_list() {
+            Some(list) => {
+                let mut result = Vec::new();
+                for item in list.iter() {
+                    result.push(self.write(item));
+                }
+                result.to_json()
+            },
+            None => json.clone()
+        }
+    }
+
+    /// Writes the description `json` as a node, unless it has been already,
+    /// and returns the ID of the node.
+    fn node(&mut self, json: &Json) -> String {
+        let key = format!("{}", json);
+        match self.ids.find(&key) {
+            Some(id) => return id.clone(),
+            None => ()
+        }
+
+        let contents = self.contents(json);
+        let id = format!("{}{}", module_kind(json).unwrap(), self.nodes.len());
+        self.nodes.insert(id.clone(), contents);
+        self.ids.insert(key, id.clone());
+        id
+    }
+}
+
+/// Loads a module from its description, resolving references to nodes in the
+/// given context.
+fn load_module<'a>(json: &'a Json, context: &'a Context<'a>)
+    -> Result<Box<NoiseModule>, LoadError> {
+    let f = try!(Fields::new(json, context));
+
+    Ok(match f.kind {
+        "turbulence" => box TurbulenceNoise {
+            source: try!(f.module("source")),
+            power: try!(f.number("power")),
+            x_distort: try!(reader::pink(&try!(f.object("x_distort")))),
+            y_distort: try!(reader::pink(&try!(f.object("y_distort")))),
+            z_distort: try!(reader::pink(&try!(f.object("z_distort"))))
+        } as Box<NoiseModule>,
+        "function" => {
+            let name = try!(f.string("name"));
+            match f.context.registry.function(name) {
+                Some(function) => box function as Box<NoiseModule>,
+                None => return Err(UnknownFunction(name.to_string()))
+            }
+        },
+        "modifier" => {
+            let source = try!(f.module("source"));
+            let name = try!(f.string("name"));
+            match f.context.registry.modifier(name, &*source) {
+                Some(modifier) => box modifier as Box<NoiseModule>,
+                None => return Err(UnknownFunction(name.to_string()))
+            }
+        },
+        kind if is_vector_kind(kind) => {
+            return Err(UnknownType(kind.to_string()))
+        },
+        kind => match try!(reader::read(kind, &f)) {
+            ScalarModule(module) => module,
+            VectorModule(_) => return Err(UnknownType(kind.to_string()))
+        }
+    })
+}
+
+/// Loads a vector-valued module from its description, resolving references
+/// to nodes in the given context.
+fn load_vector<'a>(json: &'a Json, context: &'a Context<'a>)
+    -> Result<Box<VectorNoiseModule>, LoadError> {
+    let f = try!(Fields::new(json, context));
+    if !is_vector_kind(f.kind) {
+        return Err(UnknownType(f.kind.to_string()));
+    }
+
+    match try!(reader::read(f.kind, &f)) {
+        VectorModule(module) => Ok(module),
+        ScalarModule(_) => Err(UnknownType(f.kind.to_string()))
+    }
+}
+
+/// Converts a JSON value to a number, accepting the strings used for
+/// infinities and NaN.
+fn as_number(json: &Json) -> Option<f32> {
+    match json.as_string() {
+        Some("inf") => Some(Float::infinity()),
+        Some("-inf") => Some(Float::neg_infinity()),
+        Some("nan") => Some(Float::nan()),
+        Some(_) => None,
+        None => json.as_f64().map(|value| value as f32)
+    }
+}
+
+/// The nodes of the graph being loaded, if any, along with the modules loaded
+/// from them so far, the chain of nodes currently being loaded and the
+/// registry of functions to load with.
+struct Context<'a> {
+    nodes: Option<&'a json::Object>,
+    registry: &'a Registry,
+    modules: RefCell<HashMap<String, Box<NoiseModule>>>,
+    vectors: RefCell<HashMap<String, Box<VectorNoiseModule>>>,
+    path: RefCell<Vec<String>>,
+}
+
+impl<'a> Context<'a> {
+    fn new(nodes: Option<&'a json::Object>, registry: &'a Registry)
+        -> Context<'a> {
+        Context {
+            nodes: nodes, registry: registry,
+            modules: RefCell::new(HashMap::new()),
+            vectors: RefCell::new(HashMap::new()),
+            path: RefCell::new(Vec::new())
+        }
+    }
+
+    /// Finds the description of a node, checking that it is not already being
+    /// loaded.
+    fn node(&self, id: &str) -> Result<&'a Json, LoadError> {
+        let path = self.path.borrow();
+        if path.iter().any(|node| node.as_slice() == id) {
+            let mut cycle: Vec<String> = path.iter()
+                .skip_while(|node| node.as_slice() != id)
+                .map(|node| node.clone()).collect();
+            cycle.push(id.to_string());
+            return Err(Cycle(cycle));
+        }
+
+        match self.nodes.and_then(|nodes| nodes.find(&id.to_string())) {
+            Some(json) => Ok(json),
+            None => Err(UnknownNode(id.to_string()))
+        }
+    }
+
+    /// Whether the node with the given ID is a vector-valued module.
+    fn is_vector(&self, id: &str) -> bool {
+        self.nodes.and_then(|nodes| nodes.find(&id.to_string()))
+            .and_then(|json| module_kind(json))
+            .map_or(false, is_vector_kind)
+    }
+
+    /// Loads the node with the given ID, or returns the module already loaded
+    /// from it.
+    fn module(&'a self, id: &str) -> Result<Box<NoiseModule>, LoadError> {
+        match self.modules.borrow().find(&id.to_string()) {
+            Some(module) => return Ok(module.clone()),
+            None => ()
+        }
+
+        let json = try!(self.node(id));
+        self.path.borrow_mut().push(id.to_string());
+        let result = load_module(json, self);
+        self.path.borrow_mut().pop();
+
+        let module = box SharedNoise::new(&*try!(result)) as Box<NoiseModule>;
+        self.modules.borrow_mut().insert(id.to_string(), module.clone());
+        Ok(module)
+    }
+
+    /// Loads the vector-valued node with the given ID, or returns the module
+    /// already loaded from it.
+    fn vector(&'a self, id: &str) -> Result<Box<VectorNoiseModule>, LoadError> {
+        match self.vectors.borrow().find(&id.to_string()) {
+            Some(module) => return Ok(module.clone()),
+            None => ()
+        }
+
+        let json = try!(self.node(id));
+        self.path.borrow_mut().push(id.to_string());
+        let result = load_vector(json, self);
+        self.path.borrow_mut().pop();
+
+        let module = box SharedVectorNoise::new(&*try!(result))
+            as Box<VectorNoiseModule>;
+        self.vectors.borrow_mut().insert(id.to_string(), module.clone());
+        Ok(module)
+    }
+}
+
+/// The fields of a module description, with methods for reading them that
+/// report errors in terms of the module's type.
+struct Fields<'a> {
+    kind: &'a str,
+    object: &'a json::Object,
+    context: &'a Context<'a>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(json: &'a Json, context: &'a Context<'a>)
+        -> Result<Fields<'a>, LoadError> {
+        let object = match json.as_object() {
+            Some(object) => object,
+            None => return Err(NotAModule)
+        };
+        match object.find(&"type".to_string()).and_then(|kind| kind.as_string()) {
+            Some(kind) => Ok(Fields {
+                kind: kind, object: object, context: context
+            }),
+            None => Err(NotAModule)
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&'a Json> {
+        self.object.find(&name.to_string())
+    }
+
+    fn string(&self, name: &str) -> Result<&'a str, LoadError> {
+        match self.get(name) {
+            Some(json) => json.as_string().ok_or(self.invalid(name)),
+            None => Err(self.missing(name))
+        }
+    }
+
+    fn list(&self, name: &str) -> Result<&'a json::List, LoadError> {
+        match self.get(name) {
+            Some(json) => json.as_list().ok_or(self.invalid(name)),
+            None => Err(self.missing(name))
+        }
+    }
+
+    /// Reads the fields of a nested module description, given either inline
+    /// or as the ID of a node, for modules that are loaded as a concrete type.
+    fn object(&self, name: &str) -> Result<Fields<'a>, LoadError> {
+        match self.get(name) {
+            Some(json) => match json.as_string() {
+                Some(id) => Fields::new(try!(self.context.node(id)),
+                                        self.context),
+                None => Fields::new(json, self.context)
+            },
+            None => Err(self.missing(name))
+        }
+    }
+}
+
+impl<'a> FieldReader<LoadError> for Fields<'a> {
+    fn unknown(&self, kind: &str) -> LoadError {
+        UnknownType(kind.to_string())
+    }
+
+    fn missing(&self, name: &str) -> LoadError {
+        MissingField(self.kind.to_string(), name.to_string())
+    }
+
+    fn invalid(&self, name: &str) -> LoadError {
+        InvalidField(self.kind.to_string(), name.to_string())
+    }
+
+    fn number(&self, name: &str) -> Result<f32, LoadError> {
+        match self.get(name) {
+            Some(json) => as_number(json).ok_or(self.invalid(name)),
+            None => Err(self.missing(name))
+        }
+    }
+
+    fn number_or(&self, name: &str, default: f32) -> Result<f32, LoadError> {
+        match self.get(name) {
+            Some(_) => self.number(name),
+            None => Ok(default)
+        }
+    }
+
+    fn uint_or(&self, name: &str, default: uint) -> Result<uint, LoadError> {
+        match self.get(name).map(|json| json.as_f64()) {
+            Some(Some(value)) if value >= 0.0 && value.fract() == 0.0 =>
+                Ok(value as uint),
+            Some(_) => Err(self.invalid(name)),
+            None => Ok(default)
+        }
+    }
+
+    fn bool_or(&self, name: &str, default: bool) -> Result<bool, LoadError> {
+        match self.get(name) {
+            Some(json) => json.as_boolean().ok_or(self.invalid(name)),
+            None => Ok(default)
+        }
+    }
+
+    fn numbers(&self, name: &str) -> Result<Vec<f32>, LoadError> {
+        let mut result = Vec::new();
+        for json in try!(self.list(name)).iter() {
+            result.push(try!(as_number(json).ok_or(self.invalid(name))));
+        }
+        Ok(result)
+    }
+
+    fn points(&self, name: &str) -> Result<Vec<(f32, f32)>, LoadError> {
+        let mut result = Vec::new();
+        for json in try!(self.list(name)).iter() {
+            let pair = try!(json.as_list().ok_or(self.invalid(name)));
+            if pair.len() != 2 {
+                return Err(self.invalid(name));
+            }
+            let x = try!(as_number(&pair[0]).ok_or(self.invalid(name)));
+            let y = try!(as_number(&pair[1]).ok_or(self.invalid(name)));
+            result.push((x, y));
+        }
+        Ok(result)
+    }
+
+    fn interpolation(&self, name: &str) -> Result<Interpolation, LoadError> {
+        match self.get(name).map(|json| json.as_string()) {
+            Some(Some("catmull_rom")) => Ok(CatmullRom),
+            Some(Some("monotone_cubic")) | None => Ok(MonotoneCubic),
+            Some(_) => Err(self.invalid(name))
+        }
+    }
+
+    /// Reads a module, given either as a description or as the ID of a node.
+    /// Vector-valued modules are reported as an invalid field.
+    fn module(&self, name: &str) -> Result<Box<NoiseModule>, LoadError> {
+        match self.get(name) {
+            Some(json) => match json.as_string() {
+                Some(id) if self.context.is_vector(id) =>
+                    Err(self.invalid(name)),
+                Some(id) => self.context.module(id),
+                None if module_kind(json).map_or(false, is_vector_kind) =>
+                    Err(self.invalid(name)),
+                None => load_module(json, self.context)
+            },
+            None => Err(self.missing(name))
+        }
+    }
+
+    fn optional_module(&self, name: &str)
+        -> Result<Option<Box<NoiseModule>>, LoadError> {
+        match self.get(name) {
+            Some(_) => Ok(Some(try!(self.module(name)))),
+            None => Ok(None)
+        }
+    }
+
+    /// Reads a vector-valued module, given either as a description or as the
+    /// ID of a node.
+    fn vector_module(&self, name: &str)
+        -> Result<Box<VectorNoiseModule>, LoadError> {
+        match self.get(name) {
+            Some(json) => match json.as_string() {
+                Some(id) => self.context.vector(id),
+                None => load_vector(json, self.context)
+            },
+            None => Err(self.missing(name))
+        }
+    }
+
+    fn flow(&self) -> Result<Option<Flow>, LoadError> {
+        let object = match self.get("flow") {
+            Some(json) => match json.as_object() {
+                Some(object) => object,
+                None => return Err(self.invalid("flow"))
+            },
+            None => return Ok(None)
+        };
+        let f = Fields {
+            kind: self.kind, object: object, context: self.context
+        };
+        let d: Flow = Default::default();
+
+        Ok(Some(Flow {
+            time: try!(f.number_or("time", d.time)),
+            speed: try!(f.number_or("speed", d.speed)),
+            speed_lacunarity: try!(f.number_or("speed_lacunarity",
+                                               d.speed_lacunarity))
+        }))
+    }
+}
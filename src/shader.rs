@@ -0,0 +1,922 @@
+/*
+    This file is part of grunge, a coherent noise generation library.
+*/
+
+//! Compiling noise modules to GLSL and WGSL shader code.
+//!
+//! A module is first lowered to a Program: a list of instructions, each of
+//! which computes one value from the values before it. Every instruction is a
+//! single floating-point operation or a call to the simplex noise function, so
+//! the program can be written out as a shader function line by line:
+//!
+//! ```ignore
+//! float terrain(vec2 p) {
+//!     float r0 = p.x;
+//!     float r1 = p.y;
+//!     float r2 = 5.00000000e-1;
+//!     float r3 = r0 * r2;
+//!     ...
+//!     return r41;
+//! }
+//! ```
+//!
+//! The emitted noise functions are ports of those in the [primitives]
+//! (../primitives/index.html) module, which themselves follow the GLSL code of
+//! McEwan et al. (2012), and the instructions perform the same operations in
+//! the same order as the modules themselves. The `evaluate()` method runs a
+//! program on the CPU, as a reference for what the shader computes; the same
+//! programs, once optimized, back CompiledNoise.
+//!
+//! Modules implement NoiseModule's `to_shader()` method to lower themselves,
+//! and lower their sources through `Program::lower()`. Those supported are
+//! PinkNoise, BillowNoise, RidgedMultifractalNoise, HybridMultifractalNoise,
+//! HeteroTerrainNoise and ConstNoise; the clamped, scaled and biased,
+//! translated, rotated, input-scaled and affine modifiers, whether boxed or
+//! generic; BlendNoise, CombinedNoise, SharedNoise, TurbulenceNoise,
+//! DisplaceNoise and CompiledNoise. The rest return an error:
+//!
+//! * SwissTurbulenceNoise, JordanTurbulenceNoise and ErodedNoise, which need
+//!   the gradient of the noise function;
+//! * Rotated3Noise, which samples its source in three dimensions;
+//! * CheckerboardNoise, CylinderNoise, SelectNoise, TerraceNoise and
+//!   CurveNoise, which need operations that programs lack, such as rounding
+//!   and comparison;
+//! * FunctionNoise and ModifierNoise, which wrap arbitrary functions;
+//! * MagnitudeNoise and ComponentNoise, whose sources are vector-valued.
+//!
+//! `Program::compile()` fails for a graph containing any of these. Programs
+//! built by `Program::compile_with_calls()` call them as opaque modules
+//! instead, so that any graph can be compiled, but can only be run on the CPU
+//! unless the shader defines the functions they call.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use grunge::modules::{NoiseModule, Modifiable, PinkNoise};
+//! use grunge::shader::Program;
+//!
+//! let noise = PinkNoise::new(3).scalebias(0.5, 0.5).clamp(0.0, 1.0);
+//! let program = Program::compile(&noise).unwrap();
+//! let glsl = program.to_glsl("terrain");
+//!
+//! let expected = noise.generate_2d(0.3, 0.7).unwrap();
+//! assert!((program.evaluate(0.3, 0.7).unwrap() - expected).abs() < 1e-5);
+//! ```
+//!
+//! Only two-dimensional input is supported.
+
+use std::fmt;
+use std::mem;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serialize::json::Json;
+
+use primitives::{NoiseModule, snoise_2d, sflow_2d};
+use modifiers::Modifiable;
+
+/// A reference to the value computed by an instruction of a Program, i.e. its
+/// index in the list of instructions.
+#[deriving(Clone, PartialEq)]
+pub struct Register(pub uint);
+
+impl fmt::Show for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Register(index) = *self;
+        write!(f, "r{}", index)
+    }
+}
+
+/// The instructions of a Program. Each computes a single value.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Instruction {
+    /// The x coordinate of the input.
+    InputX,
+
+    /// The y coordinate of the input.
+    InputY,
+
+    /// A constant value.
+    Constant(f32),
+
+    /// The sum of two values.
+    Plus(Register, Register),
+
+    /// The difference of two values.
+    Minus(Register, Register),
+
+    /// The product of two values.
+    Times(Register, Register),
+
+    /// The quotient of two values.
+    Divide(Register, Register),
+
+    /// The smaller of two values.
+    Min(Register, Register),
+
+    /// The larger of two values.
+    Max(Register, Register),
+
+    /// The absolute value of a value.
+    Abs(Register),
+
+    /// Simplex noise at the point (x, y) with the given seed, as computed by
+    /// `snoise_2d`.
+    Simplex(Register, Register, uint),
+
+    /// Flow noise at the point (x, y) with the given seed and gradient angle,
+    /// as computed by `sflow_2d`.
+    FlowSimplex(Register, Register, uint, f32),
+
+    /// A value put through a chain of steps `v * scale + bias`, in order. The
+    /// product and sum of each step are rounded separately, so a chain has
+    /// the same value as the separate instructions it replaces. A scale of
+    /// one or a bias of negative zero leaves that half of a step out.
+    ScaleBias(Register, Vec<(f32, f32)>),
+
+    /// The output at the point (x, y) of the program's module with the given
+    /// index, for a module that could not be lowered.
+    CallModule(Register, Register, uint),
+}
+
+/// A module called by a Program. Modules are compared by identity, so that
+/// clones of a program compare equal.
+pub struct OpaqueModule(pub Arc<Box<NoiseModule>>);
+
+impl Clone for OpaqueModule {
+    fn clone(&self) -> OpaqueModule {
+        let OpaqueModule(ref module) = *self;
+        OpaqueModule(module.clone())
+    }
+}
+
+impl PartialEq for OpaqueModule {
+    fn eq(&self, other: &OpaqueModule) -> bool {
+        let (&OpaqueModule(ref a), &OpaqueModule(ref b)) = (self, other);
+        &**a as *const Box<NoiseModule> == &**b as *const Box<NoiseModule>
+    }
+}
+
+impl fmt::Show for OpaqueModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OpaqueModule")
+    }
+}
+
+/// A noise module lowered to a list of instructions, which can be evaluated
+/// directly or written out as shader code.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Program {
+    /// The instructions, in the order they are computed.
+    pub instructions: Vec<Instruction>,
+
+    /// The register holding the output of the program.
+    pub output: Register,
+
+    /// The modules called by CallModule instructions.
+    pub modules: Vec<OpaqueModule>,
+
+    /// Whether `lower()` calls modules that cannot be lowered, rather than
+    /// failing.
+    pub calls: bool,
+}
+
+impl Program {
+    /// Creates an empty program. Its output should be set once instructions
+    /// computing it have been appended.
+    pub fn new() -> Program {
+        Program {
+            instructions: Vec::new(), output: Register(0),
+            modules: Vec::new(), calls: false
+        }
+    }
+
+    /// Lowers a module to a program computing its two-dimensional output.
+    pub fn compile(module: &NoiseModule) -> Result<Program, &'static str> {
+        let mut program = Program::new();
+        let x = program.push(InputX);
+        let y = program.push(InputY);
+        program.output = try!(program.lower(module, x, y));
+        Ok(program)
+    }
+
+    /// Lowers a module like `compile()`, but calls each part of it that
+    /// cannot be lowered, so that any module can be compiled. Shader code
+    /// written from the program must define the functions it calls; see
+    /// `to_glsl()`.
+    pub fn compile_with_calls(module: &NoiseModule) -> Program {
+        let mut program = Program::new();
+        program.calls = true;
+        let x = program.push(InputX);
+        let y = program.push(InputY);
+        // Lowering cannot fail once calls are allowed.
+        program.output = program.lower(module, x, y).unwrap();
+        program
+    }
+
+    /// Appends the instructions computing the output of a module at (x, y),
+    /// and returns the register holding it. Modules lower their sources
+    /// through this rather than `to_shader()`, so that if calls are allowed,
+    /// only the sources that cannot be lowered are called.
+    pub fn lower(&mut self, module: &NoiseModule, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        match module.to_shader(self, x, y) {
+            Err(_) if self.calls => Ok(self.call(module, x, y)),
+            result => result
+        }
+    }
+
+    /// Appends a call to a module at the point (x, y).
+    pub fn call(&mut self, module: &NoiseModule, x: Register, y: Register)
+        -> Register {
+        self.modules.push(OpaqueModule(Arc::new(module.to_box())));
+        let index = self.modules.len() - 1;
+        self.push(CallModule(x, y, index))
+    }
+
+    /// Appends an instruction, and returns the register holding its value.
+    pub fn push(&mut self, instruction: Instruction) -> Register {
+        self.instructions.push(instruction);
+        Register(self.instructions.len() - 1)
+    }
+
+    /// Appends a constant value.
+    pub fn constant(&mut self, value: f32) -> Register {
+        self.push(Constant(value))
+    }
+
+    /// Appends the sum `a + b`.
+    pub fn add(&mut self, a: Register, b: Register) -> Register {
+        self.push(Plus(a, b))
+    }
+
+    /// Appends the difference `a - b`.
+    pub fn sub(&mut self, a: Register, b: Register) -> Register {
+        self.push(Minus(a, b))
+    }
+
+    /// Appends the product `a * b`.
+    pub fn mul(&mut self, a: Register, b: Register) -> Register {
+        self.push(Times(a, b))
+    }
+
+    /// Appends the quotient `a / b`.
+    pub fn div(&mut self, a: Register, b: Register) -> Register {
+        self.push(Divide(a, b))
+    }
+
+    /// Appends the smaller of `a` and `b`.
+    pub fn min(&mut self, a: Register, b: Register) -> Register {
+        self.push(Min(a, b))
+    }
+
+    /// Appends the larger of `a` and `b`.
+    pub fn max(&mut self, a: Register, b: Register) -> Register {
+        self.push(Max(a, b))
+    }
+
+    /// Appends the absolute value of `a`.
+    pub fn abs(&mut self, a: Register) -> Register {
+        self.push(Abs(a))
+    }
+
+    /// Runs the program at the point (x, y) and returns its output, or the
+    /// error of a module it calls.
+    pub fn evaluate(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let mut values = Vec::with_capacity(self.instructions.len());
+        self.evaluate_with(&mut values, x, y)
+    }
+
+    /// Runs the program at the point (x, y) like `evaluate()`, but keeps the
+    /// value of each instruction in `values`, which is cleared first. Passing
+    /// the same buffer to every call saves allocating one per sample.
+    pub fn evaluate_with(&self, values: &mut Vec<f32>, x: f32, y: f32)
+        -> Result<f32, &str> {
+        values.clear();
+        values.grow(self.instructions.len(), 0.0);
+        self.run(values.as_mut_slice(), x, y)
+    }
+
+    /// Runs the program at the point (x, y), keeping the value of each
+    /// instruction in `values`, which must have room for all of them.
+    fn run(&self, values: &mut [f32], x: f32, y: f32) -> Result<f32, &str> {
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let value = match *instruction {
+                CallModule(Register(a), Register(b), callee) => {
+                    let OpaqueModule(ref module) = self.modules[callee];
+                    try!(module.generate_2d(values[a], values[b]))
+                },
+                _ => compute(instruction, x, y, values.slice_to(index))
+            };
+            values[index] = value;
+        }
+
+        let Register(output) = self.output;
+        Ok(values[output])
+    }
+
+    /// Returns an equivalent program in which instructions that depend only on
+    /// constants are replaced by their values, repeated instructions are
+    /// computed once, chains of scales and biases are fused, and instructions
+    /// that do not contribute to the output are removed.
+    ///
+    /// The output of the optimized program is identical, bit for bit, to that
+    /// of the original. For this reason arithmetic is never reassociated: a
+    /// scalebias of a scalebias, or a translation of a translation, becomes a
+    /// single ScaleBias instruction that still performs both steps, since
+    /// merging their constants would round differently. Steps that leave
+    /// their input unchanged, such as a scale of one, are removed.
+    pub fn optimize(&self) -> Program {
+        let mut optimizer = Optimizer {
+            program: self.empty(), values: Vec::new(),
+            constant: Vec::new(), known: HashMap::new()
+        };
+        let mut registers = Vec::with_capacity(self.instructions.len());
+        for instruction in self.instructions.iter() {
+            let instruction = rename(instruction, registers.as_slice());
+            registers.push(optimizer.add(instruction));
+        }
+
+        let Register(output) = self.output;
+        optimizer.program.output = registers[output];
+        optimizer.program.prune().fuse()
+    }
+
+    /// An empty program calling the same modules as this one.
+    fn empty(&self) -> Program {
+        let mut program = Program::new();
+        program.modules = self.modules.clone();
+        program.calls = self.calls;
+        program
+    }
+
+    /// Replaces each product or sum of a value and a constant by a ScaleBias
+    /// step, added to the steps computing the value if the value is itself a
+    /// ScaleBias that nothing else reads.
+    fn fuse(&self) -> Program {
+        let mut uses = Vec::from_elem(self.instructions.len(), 0u);
+        let Register(output) = self.output;
+        *uses.get_mut(output) += 1;
+        for instruction in self.instructions.iter() {
+            for &Register(operand) in operands(instruction).iter() {
+                *uses.get_mut(operand) += 1;
+            }
+        }
+
+        // Instructions keep their registers, so that those no longer read
+        // are simply pruned afterwards.
+        let mut program = self.empty();
+        for instruction in self.instructions.iter() {
+            let fused = match step(instruction, self.instructions.as_slice()) {
+                Some((Register(source), next)) => {
+                    match program.instructions[source] {
+                        ScaleBias(first, ref steps) if uses[source] == 1 => {
+                            ScaleBias(first, join(steps.clone(), next))
+                        },
+                        _ => ScaleBias(Register(source), vec![next])
+                    }
+                },
+                None => instruction.clone()
+            };
+            program.push(fused);
+        }
+        program.output = self.output;
+        program.prune()
+    }
+
+    /// Removes the instructions that the output does not depend on.
+    fn prune(&self) -> Program {
+        let Register(output) = self.output;
+        let mut live = Vec::from_elem(self.instructions.len(), false);
+        *live.get_mut(output) = true;
+        for (index, instruction) in self.instructions.iter().enumerate().rev() {
+            if live[index] {
+                for &Register(operand) in operands(instruction).iter() {
+                    *live.get_mut(operand) = true;
+                }
+            }
+        }
+
+        let mut program = self.empty();
+        let mut registers = Vec::with_capacity(self.instructions.len());
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            registers.push(if live[index] {
+                program.push(rename(instruction, registers.as_slice()))
+            } else {
+                // Never referred to by a live instruction.
+                Register(0)
+            });
+        }
+        program.output = registers[output];
+        program
+    }
+
+    /// Writes the program as a GLSL function `float name(vec2 p)`, preceded
+    /// by the noise functions it uses. Requires GLSL 3.30 or GLSL ES 3.00.
+    ///
+    /// A call to the module with index `n` is written as a call to
+    /// `float grunge_module_n(vec2 p)`, which the caller must define.
+    pub fn to_glsl(&self, name: &str) -> String {
+        self.emit(name, Glsl)
+    }
+
+    /// Writes the program as a WGSL function `fn name(p: vec2<f32>) -> f32`,
+    /// preceded by the noise functions it uses.
+    ///
+    /// A call to the module with index `n` is written as a call to
+    /// `fn grunge_module_n(p: vec2<f32>) -> f32`, which the caller must
+    /// define.
+    pub fn to_wgsl(&self, name: &str) -> String {
+        self.emit(name, Wgsl)
+    }
+
+    fn emit(&self, name: &str, language: Language) -> String {
+        let mut out = String::new();
+
+        let noise = self.instructions.iter().any(|instruction| {
+            match *instruction {
+                Simplex(..) | FlowSimplex(..) => true,
+                _ => false
+            }
+        });
+        if noise {
+            out.push_str(match language {
+                Glsl => GLSL_NOISE,
+                Wgsl => WGSL_NOISE
+            });
+            out.push_str("\n");
+        }
+
+        out.push_str(match language {
+            Glsl => format!("float {}(vec2 p) {{\n", name),
+            Wgsl => format!("fn {}(p: vec2<f32>) -> f32 {{\n", name)
+        }.as_slice());
+
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let vec2 = match language { Glsl => "vec2", Wgsl => "vec2<f32>" };
+            let value = match *instruction {
+                InputX => "p.x".to_string(),
+                InputY => "p.y".to_string(),
+                Constant(value) => float(value, language),
+                Plus(a, b) => format!("{} + {}", a, b),
+                Minus(a, b) => format!("{} - {}", a, b),
+                Times(a, b) => format!("{} * {}", a, b),
+                Divide(a, b) => format!("{} / {}", a, b),
+                Min(a, b) => format!("min({}, {})", a, b),
+                Max(a, b) => format!("max({}, {})", a, b),
+                Abs(a) => format!("abs({})", a),
+                Simplex(a, b, seed) => {
+                    format!("grunge_snoise_2d({}({}, {}), {})", vec2, a, b,
+                            float(seed as f32, language))
+                },
+                FlowSimplex(a, b, seed, angle) => {
+                    format!("grunge_sflow_2d({}({}, {}), {}, {})", vec2, a, b,
+                            float(seed as f32, language),
+                            float(angle, language))
+                },
+                ScaleBias(a, ref steps) => {
+                    let mut text = format!("{}", a);
+                    let mut sum = false;
+                    for &(scale, bias) in steps.iter() {
+                        if bits(scale) != bits(1.0) {
+                            let scale = float(scale, language);
+                            text = if sum {
+                                format!("({}) * {}", text, scale)
+                            } else {
+                                format!("{} * {}", text, scale)
+                            };
+                        }
+                        if bits(bias) != bits(-0.0) {
+                            let bias = float(bias, language);
+                            text = format!("{} + {}", text, bias);
+                            sum = true;
+                        }
+                    }
+                    text
+                },
+                CallModule(a, b, module) => {
+                    format!("grunge_module_{}({}({}, {}))", module, vec2, a, b)
+                }
+            };
+            out.push_str(match language {
+                Glsl => format!("    float {} = {};\n", Register(index), value),
+                Wgsl => format!("    let {} = {};\n", Register(index), value)
+            }.as_slice());
+        }
+
+        out.push_str(format!("    return {};\n}}\n", self.output).as_slice());
+        out
+    }
+}
+
+/// A noise module evaluated by running its optimized Program, instead of
+/// through the chain of boxed modules it was built from.
+///
+/// Deep chains of modifiers make a virtual call and follow a pointer for each
+/// module at every sample; a compiled module instead runs a flat list of
+/// instructions, in which constant steps have been folded and shared sources
+/// are only computed once. Parts of the source that cannot be lowered are
+/// called as they are. Its two-dimensional output is identical to that of
+/// the source, so it can stand in for the source anywhere. Three-dimensional
+/// output, bounds and descriptions are those of the source itself.
+///
+/// ## Example
+///
+/// ```rust
+/// use grunge::modules::{NoiseModule, Modifiable, PinkNoise};
+///
+/// let noise = PinkNoise::new(3).scalebias(0.5, 0.5).clamp(0.0, 1.0);
+/// let compiled = noise.compile();
+/// assert_eq!(compiled.generate_2d(0.3, 0.7), noise.generate_2d(0.3, 0.7));
+/// ```
+pub struct CompiledNoise {
+    /// The module that was compiled.
+    pub source: Box<NoiseModule>,
+
+    /// The optimized program computing the output of the source.
+    pub program: Program,
+}
+
+/// The largest program whose values are kept on the stack while it runs.
+static STACK_VALUES: uint = 256;
+
+impl CompiledNoise {
+    /// Compiles the given source module.
+    pub fn new(source: &NoiseModule) -> CompiledNoise {
+        CompiledNoise::from_box(source.to_box())
+    }
+
+    /// Compiles the given source module like `new()`, taking ownership of it.
+    pub fn from_box(source: Box<NoiseModule>) -> CompiledNoise {
+        let program = Program::compile_with_calls(&*source).optimize();
+        CompiledNoise { source: source, program: program }
+    }
+}
+
+impl Clone for CompiledNoise {
+    fn clone(&self) -> CompiledNoise {
+        CompiledNoise {
+            source: self.source.to_box(), program: self.program.clone()
+        }
+    }
+}
+
+impl NoiseModule for CompiledNoise {
+    fn generate_2d(&self, x: f32, y: f32) -> Result<f32, &str> {
+        let count = self.program.instructions.len();
+        if count <= STACK_VALUES {
+            let mut values = [0.0f32, ..STACK_VALUES];
+            self.program.run(values.slice_to_mut(count), x, y)
+        } else {
+            let mut values = Vec::from_elem(count, 0.0f32);
+            self.program.run(values.as_mut_slice(), x, y)
+        }
+    }
+
+    fn generate_3d(&self, x: f32, y: f32, z: f32) -> Result<f32, &str> {
+        self.source.generate_3d(x, y, z)
+    }
+
+    fn bounds(&self) -> (f32, f32) {
+        self.source.bounds()
+    }
+
+    fn to_shader(&self, program: &mut Program, x: Register, y: Register)
+        -> Result<Register, &'static str> {
+        program.lower(&*self.source, x, y)
+    }
+
+    /// The module is described as its source, and so is loaded uncompiled.
+    fn to_json(&self) -> Result<Json, &'static str> {
+        self.source.to_json()
+    }
+}
+
+impl Modifiable for CompiledNoise {}
+
+/// Computes the value of an instruction at the point (x, y), given the values
+/// of the instructions before it.
+fn compute(instruction: &Instruction, x: f32, y: f32, values: &[f32]) -> f32 {
+    match *instruction {
+        InputX => x,
+        InputY => y,
+        Constant(value) => value,
+        Plus(Register(a), Register(b)) => values[a] + values[b],
+        Minus(Register(a), Register(b)) => values[a] - values[b],
+        Times(Register(a), Register(b)) => values[a] * values[b],
+        Divide(Register(a), Register(b)) => values[a] / values[b],
+        Min(Register(a), Register(b)) => values[a].min(values[b]),
+        Max(Register(a), Register(b)) => values[a].max(values[b]),
+        Abs(Register(a)) => values[a].abs(),
+        Simplex(Register(a), Register(b), seed) => {
+            snoise_2d(values[a], values[b], seed)
+        },
+        FlowSimplex(Register(a), Register(b), seed, angle) => {
+            sflow_2d(values[a], values[b], seed, angle)
+        },
+        ScaleBias(Register(a), ref steps) => {
+            steps.iter().fold(values[a], |value, &(scale, bias)| {
+                value * scale + bias
+            })
+        },
+        // Calls are made by `Program::run()`, and never folded.
+        CallModule(..) => unreachable!()
+    }
+}
+
+/// The registers an instruction reads.
+fn operands(instruction: &Instruction) -> Vec<Register> {
+    match *instruction {
+        InputX | InputY | Constant(..) => vec![],
+        Plus(a, b) | Minus(a, b) | Times(a, b) | Divide(a, b) | Min(a, b)
+            | Max(a, b) | Simplex(a, b, _) | FlowSimplex(a, b, _, _)
+            | CallModule(a, b, _) => {
+            vec![a, b]
+        },
+        Abs(a) | ScaleBias(a, _) => vec![a]
+    }
+}
+
+/// Replaces each register `r` read by an instruction with `registers[r]`.
+fn rename(instruction: &Instruction, registers: &[Register]) -> Instruction {
+    let r = |Register(index): Register| registers[index];
+    match *instruction {
+        InputX => InputX,
+        InputY => InputY,
+        Constant(value) => Constant(value),
+        Plus(a, b) => Plus(r(a), r(b)),
+        Minus(a, b) => Minus(r(a), r(b)),
+        Times(a, b) => Times(r(a), r(b)),
+        Divide(a, b) => Divide(r(a), r(b)),
+        Min(a, b) => Min(r(a), r(b)),
+        Max(a, b) => Max(r(a), r(b)),
+        Abs(a) => Abs(r(a)),
+        Simplex(a, b, seed) => Simplex(r(a), r(b), seed),
+        FlowSimplex(a, b, seed, angle) => FlowSimplex(r(a), r(b), seed, angle),
+        ScaleBias(a, ref steps) => ScaleBias(r(a), steps.clone()),
+        CallModule(a, b, module) => CallModule(r(a), r(b), module)
+    }
+}
+
+fn bits(value: f32) -> u32 {
+    unsafe { mem::transmute(value) }
+}
+
+/// Identifies an instruction by its kind, operands, seed or module and the
+/// bits of its floating-point parameters. Comparing bits rather than values
+/// keeps `0.0` and `-0.0` apart, and lets NaN constants be merged.
+type Key = (uint, uint, uint, uint, Vec<u32>);
+
+fn key(instruction: &Instruction) -> Key {
+    let registers = operands(instruction);
+    let register = |i: uint| {
+        registers.as_slice().get(i).map_or(0, |&Register(index)| index)
+    };
+    let (kind, seed, parameters) = match *instruction {
+        InputX => (0, 0, vec![]),
+        InputY => (1, 0, vec![]),
+        Constant(value) => (2, 0, vec![bits(value)]),
+        Plus(..) => (3, 0, vec![]),
+        Minus(..) => (4, 0, vec![]),
+        Times(..) => (5, 0, vec![]),
+        Divide(..) => (6, 0, vec![]),
+        Min(..) => (7, 0, vec![]),
+        Max(..) => (8, 0, vec![]),
+        Abs(..) => (9, 0, vec![]),
+        Simplex(_, _, seed) => (10, seed, vec![]),
+        FlowSimplex(_, _, seed, angle) => (11, seed, vec![bits(angle)]),
+        ScaleBias(_, ref steps) => {
+            let mut parameters = Vec::with_capacity(2 * steps.len());
+            for &(scale, bias) in steps.iter() {
+                parameters.push(bits(scale));
+                parameters.push(bits(bias));
+            }
+            (12, 0, parameters)
+        },
+        CallModule(_, _, module) => (13, module, vec![])
+    };
+    (kind, register(0), register(1), seed, parameters)
+}
+
+/// The register and ScaleBias step computing an instruction, if it is the
+/// product or sum of a value and a constant.
+fn step(instruction: &Instruction, instructions: &[Instruction])
+    -> Option<(Register, (f32, f32))> {
+    let constant = |Register(index): Register| {
+        match instructions[index] {
+            Constant(value) => Some(value),
+            _ => None
+        }
+    };
+    match *instruction {
+        Times(a, b) => match (constant(a), constant(b)) {
+            (None, Some(scale)) => Some((a, (scale, -0.0))),
+            (Some(scale), None) => Some((b, (scale, -0.0))),
+            _ => None
+        },
+        Plus(a, b) => match (constant(a), constant(b)) {
+            (None, Some(bias)) => Some((a, (1.0, bias))),
+            (Some(bias), None) => Some((b, (1.0, bias))),
+            _ => None
+        },
+        // Adding the negation of a value is exactly the same as subtracting.
+        Minus(a, b) => match (constant(a), constant(b)) {
+            (None, Some(bias)) => Some((a, (1.0, -bias))),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// Appends a step to a chain of ScaleBias steps. A bias following a step that
+/// only scales is merged into that step, which computes the same.
+fn join(mut steps: Vec<(f32, f32)>, (scale, bias): (f32, f32))
+    -> Vec<(f32, f32)> {
+    let last = steps.len() - 1;
+    let (last_scale, last_bias) = steps[last];
+    if bits(last_bias) == bits(-0.0) && bits(scale) == bits(1.0) {
+        *steps.get_mut(last) = (last_scale, bias);
+    } else {
+        steps.push((scale, bias));
+    }
+    steps
+}
+
+/// Builds an optimized program one instruction at a time.
+struct Optimizer {
+    program: Program,
+
+    /// The value of each register that holds a constant.
+    values: Vec<f32>,
+
+    /// Whether each register holds a constant.
+    constant: Vec<bool>,
+
+    /// The register computing each distinct instruction so far.
+    known: HashMap<Key, Register>,
+}
+
+impl Optimizer {
+    /// Adds an instruction, whose operands refer to the optimized program, and
+    /// returns the register holding an identical value.
+    fn add(&mut self, instruction: Instruction) -> Register {
+        let instruction = self.fold(instruction);
+        match self.identity(&instruction) {
+            Some(register) => return register,
+            None => ()
+        }
+
+        let key = key(&instruction);
+        match self.known.find(&key) {
+            Some(register) => return *register,
+            None => ()
+        }
+
+        let (value, constant) = match instruction {
+            Constant(value) => (value, true),
+            _ => (0.0, false)
+        };
+        let register = self.program.push(instruction);
+        self.values.push(value);
+        self.constant.push(constant);
+        self.known.insert(key, register);
+        register
+    }
+
+    /// Replaces an instruction whose operands are all constants by its value.
+    /// Calls to modules are kept, since they may fail.
+    fn fold(&self, instruction: Instruction) -> Instruction {
+        let registers = operands(&instruction);
+        let call = match instruction { CallModule(..) => true, _ => false };
+        if call || registers.is_empty() ||
+           !registers.iter().all(|&Register(index)| self.constant[index]) {
+            return instruction;
+        }
+        Constant(compute(&instruction, 0.0, 0.0, self.values.as_slice()))
+    }
+
+    /// The operand of an instruction that always computes that operand
+    /// exactly, such as `a * 1.0` or `a - 0.0`, if there is one.
+    fn identity(&self, instruction: &Instruction) -> Option<Register> {
+        let is = |Register(index): Register, value: f32| {
+            self.constant[index] && bits(self.values[index]) == bits(value)
+        };
+        match *instruction {
+            Times(a, b) if is(b, 1.0) => Some(a),
+            Times(a, b) if is(a, 1.0) => Some(b),
+            Divide(a, b) if is(b, 1.0) => Some(a),
+            // Only negative zero leaves a negative zero unchanged when added.
+            Plus(a, b) if is(b, -0.0) => Some(a),
+            Plus(a, b) if is(a, -0.0) => Some(b),
+            Minus(a, b) if is(b, 0.0) => Some(a),
+            Min(a, b) | Max(a, b) if a == b => Some(a),
+            _ => None
+        }
+    }
+}
+
+/// The shading languages that programs can be written in.
+enum Language {
+    Glsl,
+    Wgsl,
+}
+
+/// Writes a floating-point literal that reads back as exactly `value`.
+/// Infinities and NaN, which have no literal, are written by their bits.
+fn float(value: f32, language: Language) -> String {
+    if value.is_finite() {
+        return format!("{:.8e}", value);
+    }
+
+    let bits: u32 = unsafe { mem::transmute(value) };
+    match language {
+        Glsl => format!("uintBitsToFloat({}u)", bits),
+        Wgsl => format!("bitcast<f32>({}u)", bits)
+    }
+}
+
+/// GLSL ports of `snoise_2d` and `sflow_2d`. With an angle of zero, the
+/// rotation of the gradients in the latter leaves them unchanged. Like the
+/// primitives, they reduce the cell coordinates modulo 289 before hashing.
+static GLSL_NOISE: &'static str = "\
+vec3 grunge_permute(vec3 x) {
+    return mod((x * 34.0 + 1.0) * x, 289.0);
+}
+
+float grunge_sflow_2d(vec2 v, float seed, float angle) {
+    const float HAIRY = 0.366025403784439;
+    const float SKEW = 0.211324865405187;
+
+    vec2 i0 = floor(v + (v.x + v.y) * HAIRY);
+    vec2 x0 = v - i0 + SKEW * (i0.x + i0.y);
+    vec2 i1 = x0.x > x0.y ? vec2(1.0, 0.0) : vec2(0.0, 1.0);
+    vec2 x1 = x0 - i1 + SKEW;
+    vec2 x2 = x0 + (-1.0 + 2.0 * SKEW);
+
+    vec3 m = max(0.5 - vec3(dot(x0, x0), dot(x1, x1), dot(x2, x2)), 0.0);
+    m = m * m * m;
+
+    vec2 i = mod(i0, 289.0);
+    vec3 p = grunge_permute(grunge_permute(grunge_permute(
+        vec3(i.y, i.y + i1.y, i.y + 1.0)) +
+        vec3(i.x, i.x + i1.x, i.x + 1.0)) + seed);
+    vec3 h1 = 2.0 * fract(p * 0.024390243902439) - 1.0;
+    vec3 h2 = abs(h1) - 0.5;
+    vec3 h4 = h1 - floor(h1 + 0.5);
+    m = m * (1.79284291400159 - 0.85373472095314 * (h4 * h4 + h2 * h2));
+
+    float s = sin(angle);
+    float c = cos(angle);
+    vec3 gx = h4 * c - h2 * s;
+    vec3 gy = h4 * s + h2 * c;
+    vec3 g = gx * vec3(x0.x, x1.x, x2.x) + gy * vec3(x0.y, x1.y, x2.y);
+    return 130.0 * dot(m, g);
+}
+
+float grunge_snoise_2d(vec2 v, float seed) {
+    return grunge_sflow_2d(v, seed, 0.0);
+}
+";
+
+/// WGSL ports of `snoise_2d` and `sflow_2d`.
+static WGSL_NOISE: &'static str = "\
+fn grunge_permute(x: vec3<f32>) -> vec3<f32> {
+    let y = (x * 34.0 + 1.0) * x;
+    return y - 289.0 * floor(y / 289.0);
+}
+
+fn grunge_sflow_2d(v: vec2<f32>, seed: f32, angle: f32) -> f32 {
+    let hairy = 0.366025403784439;
+    let skew = 0.211324865405187;
+
+    let i0 = floor(v + (v.x + v.y) * hairy);
+    let x0 = v - i0 + skew * (i0.x + i0.y);
+    let i1 = select(vec2<f32>(0.0, 1.0), vec2<f32>(1.0, 0.0), x0.x > x0.y);
+    let x1 = x0 - i1 + skew;
+    let x2 = x0 + (-1.0 + 2.0 * skew);
+
+    var m = max(0.5 - vec3<f32>(dot(x0, x0), dot(x1, x1), dot(x2, x2)),
+                vec3<f32>(0.0));
+    m = m * m * m;
+
+    let i = i0 - 289.0 * floor(i0 / 289.0);
+    let p = grunge_permute(grunge_permute(grunge_permute(
+        vec3<f32>(i.y, i.y + i1.y, i.y + 1.0)) +
+        vec3<f32>(i.x, i.x + i1.x, i.x + 1.0)) + seed);
+    let h1 = 2.0 * fract(p * 0.024390243902439) - 1.0;
+    let h2 = abs(h1) - 0.5;
+    let h4 = h1 - floor(h1 + 0.5);
+    m = m * (1.79284291400159 - 0.85373472095314 * (h4 * h4 + h2 * h2));
+
+    let s = sin(angle);
+    let c = cos(angle);
+    let gx = h4 * c - h2 * s;
+    let gy = h4 * s + h2 * c;
+    let g = gx * vec3<f32>(x0.x, x1.x, x2.x) + gy * vec3<f32>(x0.y, x1.y, x2.y);
+    return 130.0 * dot(m, g);
+}
+
+fn grunge_snoise_2d(v: vec2<f32>, seed: f32) -> f32 {
+    return grunge_sflow_2d(v, seed, 0.0);
+}
+";
@@ -8,8 +8,13 @@ extern crate test;
 extern crate cgmath;
 extern crate grunge;
 
-use grunge::primitives::snoise_2d;
+use grunge::primitives::{snoise_2d, sflow_2d, snoise_3d, snoise_2d_grad,
+                         sflow_2d_grad, snoise_3d_grad};
 use grunge::modules::*;
+use grunge::serial;
+use grunge::expr;
+use grunge::shader;
+use grunge::vectors::{Vector2, Vector3, Matrix4};
 
 #[bench]
 fn bench_simplex_noise_2d(b: &mut test::Bencher) {
@@ -18,15 +23,587 @@ fn bench_simplex_noise_2d(b: &mut test::Bencher) {
     });
 }
 
+#[test]
+fn test_noise_gradients() {
+    // Central differences, whose error is far below the tolerance at this step.
+    let h = 1e-3;
+    let close = |analytic: f32, numeric: f32| {
+        (analytic - numeric).abs() < 0.01 * (1.0 + analytic.abs())
+    };
+    for i in range(-20i, 20) {
+        let (x, y, z) = (i as f32 * 0.53, i as f32 * -0.29, i as f32 * 0.17);
+
+        let (_, grad) = snoise_2d_grad(x, y, 3);
+        let dx = (snoise_2d(x + h, y, 3) - snoise_2d(x - h, y, 3)) / (2.0 * h);
+        let dy = (snoise_2d(x, y + h, 3) - snoise_2d(x, y - h, 3)) / (2.0 * h);
+        assert!(close(grad.x, dx) && close(grad.y, dy));
+
+        let (value, grad) = sflow_2d_grad(x, y, 3, 1.2);
+        let n = |x, y| sflow_2d(x, y, 3, 1.2);
+        let dx = (n(x + h, y) - n(x - h, y)) / (2.0 * h);
+        let dy = (n(x, y + h) - n(x, y - h)) / (2.0 * h);
+        assert!((value - n(x, y)).abs() < 1e-5);
+        assert!(close(grad.x, dx) && close(grad.y, dy));
+
+        let (_, grad) = snoise_3d_grad(x, y, z, 3);
+        let n = |x, y, z| snoise_3d(x, y, z, 3);
+        let dx = (n(x + h, y, z) - n(x - h, y, z)) / (2.0 * h);
+        let dy = (n(x, y + h, z) - n(x, y - h, z)) / (2.0 * h);
+        let dz = (n(x, y, z + h) - n(x, y, z - h)) / (2.0 * h);
+        assert!(close(grad.x, dx) && close(grad.y, dy) && close(grad.z, dz));
+    }
+}
+
+#[test]
+fn test_eroded_noise() {
+    // Each octave of PinkNoise, divided by one plus the squared slope summed
+    // over the octaves so far. Where there is no slope, the two coincide.
+    let (eroded, pink) = (ErodedNoise::new(4), PinkNoise::new(4));
+    let mut damped_points = 0u;
+    for i in range(-20i, 20) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        let (mut expected, mut amplitude, mut scale) = (0.0f32, 1.0f32, 1.0f32);
+        let (mut dx, mut dy) = (0.0f32, 0.0f32);
+        for octave in range(0u, 6) {
+            let (n, grad) = snoise_2d_grad(x * scale, y * scale, 4 + octave);
+            dx += 0.3 * grad.x;
+            dy += 0.3 * grad.y;
+            expected += amplitude * n / (1.0 + dx * dx + dy * dy);
+            amplitude *= 0.5;
+            scale *= 2.0;
+        }
+
+        let value = eroded.generate_2d(x, y).unwrap();
+        let undamped = pink.generate_2d(x, y).unwrap();
+        assert!((value - expected * 0.25).abs() < 1e-5);
+        if (value - undamped).abs() > 1e-3 {
+            damped_points += 1;
+        }
+    }
+    assert!(damped_points > 0);
+}
+
+#[test]
+fn test_flow_noise() {
+    for i in range(-20i, 20) {
+        let (x, y) = (i as f32 * 0.53, i as f32 * 0.29);
+        assert!((sflow_2d(x, y, 3, 0.0) - snoise_2d(x, y, 3)).abs() < 1e-5);
+    }
+
+    let mut pink = PinkNoise::new(0);
+    pink.flow = Some(Flow::new(2.5));
+    assert!(pink.generate_2d(0.3, 0.7).is_ok());
+    assert!(pink.generate_3d(0.3, 0.7, 0.1).is_err());
+
+    // At time zero the gradients are not rotated, so the output is unchanged.
+    let mut swiss = SwissTurbulenceNoise::new(0);
+    let mut jordan = JordanTurbulenceNoise::new(0);
+    let mut eroded = ErodedNoise::new(0);
+    let still = [swiss.generate_2d(0.3, 0.7).unwrap(),
+                 jordan.generate_2d(0.3, 0.7).unwrap(),
+                 eroded.generate_2d(0.3, 0.7).unwrap()];
+    swiss.flow = Some(Flow::new(0.0));
+    jordan.flow = Some(Flow::new(0.0));
+    eroded.flow = Some(Flow::new(0.0));
+    let flowing = [swiss.generate_2d(0.3, 0.7).unwrap(),
+                   jordan.generate_2d(0.3, 0.7).unwrap(),
+                   eroded.generate_2d(0.3, 0.7).unwrap()];
+    for (a, b) in still.iter().zip(flowing.iter()) {
+        assert!((*a - *b).abs() < 1e-4);
+    }
+}
+
 #[test]
 fn test_octave_requirements() {
     let mut pink = PinkNoise::new(0);
-    pink.octaves = 1;
+    pink.octaves = 1.0;
     let mut billow = BillowNoise::new(0);
-    billow.octaves = 31;
+    billow.octaves = 31.0;
 
     assert!(pink.generate_2d(0.05, 0.05).is_err());
     assert!(billow.generate_2d(0.05, 0.05).is_err());
+
+    // The distortion modules of turbulence are PinkNoise as well.
+    let cylinder = CylinderNoise::new(4.0);
+    assert!(cylinder.turbulence(2.0, 0.0625, 1).is_err());
+    assert!(TurbulenceNoise::new(&cylinder, 5, 2.0, 0.0625, 0).is_err());
+    assert!(TurbulenceNoise::new(&cylinder, 5, 2.0, 0.0625, 2).is_ok());
+}
+
+#[test]
+fn test_fractional_octaves() {
+    // Only the weight of the fourth octave changes between three and four
+    // octaves, so the output moves linearly, and continuously, between them.
+    let octaves = |octaves: f32, x: f32, y: f32| {
+        PinkNoise { octaves: octaves, .. PinkNoise::new(2) }
+            .generate_2d(x, y).unwrap()
+    };
+    for i in range(-10i, 10) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        let (three, four) = (octaves(3.0, x, y), octaves(4.0, x, y));
+        for step in range(0u, 21) {
+            let t = step as f32 / 20.0;
+            let expected = three + t * (four - three);
+            assert!((octaves(3.0 + t, x, y) - expected).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn test_octave_domains() {
+    let pink = PinkNoise::new(1);
+    let rotated = PinkNoise { rotate_octaves: true, .. pink.clone() };
+    let offset = PinkNoise { offset_octaves: true, .. pink.clone() };
+    let ridged = RidgedMultifractalNoise::new(1);
+    let ridged_rotated = RidgedMultifractalNoise {
+        rotate_octaves: true, .. ridged.clone()
+    };
+
+    // Without persistence only the first octave contributes.
+    let first = PinkNoise { persistence: 0.0, .. pink.clone() };
+    let first_rotated = PinkNoise { rotate_octaves: true, .. first.clone() };
+
+    let build = |seed: uint| {
+        PinkNoise {
+            rotate_octaves: true, offset_octaves: true, .. PinkNoise::new(seed)
+        }
+    };
+
+    // The origin is left in place by every rotation, so it is avoided.
+    for i in range(-10i, 10) {
+        let (x, y, z) = (i as f32 * 0.37 + 0.1, i as f32 * -0.21 + 0.2,
+                         i as f32 * 0.13 + 0.3);
+        let value = pink.generate_2d(x, y);
+        assert!(rotated.generate_2d(x, y) != value);
+        assert!(offset.generate_2d(x, y) != value);
+        assert!(first_rotated.generate_2d(x, y) != first.generate_2d(x, y));
+        assert!(ridged_rotated.generate_3d(x, y, z) !=
+                ridged.generate_3d(x, y, z));
+
+        // The rotations and offsets depend only on the seed and octave.
+        assert_eq!(build(1).generate_2d(x, y), build(1).generate_2d(x, y));
+        assert_eq!(build(1).generate_3d(x, y, z),
+                   build(1).generate_3d(x, y, z));
+        assert!(build(2).generate_2d(x, y) != build(1).generate_2d(x, y));
+    }
+}
+
+#[test]
+fn test_max_frequency() {
+    // With a lacunarity of two, octaves above 4.0 and 8.0 are dropped by these
+    // limits, leaving two and three octaves, respectively.
+    let pink = PinkNoise::new(5);
+    for &(max_frequency, octaves) in [(4.0f32, 2.0f32), (8.0, 3.0)].iter() {
+        let mut culled = pink.clone();
+        culled.max_frequency = max_frequency;
+        let fewer = PinkNoise { octaves: octaves, .. pink.clone() };
+        for i in range(-10i, 10) {
+            let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+            let value = culled.generate_2d(x, y).unwrap();
+            assert!((value - fewer.generate_2d(x, y).unwrap()).abs() < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn test_curve_requirements() {
+    let source = PinkNoise::new(0);
+
+    assert!(source.curve(&[(0.0, 1.0)]).is_err());
+    assert!(source.curve(&[(0.0, 1.0), (0.0, 0.0)]).is_err());
+    assert!(CurveNoise::new(&source, &[(0.0, 0.0), (Float::nan(), 1.0)],
+                            CatmullRom).is_err());
+    assert!(source.curve(&[(1.0, 1.0), (-1.0, -1.0)]).is_ok());
+}
+
+#[test]
+fn test_curve() {
+    // Rising data with a steep step, which a Catmull-Rom spline overshoots.
+    let points = [(-1.0, -1.0), (-0.5, -0.9), (0.0, -0.8), (0.1, 0.8),
+                  (0.5, 0.9), (1.0, 1.0)];
+    let curve = |value: f32, interpolation: Interpolation| {
+        CurveNoise::new(&ConstNoise::new(value), &points, interpolation)
+            .unwrap().generate_2d(0.3, 0.7).unwrap()
+    };
+
+    for &interpolation in [CatmullRom, MonotoneCubic].iter() {
+        for &(input, output) in points.iter() {
+            assert_eq!(curve(input, interpolation), output);
+        }
+        assert_eq!(curve(-1.5, interpolation), -1.0);
+        assert_eq!(curve(Float::neg_infinity(), interpolation), -1.0);
+        assert_eq!(curve(2.0, interpolation), 1.0);
+    }
+
+    let (mut last, mut overshoots) = (-1.0f32, false);
+    for i in range(-256i, 257) {
+        let value = curve(i as f32 / 256.0, MonotoneCubic);
+        assert!(value >= last && value <= 1.0);
+        last = value;
+        if curve(i as f32 / 256.0, CatmullRom) < -1.0 {
+            overshoots = true;
+        }
+    }
+    assert!(overshoots);
+}
+
+#[test]
+fn test_bounds() {
+    let mut pink = PinkNoise::new(0);
+    pink.persistence = 0.9;
+    pink.octaves = 12.0;
+    pink.normalize = true;
+    let mut ridged = RidgedMultifractalNoise::new(0);
+    ridged.normalize = true;
+
+    assert_eq!(pink.bounds(), (-1.0, 1.0));
+    for i in range(-50i, 50) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        let value = pink.generate_2d(x, y).unwrap();
+        assert!(value >= -1.0 && value <= 1.0);
+        let value = ridged.generate_2d(x, y).unwrap();
+        assert!(value >= -1.0 && value <= 1.0);
+    }
+
+    let modified = pink.scalebias(-2.0, 1.0).clamp(0.0, 5.0);
+    assert_eq!(modified.bounds(), (0.0, 3.0));
+
+    // With negative persistence every other octave is subtracted, so the sum
+    // of the billows may be negative.
+    let mut billow = BillowNoise::new(3);
+    billow.persistence = -0.75;
+    let (lower, _) = billow.bounds();
+    assert!(lower < -1.0);
+    sampled_mean(&billow);
+    billow.normalize = true;
+    assert_eq!(billow.bounds(), (-1.0, 1.0));
+    sampled_mean(&billow);
+}
+
+#[test]
+fn test_select() {
+    let (outside, inside) = (ConstNoise::new(-1.0), ConstNoise::new(1.0));
+    let select = |control: f32, edge_falloff: f32| {
+        let mut select = SelectNoise::new(&outside, &inside,
+                                          &ConstNoise::new(control), 0.0, 1.0);
+        select.edge_falloff = edge_falloff;
+        select.generate_2d(0.3, 0.7).unwrap()
+    };
+
+    assert_eq!(select(-0.5, 0.0), -1.0);
+    assert_eq!(select(1.5, 0.0), -1.0);
+    assert_eq!(select(0.5, 0.0), 1.0);
+
+    // Across each band the output moves steadily from one source to the
+    // other, and is halfway between them at the bound itself.
+    assert_eq!(select(-0.125, 0.125), -1.0);
+    assert_eq!(select(0.125, 0.125), 1.0);
+    assert!(select(0.0, 0.125).abs() < 1e-6);
+    assert!(select(1.0, 0.125).abs() < 1e-6);
+    let mut last = -1.0;
+    for i in range(-16i, 17) {
+        let value = select(i as f32 / 128.0, 0.125);
+        assert!(value >= last && value <= 1.0);
+        last = value;
+    }
+
+    // A falloff wider than half the range is narrowed to it, so the bands
+    // meet in the middle.
+    assert_eq!(select(0.5, 2.0), 1.0);
+    for i in range(-8i, 17) {
+        let control = i as f32 / 8.0;
+        assert_eq!(select(control, 2.0), select(control, 0.5));
+    }
+}
+
+#[test]
+fn test_rotate_3d() {
+    let pink = PinkNoise::new(0);
+    let rotated = Rotated3Noise::from_euler(&pink, 0.0, 0.0, Float::frac_pi_2());
+
+    // A quarter turn about the z axis takes the x axis onto the y axis.
+    let expected = pink.generate_3d(0.0, 1.0, 0.5).unwrap();
+    let actual = rotated.generate_3d(1.0, 0.0, 0.5).unwrap();
+    assert!((actual - expected).abs() < 1e-4);
+}
+
+#[test]
+fn test_affine_noise() {
+    let pink = PinkNoise::new(0);
+    let (scale, rotation) = (Vector3::new(8.0, 0.5, 2.0), 0.3);
+    let translation = Vector2::new(10.0, -4.0);
+
+    let nested = pink.scale_input(scale).rotate(rotation)
+                     .translate(translation);
+    let collapsed = pink.affine(Matrix4::identity()).scale_input(scale)
+                        .rotate(rotation).translate(translation);
+    let boxed = ScaledInputNoise::new(&pink, scale);
+
+    for i in range(-10i, 10) {
+        let (x, y, z) = (i as f32 * 0.31, i as f32 * -0.17, i as f32 * 0.07);
+        let expected = nested.generate_2d(x, y).unwrap();
+        assert!((collapsed.generate_2d(x, y).unwrap() - expected).abs() < 1e-4);
+        let expected = nested.generate_3d(x, y, z).unwrap();
+        let actual = collapsed.generate_3d(x, y, z).unwrap();
+        assert!((actual - expected).abs() < 1e-4);
+
+        let expected = pink.generate_3d(x * 8.0, y * 0.5, z * 2.0).unwrap();
+        assert_eq!(boxed.generate_3d(x, y, z).unwrap(), expected);
+        assert_eq!(boxed.generate_2d(x, y).unwrap(),
+                   pink.generate_2d(x * 8.0, y * 0.5).unwrap());
+    }
+}
+
+#[test]
+fn test_displace() {
+    let pink = PinkNoise::new(2);
+    let (dx, dy, dz) = (ConstNoise::new(0.75), ConstNoise::new(-1.25),
+                        ConstNoise::new(0.5));
+    let displaced = pink.displace(&dx, &dy);
+    let translated = pink.translate(Vector2::new(0.75, -1.25));
+    let displaced_3d = DisplaceNoise::new_3d(&pink, &dx, &dy, &dz);
+
+    // Displacements that are themselves displaced, as in domain warping.
+    let billow = BillowNoise::new(3);
+    let ridged = RidgedMultifractalNoise::new(4);
+    let warp = pink.displace(&billow, &ridged);
+    let warped = pink.displace(&warp, &warp.translate(Vector2::new(3.1, 0.4)));
+    let f = |x: f32, y: f32| pink.generate_2d(x, y).unwrap();
+    let w = |x: f32, y: f32| {
+        f(x + billow.generate_2d(x, y).unwrap(),
+          y + ridged.generate_2d(x, y).unwrap())
+    };
+
+    for i in range(-10i, 10) {
+        let (x, y, z) = (i as f32 * 0.31, i as f32 * -0.17, i as f32 * 0.07);
+        assert_eq!(displaced.generate_2d(x, y), translated.generate_2d(x, y));
+        assert_eq!(displaced.generate_3d(x, y, z),
+                   translated.generate_3d(x, y, z));
+        assert_eq!(displaced_3d.generate_3d(x, y, z),
+                   pink.generate_3d(x + 0.75, y + -1.25, z + 0.5));
+
+        let expected = f(x + w(x, y), y + w(x + 3.1, y + 0.4));
+        assert_eq!(warped.generate_2d(x, y).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_curl_divergence() {
+    let curl = CurlNoise::new(0);
+    let h = 1e-3;
+
+    for i in range(-10i, 10) {
+        let (x, y) = (i as f32 * 0.31, i as f32 * -0.17);
+        let dx = curl.generate_2d(x + h, y).unwrap().x -
+            curl.generate_2d(x - h, y).unwrap().x;
+        let dy = curl.generate_2d(x, y + h).unwrap().y -
+            curl.generate_2d(x, y - h).unwrap().y;
+        assert!(((dx + dy) / (2.0 * h)).abs() < 0.05);
+    }
+
+    // A fractional last octave is weighted, as in the fractal modules.
+    let mut two = CurlNoise::new(0);
+    two.octaves = 2.0;
+    let mut half = two.clone();
+    half.octaves = 1.5;
+    let (a, b) = (curl.generate_2d(0.3, 0.7).unwrap(),
+                  two.generate_2d(0.3, 0.7).unwrap());
+    let mid = half.generate_2d(0.3, 0.7).unwrap();
+    assert!((mid.x - (a.x + b.x) * 0.5).abs() < 1e-4);
+    assert!((mid.y - (a.y + b.y) * 0.5).abs() < 1e-4);
+
+    half.octaves = 0.5;
+    assert!(half.generate_2d(0.3, 0.7).is_err());
+}
+
+#[test]
+fn test_vector_combinators() {
+    let wind = ComposedVectorNoise::new(&ConstNoise::new(3.0),
+                                        &ConstNoise::new(-4.0));
+
+    assert_eq!(wind.magnitude().generate_2d(0.5, 0.5).unwrap(), 5.0);
+    assert_eq!(wind.component(1).generate_2d(0.5, 0.5).unwrap(), -4.0);
+    assert!(wind.component(2).generate_2d(0.5, 0.5).is_err());
+    assert!(wind.generate_3d(0.5, 0.5, 0.5).is_err());
+}
+
+#[test]
+fn test_serial_round_trip() {
+    let mut ridged = RidgedMultifractalNoise::new(7);
+    ridged.max_frequency = 40.0;
+    ridged.rotate_octaves = true;
+    let mut pink = PinkNoise::new(3);
+    pink.flow = Some(Flow::new(0.25));
+    let wind = ComposedVectorNoise::new(&BillowNoise::new(1),
+                                        &CurlNoise::new(2).component(0));
+    let curve = wind.magnitude().curve(&[(0.0, -1.0), (2.0, 1.0)]).unwrap();
+
+    let terrain = SelectNoise::new(&ridged.rotate(0.3)
+                                          .translate(Vector2::new(1.5, -2.0)),
+                                   &pink.terrace(&[-1.0, 0.0, 0.5, 1.0]),
+                                   &curve, 0.0, 1.0)
+        .scale_input(Vector3::new(2.0, 0.5, 1.0))
+        .turbulence(0.5, 0.1, 3).unwrap()
+        .clamp(-0.8, 0.8);
+
+    let text = serial::to_string(&terrain).unwrap();
+    let loaded = serial::from_str(text.as_slice()).unwrap();
+    assert_eq!(serial::to_string(&*loaded).unwrap(), text);
+
+    for i in range(-20i, 20) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        assert_eq!(terrain.generate_2d(x, y), loaded.generate_2d(x, y));
+    }
+
+    // Unsorted control points are sorted on loading, as by TerraceNoise::new.
+    let unsorted = serial::from_str(r#"{"type": "terrace",
+        "source": {"type": "pink", "seed": 3},
+        "points": [0.5, -0.5, 1.0, -1.0]}"#).unwrap();
+    let expected = PinkNoise::new(3).terrace(&[-1.0, -0.5, 0.5, 1.0]);
+    let compiled = expr::compile("terrace(pink(3), [0.5, -0.5, 1.0, -1.0])")
+        .unwrap();
+    for i in range(-20i, 20) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        assert_eq!(unsorted.generate_2d(x, y), expected.generate_2d(x, y));
+        assert_eq!(compiled.generate_2d(x, y), expected.generate_2d(x, y));
+    }
+
+    assert_eq!(serial::from_str("{\"type\": \"bogus\"}").err(),
+               Some(serial::UnknownType("bogus".to_string())));
+    assert_eq!(serial::from_str("{\"type\": \"clamp\"}").err(),
+               Some(serial::MissingField("clamp".to_string(),
+                                         "source".to_string())));
+}
+
+#[test]
+fn test_graph_loading() {
+    let graph = serial::graph_from_str(r#"{
+        "output": "terrain",
+        "nodes": {
+            "mask": { "type": "pink", "seed": 4, "frequency": 0.25 },
+            "hills": { "type": "blend", "first": { "type": "billow" },
+                       "second": "peaks", "control": "mask" },
+            "peaks": { "type": "ridged", "seed": 3 },
+            "terrain": { "type": "select", "outside": "hills",
+                         "inside": { "type": "const", "value": -1.0 },
+                         "control": "mask", "lower_bound": -1.0,
+                         "upper_bound": -0.5 }
+        }
+    }"#).unwrap();
+
+    let mask = PinkNoise { seed: 4, frequency: 0.25, .. PinkNoise::new(0) };
+    let hills = BlendNoise::new(&BillowNoise::new(0),
+                                &RidgedMultifractalNoise::new(3), &mask);
+    let terrain = SelectNoise::new(&hills, &ConstNoise::new(-1.0), &mask,
+                                   -1.0, -0.5);
+    assert_eq!(graph.generate_2d(0.3, -0.7), terrain.generate_2d(0.3, -0.7));
+
+    // Shared nodes are counted atomically, so a loaded graph can be sampled
+    // from another task.
+    let (sender, receiver) = channel();
+    spawn(proc() {
+        sender.send(graph.generate_2d(0.3, -0.7).ok());
+    });
+    assert_eq!(receiver.recv(), terrain.generate_2d(0.3, -0.7).ok());
+
+    let cycle = serial::graph_from_str(r#"{
+        "output": "a",
+        "nodes": {
+            "a": { "type": "clamp", "source": "b" },
+            "b": { "type": "scalebias", "source": "a" }
+        }
+    }"#);
+    assert_eq!(cycle.err(), Some(serial::Cycle(vec!["a".to_string(),
+                                                    "b".to_string(),
+                                                    "a".to_string()])));
+
+    let unknown = serial::graph_from_str(r#"{
+        "output": "a",
+        "nodes": {
+            "a": { "type": "const", "value": 1.0 },
+            "b": { "type": "bogus" }
+        }
+    }"#);
+    assert_eq!(unknown.err(), Some(serial::UnknownType("bogus".to_string())));
+
+    let vector = serial::graph_from_str(r#"{
+        "output": "a",
+        "nodes": {
+            "a": { "type": "clamp", "source": "wind" },
+            "wind": { "type": "curl" }
+        }
+    }"#);
+    assert_eq!(vector.err(), Some(serial::InvalidField("clamp".to_string(),
+                                                       "source".to_string())));
+}
+
+#[test]
+fn test_graph_writing() {
+    let mask = PinkNoise { seed: 4, frequency: 0.25, .. PinkNoise::new(0) };
+    let hills = BlendNoise::new(&BillowNoise::new(0),
+                                &RidgedMultifractalNoise::new(3), &mask);
+    let terrain = SelectNoise::new(&hills, &ConstNoise::new(-1.0), &mask,
+                                   -1.0, -0.5)
+        .turbulence(0.5, 0.1, 3).unwrap();
+
+    // The mask is written once, and shared by both of its parents again when
+    // the graph is loaded.
+    let graph = serial::to_graph(&terrain).unwrap();
+    let nodes = graph.find(&"nodes".to_string()).unwrap();
+    assert_eq!(nodes.as_object().unwrap().len(), 2);
+
+    let text = serial::to_graph_string(&terrain).unwrap();
+    let loaded = serial::graph_from_str(text.as_slice()).unwrap();
+    assert_eq!(serial::to_graph_string(&*loaded).unwrap(), text);
+    for i in range(-20i, 20) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        assert_eq!(terrain.generate_2d(x, y), loaded.generate_2d(x, y));
+    }
+}
+
+#[test]
+fn test_expressions() {
+    let noise = expr::compile("clamp(scalebias(pink(seed=3, octaves=8), 0.5, \
+                               0.5), 0, 1) * cylinder(2.0)").unwrap();
+    let pink = PinkNoise { seed: 3, octaves: 8.0, .. PinkNoise::new(0) };
+    let expected = CombinedNoise::new(Product,
+                                      &pink.scalebias(0.5, 0.5).clamp(0.0, 1.0),
+                                      &CylinderNoise::new(2.0));
+    let wood = expr::compile("turbulence(cylinder(4), 2, 0.0625, 3)").unwrap();
+    let expected_wood = CylinderNoise::new(4.0).turbulence(2.0, 0.0625, 3)
+        .unwrap();
+    for i in range(-10i, 10) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        assert_eq!(noise.generate_2d(x, y), expected.generate_2d(x, y));
+        assert_eq!(wood.generate_2d(x, y), expected_wood.generate_2d(x, y));
+    }
+
+    let error = |text: &str| {
+        let err = expr::compile(text).err().unwrap();
+        (err.kind, err.span.start, err.span.end)
+    };
+    assert_eq!(error("pink(3) + checker"),
+               (expr::UnknownFunction("checker".to_string()), 10, 17));
+    assert_eq!(error("clamp(pink(3), 0, 1, 2)"),
+               (expr::TooManyArguments("clamp".to_string()), 21, 22));
+    assert_eq!(error("scalebias(pink(), 0.5"), (expr::UnexpectedEnd, 21, 21));
+    assert_eq!(error("magnitude(pink())"),
+               (expr::ExpectedVectorModule, 10, 16));
+    assert_eq!(error("turbulence(pink(), 1, 0.1, 2.5)"),
+               (expr::InvalidArgument("turbulence".to_string(),
+                                      "roughness".to_string()), 27, 30));
+    assert_eq!(error("turbulence(pink(), 1, 0.1, 1)"),
+               (expr::InvalidArgument("turbulence".to_string(),
+                                      "roughness".to_string()), 27, 28));
+    assert_eq!(error("clamp(pink(), mn=0)"),
+               (expr::UnknownArgument("clamp".to_string(),
+                                      "mn".to_string()), 14, 16));
+    assert_eq!(error("const(valu=1)"),
+               (expr::UnknownArgument("const".to_string(),
+                                      "valu".to_string()), 6, 10));
+    assert_eq!(error("swiss(flow=1)"),
+               (expr::InvalidArgument("swiss".to_string(),
+                                      "flow".to_string()), 11, 12));
 }
 
 #[test]
@@ -41,3 +618,843 @@ fn test_geom_output() {
 fn test_boxes() {
     let noise = PinkNoise::new(0u).scalebias(0.5, 0.5).clamp(0.0, 1.0);
 }
+
+#[test]
+fn test_shader() {
+    let mut pink = PinkNoise::new(1);
+    pink.rotate_octaves = true;
+    let mut billow = BillowNoise::new(2);
+    billow.flow = Some(Flow::new(0.75));
+    let mut ridged = RidgedMultifractalNoise::new(3);
+    ridged.offset_octaves = true;
+    ridged.normalize = true;
+
+    let noise = BlendNoise::new(
+        &pink.scalebias(0.5, 0.25).translate(Vector2::new(1.5, -2.0)),
+        &CombinedNoise::new(Minimum, &billow.rotate(0.7),
+                            &ridged.clamp(-0.5, 0.5)),
+        &PinkNoise::new(4).turbulence(2.0, 0.125, 3).unwrap());
+    let program = shader::Program::compile(&noise).unwrap();
+    for i in range(-20i, 20) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        let expected = noise.generate_2d(x, y).unwrap();
+        assert!((program.evaluate(x, y).unwrap() - expected).abs() < 1e-5);
+    }
+
+    assert!(program.to_glsl("terrain").as_slice()
+            .contains("float terrain(vec2 p) {"));
+    assert!(program.to_wgsl("terrain").as_slice()
+            .contains("fn terrain(p: vec2<f32>) -> f32 {"));
+    assert!(shader::Program::compile(&CheckerboardNoise).is_err());
+    assert!(shader::Program::compile(
+        &Rotated3Noise::from_euler(&pink, 0.3, 0.0, 0.0)).is_err());
+
+    let mut hetero = HeteroTerrainNoise::new(6);
+    hetero.flow = Some(Flow::new(1.5));
+    let mut hybrid = HybridMultifractalNoise::new(5);
+    hybrid.octaves = 4.5;
+    let noise = CombinedNoise::new(
+        Sum, &hybrid.affine(Matrix4::new(0.5, 0.2, 0.0, 0.0,
+                                         -0.3, 1.5, 0.0, 0.0,
+                                         0.0, 0.0, 1.0, 0.0,
+                                         2.0, -1.0, 0.0, 1.0)),
+        &hetero);
+    let program = shader::Program::compile(&noise).unwrap();
+    for i in range(-20i, 20) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        let expected = noise.generate_2d(x, y).unwrap();
+        assert!((program.evaluate(x, y).unwrap() - expected).abs() < 1e-4);
+    }
+}
+
+/// An interpreter for the subset of GLSL and WGSL that programs are written
+/// in, so that the tests check the text of the shaders rather than a port.
+mod shading {
+    use std::cmp;
+
+    #[deriving(Clone, PartialEq, Show)]
+    enum Token {
+        Number(f32),
+        Word(String),
+        Symbol(String),
+    }
+
+    fn tokenize(text: &str) -> Vec<Token> {
+        let bytes = text.as_bytes();
+        let mut tokens = Vec::new();
+        let mut i = 0u;
+
+        while i < bytes.len() {
+            let start = i;
+            let c = bytes[i] as char;
+            if c.is_whitespace() {
+                i += 1;
+                continue;
+            }
+
+            let token = if c.is_digit() {
+                while i < bytes.len() {
+                    let c = bytes[i] as char;
+                    if c == 'e' && i + 1 < bytes.len() && bytes[i + 1] == b'-' {
+                        i += 2;
+                    } else if c.is_digit() || c == '.' || c == 'e' {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                match from_str::<f32>(text.slice(start, i)) {
+                    Some(value) => Number(value),
+                    None => fail!("invalid number {}", text.slice(start, i))
+                }
+            } else if c.is_alphabetic() || c == '_' {
+                while i < bytes.len() && ((bytes[i] as char).is_alphanumeric()
+                                          || bytes[i] == b'_') {
+                    i += 1;
+                }
+                // The vector types of WGSL are read as a single word.
+                if text.slice_from(i).starts_with("<f32>") {
+                    i += 5;
+                }
+                Word(text.slice(start, i).to_string())
+            } else {
+                i += if text.slice_from(i).starts_with("->") { 2 } else { 1 };
+                Symbol(text.slice(start, i).to_string())
+            };
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    enum Expr {
+        Literal(f32),
+        Variable(String),
+        Call(String, Vec<Expr>),
+        Binary(char, Box<Expr>, Box<Expr>),
+        Negate(Box<Expr>),
+        Component(Box<Expr>, uint),
+        Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    }
+
+    enum Statement {
+        Assign(String, Expr),
+        Return(Expr),
+    }
+
+    struct Function {
+        name: String,
+        parameters: Vec<String>,
+        body: Vec<Statement>,
+    }
+
+    struct Parser {
+        tokens: Vec<Token>,
+        position: uint,
+    }
+
+    impl Parser {
+        fn next(&mut self) -> Token {
+            let token = self.tokens[self.position].clone();
+            self.position += 1;
+            token
+        }
+
+        fn at(&self, symbol: &str) -> bool {
+            match self.tokens.get(self.position) {
+                Some(&Symbol(ref text)) => text.as_slice() == symbol,
+                _ => false
+            }
+        }
+
+        fn expect(&mut self, symbol: &str) {
+            let token = self.next();
+            if token != Symbol(symbol.to_string()) {
+                fail!("expected {}, found {}", symbol, token);
+            }
+        }
+
+        fn word(&mut self) -> String {
+            match self.next() {
+                Word(word) => word,
+                token => fail!("expected a word, found {}", token)
+            }
+        }
+
+        // function := 'fn' name '(' [name ':' type (',' ...)*] ')' '->' type
+        //             '{' statement* '}'
+        //           | type name '(' [type name (',' ...)*] ')'
+        //             '{' statement* '}'
+        fn function(&mut self) -> Function {
+            let wgsl = self.word().as_slice() == "fn";
+            let name = self.word();
+            self.expect("(");
+            let mut parameters = Vec::new();
+            while !self.at(")") {
+                if wgsl {
+                    parameters.push(self.word());
+                    self.expect(":");
+                    self.word();
+                } else {
+                    self.word();
+                    parameters.push(self.word());
+                }
+                if self.at(",") {
+                    self.position += 1;
+                }
+            }
+            self.expect(")");
+            if wgsl {
+                self.expect("->");
+                self.word();
+            }
+
+            self.expect("{");
+            let mut body = Vec::new();
+            while !self.at("}") {
+                body.push(self.statement());
+            }
+            self.expect("}");
+            Function { name: name, parameters: parameters, body: body }
+        }
+
+        // statement := 'return' conditional ';'
+        //            | word* name '=' conditional ';'
+        fn statement(&mut self) -> Statement {
+            let statement = if self.tokens[self.position] ==
+                                   Word("return".to_string()) {
+                self.position += 1;
+                Return(self.conditional())
+            } else {
+                // Any qualifiers and type come before the name.
+                let mut name = self.word();
+                while !self.at("=") {
+                    name = self.word();
+                }
+                self.position += 1;
+                Assign(name, self.conditional())
+            };
+            self.expect(";");
+            statement
+        }
+
+        // conditional := comparison ['?' conditional ':' conditional]
+        fn conditional(&mut self) -> Expr {
+            let condition = self.comparison();
+            if !self.at("?") {
+                return condition;
+            }
+            self.position += 1;
+            let yes = self.conditional();
+            self.expect(":");
+            let no = self.conditional();
+            Conditional(box condition, box yes, box no)
+        }
+
+        // comparison := sum ['>' sum]
+        fn comparison(&mut self) -> Expr {
+            let left = self.sum();
+            if !self.at(">") {
+                return left;
+            }
+            self.position += 1;
+            let right = self.sum();
+            Binary('>', box left, box right)
+        }
+
+        // sum := product (('+' | '-') product)*
+        fn sum(&mut self) -> Expr {
+            let mut left = self.product();
+            loop {
+                let op = if self.at("+") {
+                    '+'
+                } else if self.at("-") {
+                    '-'
+                } else {
+                    return left;
+                };
+                self.position += 1;
+                let right = self.product();
+                left = Binary(op, box left, box right);
+            }
+        }
+
+        // product := unary (('*' | '/') unary)*
+        fn product(&mut self) -> Expr {
+            let mut left = self.unary();
+            loop {
+                let op = if self.at("*") {
+                    '*'
+                } else if self.at("/") {
+                    '/'
+                } else {
+                    return left;
+                };
+                self.position += 1;
+                let right = self.unary();
+                left = Binary(op, box left, box right);
+            }
+        }
+
+        // unary := '-' unary | primary ('.' component)*
+        fn unary(&mut self) -> Expr {
+            if self.at("-") {
+                self.position += 1;
+                return Negate(box self.unary());
+            }
+
+            let mut value = self.primary();
+            while self.at(".") {
+                self.position += 1;
+                let index = match self.word().as_slice() {
+                    "x" => 0,
+                    "y" => 1,
+                    "z" => 2,
+                    component => fail!("unknown component {}", component)
+                };
+                value = Component(box value, index);
+            }
+            value
+        }
+
+        // primary := number | '(' conditional ')'
+        //          | name ['(' [conditional (',' conditional)*] ')']
+        fn primary(&mut self) -> Expr {
+            match self.next() {
+                Number(value) => Literal(value),
+                Word(name) => {
+                    if !self.at("(") {
+                        return Variable(name);
+                    }
+                    self.position += 1;
+                    let mut arguments = Vec::new();
+                    while !self.at(")") {
+                        arguments.push(self.conditional());
+                        if self.at(",") {
+                            self.position += 1;
+                        }
+                    }
+                    self.position += 1;
+                    Call(name, arguments)
+                },
+                Symbol(symbol) => {
+                    if symbol.as_slice() != "(" {
+                        fail!("unexpected {}", symbol);
+                    }
+                    let inner = self.conditional();
+                    self.expect(")");
+                    inner
+                }
+            }
+        }
+    }
+
+    /// A scalar or vector, or the result of a comparison.
+    #[deriving(Clone, Show)]
+    enum Value {
+        Numbers(Vec<f32>),
+        Bool(bool),
+    }
+
+    fn numbers<'a>(value: &'a Value) -> &'a [f32] {
+        match *value {
+            Numbers(ref numbers) => numbers.as_slice(),
+            Bool(..) => fail!("expected a number, found a bool")
+        }
+    }
+
+    fn truth(value: &Value) -> bool {
+        match *value {
+            Bool(truth) => truth,
+            Numbers(..) => fail!("expected a bool, found a number")
+        }
+    }
+
+    fn map(value: &Value, f: |f32| -> f32) -> Value {
+        let mut result = Vec::new();
+        for &x in numbers(value).iter() {
+            result.push(f(x));
+        }
+        Numbers(result)
+    }
+
+    /// Applies `f` to each pair of components, repeating a scalar operand as
+    /// often as needed.
+    fn zip(a: &Value, b: &Value, f: |f32, f32| -> f32) -> Value {
+        let (a, b) = (numbers(a), numbers(b));
+        let mut result = Vec::new();
+        for i in range(0, cmp::max(a.len(), b.len())) {
+            let x = if a.len() == 1 { a[0] } else { a[i] };
+            let y = if b.len() == 1 { b[0] } else { b[i] };
+            result.push(f(x, y));
+        }
+        Numbers(result)
+    }
+
+    fn lookup<'a>(variables: &'a [(String, Value)], name: &str) -> &'a Value {
+        // Later assignments replace earlier ones.
+        for &(ref variable, ref value) in variables.iter().rev() {
+            if variable.as_slice() == name {
+                return value;
+            }
+        }
+        fail!("undefined variable {}", name)
+    }
+
+    pub struct Shader {
+        functions: Vec<Function>,
+    }
+
+    impl Shader {
+        pub fn parse(text: &str) -> Shader {
+            let mut parser = Parser { tokens: tokenize(text), position: 0 };
+            let mut functions = Vec::new();
+            while parser.position < parser.tokens.len() {
+                functions.push(parser.function());
+            }
+            Shader { functions: functions }
+        }
+
+        /// Calls the function `name` of a point, which must return a float.
+        pub fn sample(&self, name: &str, x: f32, y: f32) -> f32 {
+            let value = self.call(name, vec![Numbers(vec![x, y])]);
+            let result = numbers(&value);
+            assert_eq!(result.len(), 1);
+            result[0]
+        }
+
+        fn call(&self, name: &str, arguments: Vec<Value>) -> Value {
+            let mut found = None;
+            for function in self.functions.iter() {
+                if function.name.as_slice() == name {
+                    found = Some(function);
+                }
+            }
+            let function = match found {
+                Some(function) => function,
+                None => fail!("undefined function {}", name)
+            };
+
+            let mut variables = Vec::new();
+            for (parameter, argument) in function.parameters.iter()
+                                                 .zip(arguments.iter()) {
+                variables.push((parameter.clone(), argument.clone()));
+            }
+            for statement in function.body.iter() {
+                match *statement {
+                    Assign(ref variable, ref expr) => {
+                        let value = self.evaluate(expr, variables.as_slice());
+                        variables.push((variable.clone(), value));
+                    },
+                    Return(ref expr) => {
+                        return self.evaluate(expr, variables.as_slice());
+                    }
+                }
+            }
+            fail!("{} does not return", name)
+        }
+
+        fn evaluate(&self, expr: &Expr, variables: &[(String, Value)])
+            -> Value {
+            match *expr {
+                Literal(value) => Numbers(vec![value]),
+                Variable(ref name) => {
+                    lookup(variables, name.as_slice()).clone()
+                },
+                Negate(ref a) => map(&self.evaluate(&**a, variables), |x| -x),
+                Component(ref a, index) => {
+                    let value = self.evaluate(&**a, variables);
+                    Numbers(vec![numbers(&value)[index]])
+                },
+                Binary(op, ref a, ref b) => {
+                    let a = self.evaluate(&**a, variables);
+                    let b = self.evaluate(&**b, variables);
+                    match op {
+                        '+' => zip(&a, &b, |x, y| x + y),
+                        '-' => zip(&a, &b, |x, y| x - y),
+                        '*' => zip(&a, &b, |x, y| x * y),
+                        '/' => zip(&a, &b, |x, y| x / y),
+                        _ => Bool(numbers(&a)[0] > numbers(&b)[0])
+                    }
+                },
+                Conditional(ref condition, ref yes, ref no) => {
+                    if truth(&self.evaluate(&**condition, variables)) {
+                        self.evaluate(&**yes, variables)
+                    } else {
+                        self.evaluate(&**no, variables)
+                    }
+                },
+                Call(ref name, ref arguments) => {
+                    let mut values = Vec::new();
+                    for argument in arguments.iter() {
+                        values.push(self.evaluate(argument, variables));
+                    }
+                    self.builtin(name.as_slice(), values)
+                }
+            }
+        }
+
+        /// Calls a function of the language, or else one of the shader.
+        fn builtin(&self, name: &str, arguments: Vec<Value>) -> Value {
+            match (name, arguments.len()) {
+                ("floor", 1) => map(&arguments[0], |x| x.floor()),
+                ("fract", 1) => map(&arguments[0], |x| x - x.floor()),
+                ("abs", 1) => map(&arguments[0], |x| x.abs()),
+                ("sin", 1) => map(&arguments[0], |x| x.sin()),
+                ("cos", 1) => map(&arguments[0], |x| x.cos()),
+                ("min", 2) => zip(&arguments[0], &arguments[1],
+                                  |x, y| x.min(y)),
+                ("max", 2) => zip(&arguments[0], &arguments[1],
+                                  |x, y| x.max(y)),
+                // Floored, unlike Rust's `%`.
+                ("mod", 2) => zip(&arguments[0], &arguments[1],
+                                  |x, y| x - y * (x / y).floor()),
+                ("dot", 2) => {
+                    let products = zip(&arguments[0], &arguments[1],
+                                       |x, y| x * y);
+                    let sum = numbers(&products).iter()
+                        .fold(0.0f32, |sum, &x| sum + x);
+                    Numbers(vec![sum])
+                },
+                ("select", 3) => {
+                    if truth(&arguments[2]) {
+                        arguments[1].clone()
+                    } else {
+                        arguments[0].clone()
+                    }
+                },
+                _ if name.starts_with("vec") => {
+                    let mut components = Vec::new();
+                    for argument in arguments.iter() {
+                        components.push_all(numbers(argument));
+                    }
+                    if components.len() == 1 {
+                        let size = if name.starts_with("vec2") { 2 } else { 3 };
+                        Numbers(Vec::from_elem(size, components[0]))
+                    } else {
+                        Numbers(components)
+                    }
+                },
+                _ => self.call(name, arguments)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_shader_noise() {
+    // Besides a grid about the origin, points whose cells are far enough out
+    // that hashing them without reducing them first would overflow the exact
+    // integers of an f32, where the floored modulus of the shaders and the
+    // truncated one of the primitives round differently.
+    let mut points = vec![(731.3f32, 5.9f32), (-1024.7, 2047.9),
+                          (12345.6, -777.7), (-40000.2, -3001.3)];
+    for i in range(-30i, 30) {
+        for j in range(-30i, 30) {
+            points.push((i as f32 * 0.37, j as f32 * -0.23));
+        }
+    }
+
+    for &seed in [0u, 7, 200].iter() {
+        for &angle in [None, Some(1.3f32)].iter() {
+            let mut program = shader::Program::new();
+            let x = program.push(shader::InputX);
+            let y = program.push(shader::InputY);
+            let output = program.push(match angle {
+                Some(angle) => shader::FlowSimplex(x, y, seed, angle),
+                None => shader::Simplex(x, y, seed)
+            });
+            program.output = output;
+
+            let glsl = shading::Shader::parse(
+                program.to_glsl("noise").as_slice());
+            let wgsl = shading::Shader::parse(
+                program.to_wgsl("noise").as_slice());
+            for &(x, y) in points.iter() {
+                let expected = match angle {
+                    Some(angle) => sflow_2d(x, y, seed, angle),
+                    None => snoise_2d(x, y, seed)
+                };
+                assert!((glsl.sample("noise", x, y) - expected).abs() < 1e-5);
+                assert!((wgsl.sample("noise", x, y) - expected).abs() < 1e-5);
+            }
+        }
+    }
+}
+
+/// Blends between two chains of modifiers over a shared fractal, which
+/// compilation computes once.
+fn compiled_graph() -> BlendNoise {
+    let pink = SharedNoise::new(&PinkNoise::new(5));
+    let first = pink.scalebias(0.5, 0.5).scalebias(2.0, -1.0)
+        .translate(Vector2::new(0.5, 0.0)).translate(Vector2::new(0.0, 0.25));
+    let second = pink.clamp(-0.5, 0.5).scalebias(1.0, 0.0);
+    BlendNoise::new(&first, &second, &pink)
+}
+
+#[test]
+fn test_compiled() {
+    let noise = compiled_graph();
+    let compiled = noise.compile();
+    let program = shader::Program::compile(&noise).unwrap();
+    assert!(compiled.program.instructions.len() < program.instructions.len());
+    for i in range(-50i, 50) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        assert_eq!(compiled.generate_2d(x, y), noise.generate_2d(x, y));
+        assert_eq!(compiled.program.evaluate(x, y), program.evaluate(x, y));
+    }
+
+    // Chains of scales, biases and translations become single instructions,
+    // which still take each step in turn.
+    let fused = |tail: &[(f32, f32)]| {
+        compiled.program.instructions.iter().any(|instruction| {
+            match *instruction {
+                shader::ScaleBias(_, ref steps) => {
+                    steps.as_slice().ends_with(tail)
+                },
+                _ => false
+            }
+        })
+    };
+    assert!(fused([(0.5, 0.5), (2.0, -1.0)]));
+    assert!(fused([(1.0, 0.0), (1.0, 0.5)]));
+    assert!(fused([(1.0, 0.25), (1.0, 0.0)]));
+
+    // A reused buffer is cleared of the values of earlier samples.
+    let mut values = vec![1.0f32, 2.0, 3.0];
+    for i in range(-5i, 5) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        assert_eq!(program.evaluate_with(&mut values, x, y),
+                   program.evaluate(x, y));
+    }
+
+    // Sources that cannot be lowered are called instead.
+    let pink = PinkNoise::new(2);
+    let noise = BlendNoise::new(&pink.terrace(&[-1.0, 0.0, 1.0]),
+                                &pink.scalebias(0.5, 0.25),
+                                &CheckerboardNoise.scalebias(0.5, 0.0));
+    let compiled = noise.compile();
+    let calls = compiled.program.instructions.iter().filter(|instruction| {
+        match **instruction {
+            shader::CallModule(..) => true,
+            _ => false
+        }
+    }).count();
+    assert_eq!(calls, 2);
+    assert!(compiled.program.to_glsl("terrain").as_slice()
+            .contains("grunge_module_1(vec2("));
+    for i in range(-50i, 50) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        assert_eq!(compiled.generate_2d(x, y), noise.generate_2d(x, y));
+    }
+    assert!(shader::Program::compile(&noise).is_err());
+
+    // The errors of called modules are passed on.
+    let broken = PinkNoise { octaves: 1.0, .. PinkNoise::new(2) };
+    let broken = broken.terrace(&[-1.0, 0.0, 1.0]).compile();
+    assert!(broken.generate_2d(0.3, 0.7).is_err());
+}
+
+#[bench]
+fn bench_interpreted_graph(b: &mut test::Bencher) {
+    let noise = compiled_graph();
+    b.iter(|| noise.generate_2d(0.05, 0.05));
+}
+
+#[bench]
+fn bench_compiled_graph(b: &mut test::Bencher) {
+    let noise = compiled_graph().compile();
+    b.iter(|| noise.generate_2d(0.05, 0.05));
+}
+
+type UnboxedChain = Clamped<ScaledBiased<Rotated<Translated<CylinderNoise>>>>;
+
+/// The same chain of modifiers, built from boxed modules and by value.
+fn modifier_chains() -> (ClampedNoise, UnboxedChain) {
+    let source = CylinderNoise::new(2.0);
+    let translated = TranslatedNoise::new(&source, Vector2::new(0.5, 0.25));
+    let rotated = RotatedNoise::new(&translated, 0.3);
+    let scaled = ScaledBiasedNoise {
+        source: rotated.to_box(), scale: 0.5, bias: 0.5
+    };
+    let boxed = ClampedNoise { source: scaled.to_box(), min: 0.0, max: 1.0 };
+    let unboxed = source.translate(Vector2::new(0.5, 0.25)).rotate(0.3)
+        .scalebias(0.5, 0.5).clamp(0.0, 1.0);
+    (boxed, unboxed)
+}
+
+#[test]
+fn test_unboxed_modifiers() {
+    let (boxed, unboxed) = modifier_chains();
+    for i in range(-20i, 20) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        assert_eq!(boxed.generate_2d(x, y), unboxed.generate_2d(x, y));
+    }
+    assert_eq!(boxed.to_json(), unboxed.to_json());
+
+    // A boxed module is a module in its own right, so the methods of
+    // Modifiable build the same modifiers on it.
+    let source = CylinderNoise::new(2.0).to_box();
+    let clamped: ClampedNoise = source.clamp(0.0, 1.0);
+    let chained = source.translate(Vector2::new(0.5, 0.25)).rotate(0.3)
+        .scalebias(0.5, 0.5).clamp(0.0, 1.0);
+    for i in range(-20i, 20) {
+        let (x, y) = (i as f32 * 0.37, i as f32 * -0.21);
+        assert_eq!(chained.generate_2d(x, y), unboxed.generate_2d(x, y));
+    }
+    assert_eq!(chained.to_json(), unboxed.to_json());
+    assert_eq!(clamped.bounds(), (0.0, 1.0));
+}
+
+#[bench]
+fn bench_boxed_modifiers(b: &mut test::Bencher) {
+    let (noise, _) = modifier_chains();
+    b.iter(|| noise.generate_2d(0.05, 0.05));
+}
+
+#[bench]
+fn bench_unboxed_modifiers(b: &mut test::Bencher) {
+    let (_, noise) = modifier_chains();
+    b.iter(|| noise.generate_2d(0.05, 0.05));
+}
+
+#[test]
+fn test_closures() {
+    let (scale, offset) = (0.5f32, 0.25f32);
+    let plane = FunctionNoise::new(move |x: f32, y: f32| Ok(scale * x + y));
+    let shifted = ModifierNoise::new(&plane, move |_: f32, _: f32, out: f32| {
+        if out.is_finite() { Ok(out + offset) } else { Err("Not finite.") }
+    });
+
+    let boxed = shifted.to_box();
+    assert_eq!(plane.clone().generate_2d(2.0, 1.0), Ok(2.0));
+    assert_eq!(boxed.generate_2d(2.0, 1.0), Ok(2.25));
+    assert!(boxed.generate_2d(Float::infinity(), 0.0).is_err());
+}
+
+#[test]
+fn test_registry() {
+    let mut registry = serial::Registry::new();
+    let slope = 0.5f32;
+    registry.register_function("plane", move |x: f32, y: f32| {
+        Ok(slope * x + y)
+    });
+    registry.register_modifier("square", |_: f32, _: f32, out: f32| {
+        Ok(out * out)
+    });
+
+    let plane = registry.function("plane").unwrap();
+    let shifted = plane.translate(Vector2::new(1.0, 0.0));
+    let noise = registry.modifier("square", &shifted).unwrap()
+        .scalebias(2.0, 0.0);
+    let json = noise.to_json().unwrap();
+    let loaded = registry.from_json(&json).unwrap();
+    assert_eq!(loaded.generate_2d(1.0, 1.0), Ok(8.0));
+    assert_eq!(loaded.to_json(), Ok(json.clone()));
+
+    let text = "scalebias(modifier(translate(function(plane), [1, 0]), \
+                square), 2, 0)";
+    let compiled = expr::compile_with(text, &registry).unwrap();
+    assert_eq!(compiled.generate_2d(1.0, 1.0), Ok(8.0));
+    assert_eq!(compiled.to_json(), Ok(json.clone()));
+    let err = expr::compile_with("modifier(pink(), cube)", &registry)
+        .err().unwrap();
+    assert_eq!((err.kind, err.span.start, err.span.end),
+               (expr::UnknownFunction("cube".to_string()), 17, 21));
+    let err = expr::compile("function(plane)").err().unwrap();
+    assert_eq!((err.kind, err.span.start, err.span.end),
+               (expr::UnknownFunction("plane".to_string()), 9, 14));
+
+    assert!(FunctionNoise::new(|x: f32, _: f32| Ok(x)).to_json().is_err());
+    assert_eq!(serial::from_json(&json).err(),
+               Some(serial::UnknownFunction("square".to_string())));
+}
+
+#[test]
+fn test_terrace_points() {
+    let pink = PinkNoise::new(0);
+    let nan: f32 = Float::nan();
+    let terrace = pink.terrace(&[0.5, nan, -0.5, Float::infinity(), 0.5]);
+    assert_eq!(terrace.points, vec![-0.5, 0.5]);
+    assert!(pink.terrace(&[nan]).generate_2d(0.3, 0.7).is_err());
+}
+
+#[test]
+fn test_terrace() {
+    let points = [-1.0, 0.0, 0.5, 1.0];
+    let terrace = |value: f32, invert: bool, smoothness: f32| {
+        let mut terrace = ConstNoise::new(value).terrace(&points);
+        terrace.invert = invert;
+        terrace.smoothness = smoothness;
+        terrace.generate_2d(0.3, 0.7).unwrap()
+    };
+
+    for &point in points.iter() {
+        for &smoothness in [0.0, 0.25, 0.5, 1.0].iter() {
+            assert_eq!(terrace(point, false, smoothness), point);
+            assert_eq!(terrace(point, true, smoothness), point);
+        }
+    }
+
+    // Within the terrace from 0.0 to 0.5, inverting the curve turns it upside
+    // down, and lower smoothness flattens the start of each terrace further.
+    for i in range(1i, 10) {
+        let value = i as f32 * 0.05;
+        let (hard, sharp) = (terrace(value, false, 0.0),
+                             terrace(value, false, 0.25));
+        let (smooth, linear) = (terrace(value, false, 0.5),
+                                terrace(value, false, 1.0));
+        assert_eq!(hard, 0.0);
+        assert!(sharp < smooth && smooth < value);
+        assert!((linear - value).abs() < 1e-6);
+
+        assert_eq!(terrace(value, true, 0.0), 0.5);
+        let inverted = terrace(value, true, 0.5);
+        assert!(inverted > value);
+        assert!((inverted - (0.5 - terrace(0.5 - value, false, 0.5))).abs()
+                < 1e-6);
+    }
+}
+
+/// Samples a module over a grid, checking that its output lies within its
+/// bounds, and returns the mean output.
+fn sampled_mean(noise: &NoiseModule) -> f32 {
+    let (lower, upper) = noise.bounds();
+    let mut sum = 0.0;
+    for i in range(0i, 40) {
+        for j in range(0i, 40) {
+            let (x, y) = (i as f32 * 0.173, j as f32 * 0.219);
+            let value = noise.generate_2d(x, y).unwrap();
+            assert!(value >= lower && value <= upper);
+            sum += value;
+        }
+    }
+    sum / 1600.0
+}
+
+#[test]
+fn test_turbulence_ranges() {
+    let mut swiss = SwissTurbulenceNoise::new(1);
+    let mut jordan = JordanTurbulenceNoise::new(2);
+    sampled_mean(&swiss);
+    sampled_mean(&jordan);
+
+    swiss.normalize = true;
+    jordan.normalize = true;
+    sampled_mean(&swiss);
+    sampled_mean(&jordan);
+}
+
+#[test]
+fn test_multifractal_ranges() {
+    let mut hybrid = HybridMultifractalNoise::new(1);
+    let mut hetero = HeteroTerrainNoise::new(2);
+    assert!(sampled_mean(&hybrid).abs() < 0.5);
+    assert!(sampled_mean(&hetero).abs() < 0.5);
+
+    hybrid.normalize = true;
+    hetero.normalize = true;
+    assert_eq!(hybrid.bounds(), (-1.0, 1.0));
+    assert_eq!(hetero.bounds(), (-1.0, 1.0));
+    sampled_mean(&hybrid);
+    sampled_mean(&hetero);
+}
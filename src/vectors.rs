@@ -2,6 +2,9 @@
 	This file is part of grunge, a coherent noise generation library.
 */
 
-//! Re-exports of relevant `cgmath` vector implementations.
+//! Re-exports of relevant `cgmath` vector, matrix and quaternion
+//! implementations.
 
 pub use cgmath::vector::{Vector, Vector2, Vector3, dot};
+pub use cgmath::matrix::{Matrix, Matrix3, Matrix4};
+pub use cgmath::quaternion::Quaternion;